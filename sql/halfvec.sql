@@ -0,0 +1,12 @@
+-- Store transaction embeddings at half precision (EMBEDDING_STORAGE_PRECISION=f16).
+-- Requires pgvector 0.7+. Run once, then restart with the new setting.
+
+DROP INDEX IF EXISTS idx_transactions_embedding;
+
+ALTER TABLE transactions
+    ALTER COLUMN transaction_embedding TYPE halfvec(768)
+    USING transaction_embedding::halfvec(768);
+
+CREATE INDEX IF NOT EXISTS idx_transactions_embedding ON transactions
+    USING ivfflat (transaction_embedding halfvec_cosine_ops)
+    WITH (lists = 100);
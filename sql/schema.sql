@@ -12,6 +12,10 @@ CREATE TABLE IF NOT EXISTS users (
     home_location JSONB
 );
 
+-- Spending profile: running centroid of the user's transaction embeddings
+ALTER TABLE users ADD COLUMN IF NOT EXISTS spending_centroid vector(768);
+ALTER TABLE users ADD COLUMN IF NOT EXISTS centroid_count INTEGER DEFAULT 0;
+
 -- Transactions table
 CREATE TABLE IF NOT EXISTS transactions (
     transaction_id TEXT PRIMARY KEY,
@@ -53,6 +57,33 @@ CREATE INDEX IF NOT EXISTS idx_transactions_embedding ON transactions
     WITH (lists = 100);
 CREATE INDEX IF NOT EXISTS idx_transactions_tsv ON transactions USING gin(description_tsv);
 
+-- Free-form payment attributes (channel, session_id, card BIN, ...) for investigation
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS metadata JSONB;
+CREATE INDEX IF NOT EXISTS idx_transactions_metadata ON transactions USING gin(metadata jsonb_path_ops);
+
+-- Acquirer's merchant id, stable across renames of the merchant name
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS merchant_id TEXT;
+CREATE INDEX IF NOT EXISTS idx_transactions_merchant_id ON transactions(merchant_id);
+
+-- Every scoring event; transactions.decision only holds the latest one
+CREATE TABLE IF NOT EXISTS analysis_results (
+    id BIGSERIAL PRIMARY KEY,
+    transaction_id TEXT NOT NULL,
+    user_id TEXT,
+    decision TEXT NOT NULL,
+    risk_score DOUBLE PRECISION,
+    confidence DOUBLE PRECISION,
+    result JSONB NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+ALTER TABLE analysis_results ADD COLUMN IF NOT EXISTS model_version TEXT;
+-- Decision forced by the operator override, `decision` stays the scored one
+ALTER TABLE analysis_results ADD COLUMN IF NOT EXISTS override_decision TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_analysis_results_transaction ON analysis_results(transaction_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_analysis_results_user ON analysis_results(user_id, created_at);
+
 -- Merchants table
 CREATE TABLE IF NOT EXISTS merchants (
     merchant_id SERIAL PRIMARY KEY,
@@ -69,6 +100,41 @@ CREATE INDEX IF NOT EXISTS idx_merchants_embedding ON merchants
     USING ivfflat (merchant_embedding vector_cosine_ops)
     WITH (lists = 100);
 
+-- Acquirer's id for the merchant (transactions.merchant_id). merchant_id
+-- above is only the internal key
+ALTER TABLE merchants ADD COLUMN IF NOT EXISTS acquirer_merchant_id TEXT UNIQUE;
+
+-- Merchants blocked outright, whatever the score. Names stored lowercase
+CREATE TABLE IF NOT EXISTS merchant_denylist (
+    merchant_name TEXT PRIMARY KEY,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Per-user special handling: a forced decision and/or a score multiplier
+CREATE TABLE IF NOT EXISTS user_overrides (
+    user_id TEXT PRIMARY KEY,
+    action TEXT CHECK (action IN ('APPROVE', 'CHALLENGE', 'BLOCK')),
+    risk_multiplier DOUBLE PRECISION CHECK (risk_multiplier >= 0),
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (action IS NOT NULL OR risk_multiplier IS NOT NULL)
+);
+
+-- Step-up authentication results for challenged transactions
+CREATE TABLE IF NOT EXISTS challenge_outcomes (
+    id BIGSERIAL PRIMARY KEY,
+    transaction_id TEXT NOT NULL REFERENCES transactions(transaction_id),
+    user_id TEXT NOT NULL,
+    merchant TEXT NOT NULL,
+    outcome TEXT NOT NULL CHECK (outcome IN ('passed', 'failed', 'abandoned')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_challenge_outcomes_user_merchant ON challenge_outcomes(user_id, merchant, created_at);
+-- One outcome per challenged transaction
+CREATE UNIQUE INDEX IF NOT EXISTS idx_challenge_outcomes_transaction ON challenge_outcomes(transaction_id);
+
 -- Appeals table
 CREATE TABLE IF NOT EXISTS appeals (
     appeal_id SERIAL PRIMARY KEY,
@@ -0,0 +1,58 @@
+//! Bearer-token guard for the `/api/admin` routes.
+//!
+//! Admin endpoints change how every transaction is decided (the kill switch,
+//! user overrides, the denylist, the model), so they need
+//! `Authorization: Bearer <ADMIN_API_TOKEN>`. Without a configured token
+//! they are disabled rather than open.
+
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{AppState, error::ApiError};
+
+/// Middleware for the admin router
+pub async fn require_admin(State(state): State<AppState>, request: Request, next: Next) -> Result<Response, ApiError> {
+    let Some(expected) = state.config.admin_api_token.as_deref() else {
+        return Err(ApiError::Forbidden(
+            "Admin endpoints are disabled: set ADMIN_API_TOKEN".to_string(),
+        ));
+    };
+
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match presented {
+        Some(token) if tokens_match(token.trim(), expected) => Ok(next.run(request).await),
+        _ => Err(ApiError::Unauthorized("Missing or invalid admin token".to_string())),
+    }
+}
+
+/// Compared as digests, in constant time whatever the lengths
+fn tokens_match(presented: &str, expected: &str) -> bool {
+    let (presented, expected) = (Sha256::digest(presented), Sha256::digest(expected));
+    presented
+        .iter()
+        .zip(expected.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_exact_token_matches() {
+        assert!(tokens_match("s3cret", "s3cret"));
+        assert!(!tokens_match("s3cre", "s3cret"));
+        assert!(!tokens_match("", "s3cret"));
+        assert!(!tokens_match("S3CRET", "s3cret"));
+    }
+}
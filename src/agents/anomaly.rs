@@ -1,15 +1,164 @@
 use sqlx::PgPool;
 use anyhow::Result;
-use chrono::{ Timelike, Utc};
+use chrono::{FixedOffset, Timelike};
 
-use crate::models::transaction::{AgentScore, Transaction};
+use crate::config::{self, ConfigError, ConfigSource};
+use crate::i18n::{self, Locale};
+use crate::models::transaction::{AgentScore, Reason, ReasonCode, Transaction};
 
 
-pub struct AnomalyAgent;
+/// Timezone the unusual-hours window is interpreted in
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub enum HourTimezone {
+    /// Fixed offset from UTC, in seconds
+    Fixed(i32),
+    /// Approximate local time from the transaction's longitude (15° per hour)
+    FromLocation,
+}
+
+impl std::str::FromStr for HourTimezone {
+    type Err = String;
+
+    /// Accepts `UTC`, `location`, or an offset such as `+05:30` / `-08:00`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("utc") || value == "Z" {
+            return Ok(HourTimezone::Fixed(0));
+        }
+        if value.eq_ignore_ascii_case("location") {
+            return Ok(HourTimezone::FromLocation);
+        }
+
+        let (sign, rest) = match value.chars().next() {
+            Some('+') => (1, &value[1..]),
+            Some('-') => (-1, &value[1..]),
+            _ => return Err(format!("Invalid timezone '{}'", value)),
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let hours: i32 = hours.parse().map_err(|_| format!("Invalid timezone '{}'", value))?;
+        let minutes: i32 = minutes.parse().map_err(|_| format!("Invalid timezone '{}'", value))?;
+        if hours > 14 || minutes > 59 {
+            return Err(format!("Invalid timezone '{}'", value));
+        }
+
+        Ok(HourTimezone::Fixed(sign * (hours * 3600 + minutes * 60)))
+    }
+}
+
+/// Tunables for the anomaly agent
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AnomalyConfig {
+    /// First hour (inclusive) of the unusual-hours window
+    pub night_start_hour: u32,
+    /// Hour (exclusive) the window ends at, may wrap past midnight (e.g. 22 -> 5)
+    pub night_end_hour: u32,
+    pub timezone: HourTimezone,
+    /// Risk added for a user's first transaction from a device fingerprint
+    pub new_device_risk: f64,
+    /// Most recent transactions (last 24h) fetched for the spike and succession checks
+    pub recent_transactions_limit: i64,
+    /// Maximum extra risk for a large amount at an unusual hour, on top of
+    /// what either adds alone. Scales with how high the amount ranks in the
+    /// user's history above the median
+    pub joint_amount_hour_weight: f64,
+    /// Risk added for an exact multiple of `round_amount_unit` at or above
+    /// `round_amount_threshold`, 0 turns the check off. Only half applies
+    /// unless it's the user's first transaction or from a new device
+    pub round_amount_risk: f64,
+    pub round_amount_threshold: f64,
+    pub round_amount_unit: f64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            night_start_hour: 2,
+            night_end_hour: 6,
+            timezone: HourTimezone::Fixed(0),
+            new_device_risk: 0.2,
+            recent_transactions_limit: 20,
+            joint_amount_hour_weight: 0.2,
+            round_amount_risk: 0.0,
+            round_amount_threshold: 100.0,
+            round_amount_unit: 100.0,
+        }
+    }
+}
+
+impl AnomalyConfig {
+    /// Read `ANOMALY_NIGHT_START_HOUR`, `ANOMALY_NIGHT_END_HOUR`, `ANOMALY_TIMEZONE`,
+    /// `ANOMALY_NEW_DEVICE_RISK`, `ANOMALY_RECENT_TRANSACTIONS_LIMIT`,
+    /// `ANOMALY_JOINT_AMOUNT_HOUR_WEIGHT` and `ANOMALY_ROUND_AMOUNT_*`
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            night_start_hour: config::parsed(source, "ANOMALY_NIGHT_START_HOUR", defaults.night_start_hour)?,
+            night_end_hour: config::parsed(source, "ANOMALY_NIGHT_END_HOUR", defaults.night_end_hour)?,
+            timezone: config::parsed(source, "ANOMALY_TIMEZONE", defaults.timezone)?,
+            new_device_risk: config::parsed(source, "ANOMALY_NEW_DEVICE_RISK", defaults.new_device_risk)?,
+            recent_transactions_limit: config::parsed(
+                source,
+                "ANOMALY_RECENT_TRANSACTIONS_LIMIT",
+                defaults.recent_transactions_limit,
+            )?,
+            joint_amount_hour_weight: config::parsed(
+                source,
+                "ANOMALY_JOINT_AMOUNT_HOUR_WEIGHT",
+                defaults.joint_amount_hour_weight,
+            )?,
+            round_amount_risk: config::parsed(source, "ANOMALY_ROUND_AMOUNT_RISK", defaults.round_amount_risk)?,
+            round_amount_threshold: config::parsed(
+                source,
+                "ANOMALY_ROUND_AMOUNT_THRESHOLD",
+                defaults.round_amount_threshold,
+            )?,
+            round_amount_unit: config::parsed(source, "ANOMALY_ROUND_AMOUNT_UNIT", defaults.round_amount_unit)?,
+        })
+    }
+
+    fn is_unusual_hour(&self, hour: u32) -> bool {
+        if self.night_start_hour <= self.night_end_hour {
+            hour >= self.night_start_hour && hour < self.night_end_hour
+        } else {
+            hour >= self.night_start_hour || hour < self.night_end_hour
+        }
+    }
+
+    /// Exact multiple of the round unit at or above the threshold, to the cent
+    fn is_round_amount(&self, amount: f64) -> bool {
+        if self.round_amount_risk <= 0.0 || amount < self.round_amount_threshold {
+            return false;
+        }
+        let cents = (amount * 100.0).round() as i64;
+        let unit_cents = (self.round_amount_unit * 100.0).round() as i64;
+        unit_cents > 0 && cents % unit_cents == 0
+    }
+}
+
+pub struct AnomalyAgent {
+    config: AnomalyConfig,
+}
 
 impl AnomalyAgent {
     pub fn new() -> Self {
-        Self
+        Self::with_config(AnomalyConfig::default())
+    }
+
+    pub fn with_config(config: AnomalyConfig) -> Self {
+        Self { config }
+    }
+
+    /// Offset used to turn the transaction timestamp into a local hour
+    fn utc_offset_secs(&self, transaction: &Transaction) -> i32 {
+        match self.config.timezone {
+            HourTimezone::Fixed(secs) => secs,
+            // Without a location there's nothing to estimate from, fall back to UTC
+            HourTimezone::FromLocation => transaction
+                .location
+                .as_ref()
+                .map(|l| ((l.lon / 15.0).round() as i32).clamp(-12, 14) * 3600)
+                .unwrap_or(0),
+        }
     }
     
     /// Detect anomalies in transaction timing, frequency, and amount patterns
@@ -21,35 +170,62 @@ impl AnomalyAgent {
         tracing::info!("🔍 Anomaly Agent analyzing {}", transaction.transaction_id);
         
         // Get user's recent transaction history
-        let recent_txns = self.get_recent_transactions(pool, &transaction.user_id).await?;
+        let recent_txns = self.get_recent_transactions(pool, transaction).await?;
         
         let mut risk_score: f64 = 0.0;
         let mut reasons = Vec::new();
         
-        // 1. Check transaction frequency (velocity)
-        let txns_last_hour = recent_txns.iter()
-            .filter(|t| t.minutes_ago <= 60.0)
-            .count();
+        // 1. Check transaction frequency (velocity), counted in the database:
+        // the fetched history is capped and would cap the count with it
+        let txns_last_hour = self.count_transactions_last_hour(pool, transaction).await?;
         
         if txns_last_hour >= 5 {
             risk_score += 0.3;
-            reasons.push(format!("{} transactions in last hour (high velocity)", txns_last_hour));
+            reasons.push(Reason::new(ReasonCode::HighVelocity).with("count", txns_last_hour));
         } else if txns_last_hour >= 3 {
             risk_score += 0.15;
         }
         
-        // 2. Check unusual time (late night transactions)
-        let hour = Utc::now().time().hour();  // Fixed: use .time().hour()
-        if hour >= 2 && hour <= 5 {
+        // 2. Check unusual time (late night transactions), in the configured
+        // timezone and at the time the transaction actually happened
+        let offset_secs = self.utc_offset_secs(transaction);
+        let hour = FixedOffset::east_opt(offset_secs)
+            .map(|offset| transaction.timestamp.with_timezone(&offset).hour())
+            .unwrap_or_else(|| transaction.timestamp.hour());
+        let unusual_hour = self.config.is_unusual_hour(hour);
+        if unusual_hour {
             risk_score += 0.2;
-            reasons.push(format!("Transaction at unusual hour: {}:00", hour));
+            reasons.push(Reason::new(ReasonCode::UnusualHour).with("hour", hour));
+        }
+
+        // 2b. A large amount at an unusual hour is worse than the two
+        // separately, the interaction term only exists when both hold
+        let amount_rank = if unusual_hour {
+            self.get_amount_rank(pool, transaction).await?
+        } else {
+            None
+        };
+        let amount_factor = amount_rank.map(|rank| ((rank - 0.5) / 0.5).clamp(0.0, 1.0)).unwrap_or(0.0);
+        let joint_amount_hour = self.config.joint_amount_hour_weight * amount_factor;
+        if joint_amount_hour > 0.0 {
+            risk_score += joint_amount_hour;
+            if amount_factor >= 0.5 {
+                reasons.push(
+                    Reason::new(ReasonCode::LargeAmountAtUnusualHour)
+                        .with("percentile", format!("{:.0}", amount_rank.unwrap_or(0.0) * 100.0))
+                        .with("hour", hour),
+                );
+            }
         }
         
         // 3. Check for rapid successive transactions
         if let Some(last_txn) = recent_txns.first() {
             if last_txn.minutes_ago < 5.0 {
                 risk_score += 0.25;
-                reasons.push(format!("Transaction only {:.0} minutes after previous", last_txn.minutes_ago));
+                reasons.push(
+                    Reason::new(ReasonCode::RapidSuccession)
+                        .with("minutes", format!("{:.0}", last_txn.minutes_ago)),
+                );
             }
         }
         
@@ -61,16 +237,49 @@ impl AnomalyAgent {
             
             if transaction.amount > avg_amount * 3.0 {
                 risk_score += 0.25;
-                reasons.push(format!("Amount ${:.2} is 3x recent average ${:.2}", transaction.amount, avg_amount));
+                reasons.push(
+                    Reason::new(ReasonCode::AmountSpike)
+                        .with("amount", format!("{:.2}", transaction.amount))
+                        .with("average", format!("{:.2}", avg_amount)),
+                );
             }
         }
         
+        // 5. Check for a device this user has never transacted from. Users
+        // without any history have nothing to compare against
+        let device_history = self.get_device_history(pool, transaction).await?;
+        let new_device = device_history.user_transactions > 0 && !device_history.device_seen;
+        if new_device {
+            risk_score += self.config.new_device_risk;
+            reasons.push(Reason::new(ReasonCode::NewDevice));
+        }
+
+        // 6. Optional: card testers like round amounts, most telling when
+        // nothing else is known about the user or the device
+        let round_amount = self.config.is_round_amount(transaction.amount);
+        let first_transaction = device_history.user_transactions == 0;
+        let round_amount_risk = match (round_amount, first_transaction || new_device) {
+            (false, _) => 0.0,
+            (true, true) => self.config.round_amount_risk,
+            (true, false) => self.config.round_amount_risk * 0.5,
+        };
+        if round_amount {
+            risk_score += round_amount_risk;
+            reasons.push(
+                Reason::new(ReasonCode::RoundAmount).with("amount", format!("{:.2}", transaction.amount)),
+            );
+        }
+        
         risk_score = risk_score.clamp(0.0, 1.0);
         
+        // Without any history the spike and new-device checks can't fire,
+        // only timing is left to go on
+        let confidence = if device_history.user_transactions == 0 { 0.5 } else { 1.0 };
+        
         let reason = if reasons.is_empty() {
             "Normal transaction timing and frequency".to_string()
         } else {
-            reasons.join("; ")
+            i18n::render_all(Locale::English, &reasons)
         };
         
         tracing::info!("✅ Anomaly Agent: {:.2} - {}", risk_score, reason);
@@ -78,41 +287,142 @@ impl AnomalyAgent {
         Ok(AgentScore {
             risk_score,
             reason,
+            reasons,
+            confidence,
+            data_available: device_history.user_transactions > 0,
+            data_note: (device_history.user_transactions == 0)
+                .then(|| "No earlier transactions for this user".to_string()),
             details: serde_json::json!({
                 "transactions_last_hour": txns_last_hour,
                 "hour_of_day": hour,
-                "recent_transaction_count": recent_txns.len()
+                "amount_rank": amount_rank,
+                "joint_amount_hour": joint_amount_hour,
+                "utc_offset_secs": offset_secs,
+                "recent_transaction_count": recent_txns.len(),
+                "new_device": new_device,
+                "round_amount": round_amount,
+                "round_amount_risk": round_amount_risk,
             }),
+            embedding: None,
         })
     }
     
+    /// The user's transactions in the 24 hours before this one. Windows are
+    /// anchored on the transaction's own time, so a replayed or queued
+    /// transaction sees the history it had when it happened
     async fn get_recent_transactions(
         &self,
         pool: &PgPool,
-        user_id: &str,
+        transaction: &Transaction,
     ) -> Result<Vec<RecentTransaction>> {
         let txns = sqlx::query_as::<_, RecentTransaction>(
             r#"
             SELECT 
                 amount::float8 as amount,
-                EXTRACT(EPOCH FROM (NOW() - timestamp)) / 60 as minutes_ago
+                EXTRACT(EPOCH FROM ($2::timestamptz - timestamp)) / 60 as minutes_ago
             FROM transactions
             WHERE user_id = $1
-            AND timestamp > NOW() - INTERVAL '24 hours'
+            AND timestamp > $2::timestamptz - INTERVAL '24 hours'
+            AND timestamp <= $2::timestamptz
+            AND transaction_id != $3
             ORDER BY timestamp DESC
-            LIMIT 20
+            LIMIT $4
             "#
         )
-        .bind(user_id)
+        .bind(&transaction.user_id)
+        .bind(transaction.timestamp)
+        .bind(&transaction.transaction_id)
+        .bind(self.config.recent_transactions_limit)
         .fetch_all(pool)
         .await?;
         
         Ok(txns)
     }
+    
+    async fn count_transactions_last_hour(
+        &self,
+        pool: &PgPool,
+        transaction: &Transaction,
+    ) -> Result<i64> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*)
+            FROM transactions
+            WHERE user_id = $1
+            AND timestamp > $2::timestamptz - INTERVAL '1 hour'
+            AND timestamp <= $2::timestamptz
+            AND transaction_id != $3
+            "#
+        )
+        .bind(&transaction.user_id)
+        .bind(transaction.timestamp)
+        .bind(&transaction.transaction_id)
+        .fetch_one(pool)
+        .await?;
+        
+        Ok(count)
+    }
+    
+    /// Share of the user's earlier transactions with a smaller amount, `None`
+    /// without history
+    async fn get_amount_rank(
+        &self,
+        pool: &PgPool,
+        transaction: &Transaction,
+    ) -> Result<Option<f64>> {
+        let rank = sqlx::query_scalar::<_, Option<f64>>(
+            r#"
+            SELECT AVG(CASE WHEN amount < $2 THEN 1.0 ELSE 0.0 END)::float8
+            FROM transactions
+            WHERE user_id = $1
+            AND transaction_id != $3
+            AND timestamp < $4::timestamptz
+            "#
+        )
+        .bind(&transaction.user_id)
+        .bind(transaction.amount)
+        .bind(&transaction.transaction_id)
+        .bind(transaction.timestamp)
+        .fetch_one(pool)
+        .await?;
+        
+        Ok(rank)
+    }
+    
+    /// Whether the user has transacted before, and from this device. The
+    /// transaction itself is excluded so re-scoring gives the same answer
+    async fn get_device_history(
+        &self,
+        pool: &PgPool,
+        transaction: &Transaction,
+    ) -> Result<DeviceHistory> {
+        let history = sqlx::query_as::<_, DeviceHistory>(
+            r#"
+            SELECT 
+                COUNT(*) as user_transactions,
+                COALESCE(BOOL_OR(device_fingerprint = $2), false) as device_seen
+            FROM transactions
+            WHERE user_id = $1
+            AND transaction_id != $3
+            "#
+        )
+        .bind(&transaction.user_id)
+        .bind(&transaction.device_fingerprint)
+        .bind(&transaction.transaction_id)
+        .fetch_one(pool)
+        .await?;
+        
+        Ok(history)
+    }
 }
 
 #[derive(sqlx::FromRow, Debug)]
 struct RecentTransaction {
     amount: f64,
     minutes_ago: f64,
-}
\ No newline at end of file
+}
+#[derive(sqlx::FromRow, Debug)]
+struct DeviceHistory {
+    user_transactions: i64,
+    device_seen: bool,
+}
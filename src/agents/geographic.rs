@@ -2,15 +2,210 @@
 
 use sqlx::PgPool;
 use anyhow::Result;
+use std::collections::HashSet;
 
-use crate::models::transaction::{AgentScore, Location, Transaction};
+use crate::agents::pattern::parse_category_list;
+use crate::config::{self, ConfigError, ConfigSource};
+use crate::i18n::{self, Locale};
+use crate::models::transaction::{AgentScore, Location, Reason, ReasonCode, Transaction};
 
+/// How the card was used, which decides how much location means
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    /// No physical presence, location is the billing/IP guess at best
+    Online,
+    /// Card physically at the merchant, location is where the card is
+    CardPresent,
+    Unknown,
+}
+
+/// Tunables for the geographic agent
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GeographicConfig {
+    /// Categories treated as online purchases (lowercase)
+    #[serde(serialize_with = "config::sorted_set")]
+    pub online_categories: HashSet<String>,
+    /// Payment methods treated as online purchases (lowercase)
+    #[serde(serialize_with = "config::sorted_set")]
+    pub online_payment_methods: HashSet<String>,
+    /// Categories treated as card-present purchases (lowercase)
+    #[serde(serialize_with = "config::sorted_set")]
+    pub card_present_categories: HashSet<String>,
+    /// Payment methods treated as card-present purchases (lowercase)
+    #[serde(serialize_with = "config::sorted_set")]
+    pub card_present_payment_methods: HashSet<String>,
+    /// Multiplier on location signals (unknown location, new country, travel) for online purchases
+    pub online_location_weight: f64,
+    /// Multiplier on impossible-travel signals for card-present purchases
+    pub card_present_travel_weight: f64,
+    /// Risk for a transaction that carries no location at all
+    pub missing_location_risk: f64,
+    /// Most recent locations (last 7 days) fetched for the travel and new-country checks
+    pub recent_locations_limit: i64,
+    /// Distance discounted before travel speed is judged, for city-level
+    /// coordinates (neighbouring cities share a location) and geolocation error.
+    /// Each location's own `accuracy_km` is discounted on top
+    pub travel_distance_tolerance_km: f64,
+    /// Most of a location's own `accuracy_km` that is discounted. A client
+    /// could otherwise send a huge accuracy and never be judged for travel
+    pub max_location_accuracy_km: f64,
+    /// Fastest plausible average speed between locations, a flight's cruise speed
+    pub max_travel_speed_kmh: f64,
+    /// Time lost getting to, through and from airports on any flight
+    pub travel_transit_buffer_hours: f64,
+    /// Fastest plausible speed by road, covers trips too short to fly
+    pub max_ground_speed_kmh: f64,
+    /// Most recent location older than this isn't compared for travel
+    /// speed: days of gap make any trip look plausible. 0 compares any age
+    pub travel_max_comparison_age_hours: f64,
+}
+
+impl Default for GeographicConfig {
+    fn default() -> Self {
+        Self {
+            online_categories: parse_category_list("online,digital_goods,software,subscriptions,streaming"),
+            online_payment_methods: parse_category_list("online,card_not_present,paypal"),
+            card_present_categories: parse_category_list("gas,groceries,food,restaurants,retail,hotels"),
+            card_present_payment_methods: parse_category_list("chip,contactless,swipe,card_present,pos"),
+            online_location_weight: 0.25,
+            card_present_travel_weight: 1.5,
+            missing_location_risk: 0.1,
+            recent_locations_limit: 10,
+            travel_distance_tolerance_km: 25.0,
+            max_location_accuracy_km: 50.0,
+            max_travel_speed_kmh: 900.0,
+            travel_transit_buffer_hours: 2.0,
+            max_ground_speed_kmh: 120.0,
+            travel_max_comparison_age_hours: 48.0,
+        }
+    }
+}
+
+impl GeographicConfig {
+    /// Read the `GEO_*` settings
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            online_categories: config::list(source, "GEO_ONLINE_CATEGORIES", defaults.online_categories),
+            online_payment_methods: config::list(
+                source,
+                "GEO_ONLINE_PAYMENT_METHODS",
+                defaults.online_payment_methods,
+            ),
+            card_present_categories: config::list(
+                source,
+                "GEO_CARD_PRESENT_CATEGORIES",
+                defaults.card_present_categories,
+            ),
+            card_present_payment_methods: config::list(
+                source,
+                "GEO_CARD_PRESENT_PAYMENT_METHODS",
+                defaults.card_present_payment_methods,
+            ),
+            online_location_weight: config::parsed(
+                source,
+                "GEO_ONLINE_LOCATION_WEIGHT",
+                defaults.online_location_weight,
+            )?,
+            card_present_travel_weight: config::parsed(
+                source,
+                "GEO_CARD_PRESENT_TRAVEL_WEIGHT",
+                defaults.card_present_travel_weight,
+            )?,
+            missing_location_risk: config::parsed(
+                source,
+                "GEO_MISSING_LOCATION_RISK",
+                defaults.missing_location_risk,
+            )?,
+            recent_locations_limit: config::parsed(
+                source,
+                "GEO_RECENT_LOCATIONS_LIMIT",
+                defaults.recent_locations_limit,
+            )?,
+            travel_distance_tolerance_km: config::parsed(
+                source,
+                "GEO_TRAVEL_DISTANCE_TOLERANCE_KM",
+                defaults.travel_distance_tolerance_km,
+            )?,
+            max_location_accuracy_km: config::parsed(
+                source,
+                "GEO_MAX_LOCATION_ACCURACY_KM",
+                defaults.max_location_accuracy_km,
+            )?,
+            max_travel_speed_kmh: config::parsed(source, "GEO_MAX_TRAVEL_SPEED_KMH", defaults.max_travel_speed_kmh)?,
+            travel_transit_buffer_hours: config::parsed(
+                source,
+                "GEO_TRAVEL_TRANSIT_BUFFER_HOURS",
+                defaults.travel_transit_buffer_hours,
+            )?,
+            max_ground_speed_kmh: config::parsed(source, "GEO_MAX_GROUND_SPEED_KMH", defaults.max_ground_speed_kmh)?,
+            travel_max_comparison_age_hours: config::parsed(
+                source,
+                "GEO_TRAVEL_MAX_COMPARISON_AGE_HOURS",
+                defaults.travel_max_comparison_age_hours,
+            )?,
+        })
+    }
+
+    /// Payment method is the stronger hint, category is the fallback
+    pub fn channel(&self, transaction: &Transaction) -> Channel {
+        let payment_method = transaction.payment_method.to_lowercase();
+        if self.online_payment_methods.contains(&payment_method) {
+            return Channel::Online;
+        }
+        if self.card_present_payment_methods.contains(&payment_method) {
+            return Channel::CardPresent;
+        }
+
+        let category = transaction.merchant_category.to_lowercase();
+        if self.online_categories.contains(&category) {
+            Channel::Online
+        } else if self.card_present_categories.contains(&category) {
+            Channel::CardPresent
+        } else {
+            Channel::Unknown
+        }
+    }
+
+    /// Judge covering `distance_km` in `hours`. Impossible when it beats the
+    /// flight speed even with no time lost to transit, unlikely when it fits
+    /// neither a drive nor a flight with the transit buffer
+    pub fn travel_plausibility(&self, distance_km: f64, hours: f64) -> TravelPlausibility {
+        if distance_km <= 0.0 || distance_km <= self.max_ground_speed_kmh * hours {
+            TravelPlausibility::Plausible
+        } else if distance_km > self.max_travel_speed_kmh * hours {
+            TravelPlausibility::Impossible
+        } else if distance_km > self.max_travel_speed_kmh * (hours - self.travel_transit_buffer_hours) {
+            TravelPlausibility::Unlikely
+        } else {
+            TravelPlausibility::Plausible
+        }
+    }
+}
+
+/// Whether the trip since the last location could have been made
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TravelPlausibility {
+    Plausible,
+    /// Only with a flight and no time at the airports
+    Unlikely,
+    /// Faster than flying
+    Impossible,
+}
 
-pub struct GeographicAgent;
+pub struct GeographicAgent {
+    config: GeographicConfig,
+}
 
 impl GeographicAgent {
     pub fn new() -> Self {
-        Self
+        Self::with_config(GeographicConfig::default())
+    }
+
+    pub fn with_config(config: GeographicConfig) -> Self {
+        Self { config }
     }
     
     /// Validate transaction location against user's typical locations
@@ -20,45 +215,120 @@ impl GeographicAgent {
         transaction: &Transaction,
     ) -> Result<AgentScore> {
         tracing::info!("🔍 Geographic Agent analyzing {}", transaction.transaction_id);
+
+        // Online purchases carry no meaningful location, card-present ones
+        // make impossible travel much more telling
+        let channel = self.config.channel(transaction);
+        let (location_weight, travel_weight) = match channel {
+            Channel::Online => (self.config.online_location_weight, self.config.online_location_weight),
+            Channel::CardPresent => (1.0, self.config.card_present_travel_weight),
+            Channel::Unknown => (1.0, 1.0),
+        };
+
+        // Card-not-present flows may have no geolocation at all: nothing to
+        // check, but not quite as safe as a known location either
+        let Some(location) = transaction.location.as_ref() else {
+            let risk_score = (self.config.missing_location_risk * location_weight).clamp(0.0, 1.0);
+            let missing = Reason::new(ReasonCode::MissingLocation);
+            tracing::info!("✅ Geographic Agent: {:.2} - no location data", risk_score);
+            return Ok(AgentScore {
+                risk_score,
+                reason: i18n::render(Locale::English, &missing),
+                reasons: vec![missing],
+                confidence: 0.25,
+                data_available: false,
+                data_note: Some("Transaction carries no location".to_string()),
+                details: serde_json::json!({
+                    "current_location": null,
+                    "channel": channel,
+                    "location_weight": location_weight,
+                    "travel_weight": travel_weight,
+                }),
+                embedding: None,
+            });
+        };
         
         // Get user's recent locations
-        let recent_locations = self.get_recent_locations(pool, &transaction.user_id).await?;
+        let recent_locations = self.get_recent_locations(pool, transaction).await?;
         
         let mut risk_score:f64 = 0.0;
         let mut reasons = Vec::new();
         
         // 1. Check if location is unknown/suspicious
-        if transaction.location.country == "XX" || 
-           transaction.location.city == "Unknown" ||
-           (transaction.location.lat == 0.0 && transaction.location.lon == 0.0) {
-            risk_score += 0.4;
-            reasons.push("Unknown or suspicious location".to_string());
+        if location.country == "XX" || 
+           location.city == "Unknown" ||
+           (location.lat == 0.0 && location.lon == 0.0) {
+            risk_score += 0.4 * location_weight;
+            if channel != Channel::Online {
+                reasons.push(Reason::new(ReasonCode::UnknownLocation));
+            }
         }
         
-        // 2. Check impossible travel (if we have recent location)
-        if let Some(last_location) = recent_locations.first() {
-            let distance_km = self.calculate_distance(
-                &transaction.location,
+        // 2. Check impossible travel (if we have a recent enough location).
+        // Both points are only known to within the tolerance and their
+        // accuracy, only the distance beyond that counts
+        let mut travel = None;
+        let comparison = recent_locations.first();
+        let comparison_fresh = comparison.is_some_and(|last_location| {
+            self.config.travel_max_comparison_age_hours <= 0.0
+                || last_location.hours_ago <= self.config.travel_max_comparison_age_hours
+        });
+        let travel_comparison = comparison.map(|last_location| {
+            serde_json::json!({
+                "city": last_location.city,
+                "country": last_location.country,
+                "hours_ago": last_location.hours_ago,
+                "used": comparison_fresh,
+            })
+        });
+        if let Some(last_location) = comparison.filter(|_| comparison_fresh) {
+            let raw_distance_km = self.calculate_distance(
+                location,
                 &Location {
                     city: last_location.city.clone(),
                     country: last_location.country.clone(),
                     lat: last_location.lat,
                     lon: last_location.lon,
+                    accuracy_km: last_location.accuracy_km,
                 }
             );
-            
-            let time_hours = last_location.hours_ago;
-            
-            // If distance > 500km and time < 1 hour, likely fraud
-            if distance_km > 500.0 && time_hours < 1.0 {
-                risk_score += 0.5;
-                reasons.push(format!(
-                    "Impossible travel: {:.0}km in {:.1} hours",
-                    distance_km, time_hours
-                ));
-            } else if distance_km > 1000.0 && time_hours < 3.0 {
-                risk_score += 0.3;
-                reasons.push(format!("Unlikely travel pattern: {:.0}km", distance_km));
+            let accuracy_km =
+                |accuracy: Option<f64>| accuracy.unwrap_or(0.0).clamp(0.0, self.config.max_location_accuracy_km);
+            let tolerance_km = self.config.travel_distance_tolerance_km
+                + accuracy_km(location.accuracy_km)
+                + accuracy_km(last_location.accuracy_km);
+            let distance_km = (raw_distance_km - tolerance_km).max(0.0);
+            let time_hours = last_location.hours_ago.max(0.0);
+            let plausibility = self.config.travel_plausibility(distance_km, time_hours);
+
+            // Average speed the trip needed, null when both happened at once
+            let required_speed_kmh = (time_hours > 0.0).then(|| distance_km / time_hours);
+            travel = Some(serde_json::json!({
+                "raw_distance_km": raw_distance_km,
+                "tolerance_km": tolerance_km,
+                "effective_distance_km": distance_km,
+                "hours": time_hours,
+                "required_speed_kmh": required_speed_kmh,
+                "max_speed_kmh": self.config.max_travel_speed_kmh,
+                "plausibility": plausibility,
+            }));
+
+            match plausibility {
+                TravelPlausibility::Impossible => {
+                    risk_score += 0.5 * travel_weight;
+                    reasons.push(
+                        Reason::new(ReasonCode::ImpossibleTravel)
+                            .with("distance_km", format!("{:.0}", distance_km))
+                            .with("hours", format!("{:.1}", time_hours)),
+                    );
+                }
+                TravelPlausibility::Unlikely => {
+                    risk_score += 0.3 * travel_weight;
+                    reasons.push(
+                        Reason::new(ReasonCode::UnlikelyTravel).with("distance_km", format!("{:.0}", distance_km)),
+                    );
+                }
+                TravelPlausibility::Plausible => {}
             }
         }
         
@@ -67,17 +337,21 @@ impl GeographicAgent {
             .map(|l| l.country.clone())
             .collect();
         
-        if !known_countries.contains(&transaction.location.country) {
-            risk_score += 0.2;
-            reasons.push(format!("First transaction in {}", transaction.location.country));
+        if !known_countries.contains(&location.country) {
+            risk_score += 0.2 * location_weight;
+            reasons.push(Reason::new(ReasonCode::NewCountry).with("country", &location.country));
         }
         
         risk_score = risk_score.clamp(0.0, 1.0);
         
+        // With no recent locations there's no travel to check, only the
+        // location itself
+        let confidence = if recent_locations.is_empty() { 0.5 } else { 1.0 };
+        
         let reason = if reasons.is_empty() {
-            format!("Normal location: {}, {}", transaction.location.city, transaction.location.country)
+            format!("Normal location: {}, {}", location.city, location.country)
         } else {
-            reasons.join("; ")
+            i18n::render_all(Locale::English, &reasons)
         };
         
         tracing::info!("✅ Geographic Agent: {:.2} - {}", risk_score, reason);
@@ -85,20 +359,35 @@ impl GeographicAgent {
         Ok(AgentScore {
             risk_score,
             reason,
+            reasons,
+            confidence,
+            data_available: !recent_locations.is_empty(),
+            data_note: recent_locations
+                .is_empty()
+                .then(|| "No recent locations to compare with".to_string()),
             details: serde_json::json!({
                 "current_location": {
-                    "city": transaction.location.city,
-                    "country": transaction.location.country
+                    "city": location.city,
+                    "country": location.country
                 },
                 "recent_countries": known_countries,
+                "travel": travel,
+                "travel_comparison": travel_comparison,
+                "channel": channel,
+                "location_weight": location_weight,
+                "travel_weight": travel_weight,
             }),
+            embedding: None,
         })
     }
     
+    /// The user's other located transactions in the week before this one,
+    /// newest first. Ages are measured from the transaction's own timestamp
+    /// so replayed and re-scored transactions see the same history
     async fn get_recent_locations(
         &self,
         pool: &PgPool,
-        user_id: &str,
+        transaction: &Transaction,
     ) -> Result<Vec<RecentLocation>> {
         let locations = sqlx::query_as::<_, RecentLocation>(
             r#"
@@ -107,16 +396,22 @@ impl GeographicAgent {
                 COALESCE(location->>'country', 'Unknown') as country,
                 COALESCE((location->>'lat')::float8, 0.0) as lat,
                 COALESCE((location->>'lon')::float8, 0.0) as lon,
-                EXTRACT(EPOCH FROM (NOW() - timestamp)) / 3600 as hours_ago
+                (location->>'accuracy_km')::float8 as accuracy_km,
+                EXTRACT(EPOCH FROM ($3::timestamptz - timestamp)) / 3600 as hours_ago
             FROM transactions
             WHERE user_id = $1
-            AND timestamp > NOW() - INTERVAL '7 days'
+            AND timestamp > $3::timestamptz - INTERVAL '7 days'
+            AND timestamp <= $3::timestamptz
+            AND transaction_id != $4
             AND location IS NOT NULL
             ORDER BY timestamp DESC
-            LIMIT 10
+            LIMIT $2
             "#
         )
-        .bind(user_id)
+        .bind(&transaction.user_id)
+        .bind(self.config.recent_locations_limit)
+        .bind(transaction.timestamp)
+        .bind(&transaction.transaction_id)
         .fetch_all(pool)
         .await?;
         
@@ -146,5 +441,6 @@ struct RecentLocation {
     country: String,
     lat: f64,
     lon: f64,
+    accuracy_km: Option<f64>,
     hours_ago: f64,
 }
\ No newline at end of file
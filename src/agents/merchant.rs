@@ -1,13 +1,124 @@
 use sqlx::PgPool;
 use anyhow::Result;
 
-use crate::{AppState, models::transaction::{AgentScore, Transaction}};
+use crate::{AppState, category::CategoryTaxonomy, circuit_breaker::QueryKind, config::{self, ConfigError, ConfigSource}, db::merchant_similarity::SimilarityCriteria, embedding::{cosine_similarity, embed_query}, i18n::{self, Locale}, models::transaction::{AgentScore, Reason, ReasonCode, Transaction}};
 
-pub struct MerchantAgent;
+/// Tunables for the merchant agent
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MerchantConfig {
+    /// Peers needed in a category before its average fraud rate is trusted
+    pub category_min_merchants: i64,
+    /// How many times the category average a merchant's rate must reach to be elevated
+    pub category_ratio_threshold: f64,
+    /// Minimum absolute gap over the category average, so 0.2% vs 0.1% isn't flagged
+    pub category_min_excess: f64,
+    /// Risk added when the declared category differs from the merchant's known one
+    pub category_mismatch_risk: f64,
+    /// For unknown merchants, similarity between the merchant name and the
+    /// declared category below which risk is added (up to
+    /// `category_mismatch_risk` at 0). 0 skips the check and its two embeddings
+    pub category_similarity_floor: f64,
+    /// Known merchants with fewer transactions than this are flagged new
+    pub new_merchant_threshold: i64,
+    /// Embedding similarity above which another merchant counts as alike
+    pub similar_min_similarity: f64,
+    /// Fraud rate above which an alike merchant counts as risky
+    pub similar_risky_fraud_rate: f64,
+    /// Leave the merchant itself out of its similar risky merchants, so a
+    /// risky merchant isn't reported as alike to itself
+    pub similar_exclude_self: bool,
+}
+
+impl Default for MerchantConfig {
+    fn default() -> Self {
+        Self {
+            category_min_merchants: 3,
+            category_ratio_threshold: 2.0,
+            category_min_excess: 0.02,
+            category_mismatch_risk: 0.25,
+            category_similarity_floor: 0.0,
+            new_merchant_threshold: 10,
+            similar_min_similarity: 0.7,
+            similar_risky_fraud_rate: 0.3,
+            similar_exclude_self: false,
+        }
+    }
+}
+
+impl MerchantConfig {
+    /// Read the `MERCHANT_CATEGORY_*`, `MERCHANT_NEW_THRESHOLD` and
+    /// `MERCHANT_SIMILAR_*` settings
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            category_min_merchants: config::parsed(
+                source,
+                "MERCHANT_CATEGORY_MIN_MERCHANTS",
+                defaults.category_min_merchants,
+            )?,
+            category_ratio_threshold: config::parsed(
+                source,
+                "MERCHANT_CATEGORY_RATIO_THRESHOLD",
+                defaults.category_ratio_threshold,
+            )?,
+            category_min_excess: config::parsed(
+                source,
+                "MERCHANT_CATEGORY_MIN_EXCESS",
+                defaults.category_min_excess,
+            )?,
+            category_mismatch_risk: config::parsed(
+                source,
+                "MERCHANT_CATEGORY_MISMATCH_RISK",
+                defaults.category_mismatch_risk,
+            )?,
+            category_similarity_floor: config::parsed(
+                source,
+                "MERCHANT_CATEGORY_SIMILARITY_FLOOR",
+                defaults.category_similarity_floor,
+            )?,
+            new_merchant_threshold: config::parsed(
+                source,
+                "MERCHANT_NEW_THRESHOLD",
+                defaults.new_merchant_threshold,
+            )?,
+            similar_min_similarity: config::parsed(
+                source,
+                "MERCHANT_SIMILAR_MIN_SIMILARITY",
+                defaults.similar_min_similarity,
+            )?,
+            similar_risky_fraud_rate: config::parsed(
+                source,
+                "MERCHANT_SIMILAR_RISKY_FRAUD_RATE",
+                defaults.similar_risky_fraud_rate,
+            )?,
+            similar_exclude_self: config::parsed(
+                source,
+                "MERCHANT_SIMILAR_EXCLUDE_SELF",
+                defaults.similar_exclude_self,
+            )?,
+        })
+    }
+
+    fn similarity_criteria(&self) -> SimilarityCriteria {
+        SimilarityCriteria {
+            min_similarity: self.similar_min_similarity,
+            min_fraud_rate: self.similar_risky_fraud_rate,
+            exclude_self: self.similar_exclude_self,
+        }
+    }
+}
+
+pub struct MerchantAgent {
+    config: MerchantConfig,
+}
 
 impl MerchantAgent {
     pub fn new() -> Self {
-        Self
+        Self::with_config(MerchantConfig::default())
+    }
+
+    pub fn with_config(config: MerchantConfig) -> Self {
+        Self { config }
     }
     
     /// Analyze merchant reputation using pg_text search + pgvector similarity
@@ -22,31 +133,118 @@ impl MerchantAgent {
         let mut risk_score: f64 = 0.0;
         let mut reasons = Vec::new();
         
-        // 1. Get merchant from database
-        let merchant_info = self.get_merchant_info(pool, &transaction.merchant).await?;
+        // 1. Get merchant from database, by acquirer id when the transaction
+        // carries one. The stored name is the canonical one from then on
+        let merchant_info = self.get_merchant_info(pool, transaction).await?;
+        let merchant_name = merchant_info
+            .as_ref()
+            .map(|m| m.merchant_name.as_str())
+            .unwrap_or(&transaction.merchant);
+        let matched_by = merchant_info.as_ref().map(|m| {
+            if m.acquirer_merchant_id.is_some() && m.acquirer_merchant_id == transaction.merchant_id {
+                "merchant_id"
+            } else {
+                "name"
+            }
+        });
+
+        // Fraud levels differ structurally between categories, "elevated" is
+        // judged against the merchant's category peers when there are enough
+        let taxonomy = &state.config.category_taxonomy;
+        let category = taxonomy.normalize(
+            merchant_info
+                .as_ref()
+                .and_then(|m| m.category.as_deref())
+                .unwrap_or(&transaction.merchant_category),
+        );
+        let category_baseline = self
+            .get_category_baseline(pool, taxonomy, &category, merchant_name, transaction.merchant_id.as_deref())
+            .await?
+            .filter(|b| b.merchant_count >= self.config.category_min_merchants);
         
         if let Some(ref merchant) = merchant_info {
             // Check fraud rate
             if merchant.fraud_rate > 0.3 {
                 risk_score += 0.5;
-                reasons.push(format!(
-                    "High-risk merchant: {:.0}% fraud rate",
-                    merchant.fraud_rate * 100.0
-                ));
+                reasons.push(
+                    Reason::new(ReasonCode::HighRiskMerchant)
+                        .with("fraud_rate", format!("{:.0}", merchant.fraud_rate * 100.0)),
+                );
+            } else if let Some(ref baseline) = category_baseline {
+                if merchant.fraud_rate > baseline.average_fraud_rate * self.config.category_ratio_threshold
+                    && merchant.fraud_rate - baseline.average_fraud_rate >= self.config.category_min_excess
+                {
+                    risk_score += 0.25;
+                    reasons.push(
+                        Reason::new(ReasonCode::ElevatedRiskForCategory)
+                            .with("fraud_rate", format!("{:.0}", merchant.fraud_rate * 100.0))
+                            .with("category_rate", format!("{:.0}", baseline.average_fraud_rate * 100.0))
+                            .with("category", &category),
+                    );
+                }
             } else if merchant.fraud_rate > 0.1 {
                 risk_score += 0.25;
-                reasons.push(format!("Elevated risk merchant: {:.0}% fraud rate", merchant.fraud_rate * 100.0));
+                reasons.push(
+                    Reason::new(ReasonCode::ElevatedRiskMerchant)
+                        .with("fraud_rate", format!("{:.0}", merchant.fraud_rate * 100.0)),
+                );
             }
             
             // Check if merchant is new (low transaction count)
-            if merchant.total_transactions < 10 {
+            if merchant.transaction_count < self.config.new_merchant_threshold {
                 risk_score += 0.2;
-                reasons.push("New/unknown merchant".to_string());
+                reasons.push(Reason::new(ReasonCode::NewMerchant));
             }
         } else {
             // Merchant not in database - could be new or suspicious
             risk_score += 0.3;
-            reasons.push("Unrecognized merchant".to_string());
+            reasons.push(Reason::new(ReasonCode::UnrecognizedMerchant));
+        }
+
+        // Declared category against what the merchant is known to sell, a
+        // gas station selling electronics is internally inconsistent
+        let declared_category = taxonomy.normalize(&transaction.merchant_category);
+        let expected_category = merchant_info
+            .as_ref()
+            .and_then(|m| m.category.as_deref())
+            .map(|known| taxonomy.normalize(known));
+        let mut category_similarity = None;
+        match &expected_category {
+            Some(expected) if *expected != declared_category => {
+                risk_score += self.config.category_mismatch_risk;
+                reasons.push(
+                    Reason::new(ReasonCode::CategoryMismatch)
+                        .with("declared", &transaction.merchant_category)
+                        .with("expected", expected),
+                );
+            }
+            Some(_) => {}
+            // Unknown merchant: nothing on record, judge how plausible the
+            // name is for the category instead
+            None if merchant_info.is_none() && self.config.category_similarity_floor > 0.0 => {
+                match self.merchant_category_similarity(state, transaction).await {
+                    Ok(similarity) => {
+                        let floor = self.config.category_similarity_floor;
+                        if similarity < floor {
+                            risk_score += self.config.category_mismatch_risk
+                                * ((floor - similarity) / floor).clamp(0.0, 1.0);
+                            reasons.push(
+                                Reason::new(ReasonCode::UnlikelyCategory)
+                                    .with("merchant", &transaction.merchant)
+                                    .with("declared", &transaction.merchant_category)
+                                    .with("similarity", format!("{:.2}", similarity)),
+                            );
+                        }
+                        category_similarity = Some(similarity);
+                    }
+                    Err(e) => tracing::warn!(
+                        "⚠️ Skipping category plausibility check for {}: {}",
+                        transaction.transaction_id,
+                        e
+                    ),
+                }
+            }
+            None => {}
         }
         
         // 2. Use pg_text to search for similar merchant fraud patterns
@@ -58,28 +256,40 @@ impl MerchantAgent {
         
         if fraud_patterns > 0 {
             risk_score += 0.25;
-            reasons.push(format!("Found {} similar fraud cases via pg_text search", fraud_patterns));
+            reasons.push(Reason::new(ReasonCode::MerchantFraudReports).with("count", fraud_patterns));
         }
         
-        // 3. Use pgvector to find similar merchants (if merchant has embedding)
+        // 3. Use pgvector to find similar merchants (if merchant has
+        // embedding), precomputed for all merchants and cached. Abstains
+        // while the merchant similarity breaker is open
+        let mut similar_risky = None;
+        let mut similar_risky_abstained = false;
         if merchant_info.is_some() {
-            let similar_risky_merchants = self.find_similar_risky_merchants(
-                pool,
-                &transaction.merchant
-            ).await?;
-            
-            if similar_risky_merchants > 0 {
-                risk_score += 0.2;
-                reasons.push(format!("{} similar high-risk merchants found", similar_risky_merchants));
-            }
+            similar_risky = state
+                .breakers
+                .guard(
+                    QueryKind::MerchantSimilarity,
+                    state
+                        .merchant_similarity
+                        .find(pool, merchant_name, self.config.similarity_criteria()),
+                )
+                .await?;
+            similar_risky_abstained = similar_risky.is_none();
+        }
+        if let Some(cached) = similar_risky.as_ref().filter(|cached| !cached.merchants.is_empty()) {
+            risk_score += 0.2;
+            reasons.push(Reason::new(ReasonCode::SimilarRiskyMerchants).with("count", cached.merchants.len()));
         }
         
         risk_score = risk_score.clamp(0.0, 1.0);
         
+        // An unknown merchant has no fraud rate or peers to judge by
+        let confidence = if merchant_info.is_some() { 1.0 } else { 0.6 };
+        
         let reason = if reasons.is_empty() {
             format!("Trusted merchant: {}", transaction.merchant)
         } else {
-            reasons.join("; ")
+            i18n::render_all(Locale::English, &reasons)
         };
         
         tracing::info!("✅ Merchant Agent: {:.2} - {}", risk_score, reason);
@@ -87,36 +297,121 @@ impl MerchantAgent {
         Ok(AgentScore {
             risk_score,
             reason,
+            reasons,
+            confidence,
+            data_available: merchant_info.is_some(),
+            data_note: merchant_info.is_none().then(|| "Merchant not seen before".to_string()),
             details: serde_json::json!({
                 "merchant": transaction.merchant,
+                "merchant_id": transaction.merchant_id,
+                "matched_merchant": merchant_info.as_ref().map(|m| &m.merchant_name),
+                "matched_by": matched_by,
                 "category": transaction.merchant_category,
                 "fraud_patterns_found": fraud_patterns,
+                "merchant_fraud_rate": merchant_info.as_ref().map(|m| m.fraud_rate),
+                "merchant_transactions": merchant_info.as_ref().map(|m| m.transaction_count),
+                "category_fraud_rate": category_baseline.as_ref().map(|b| b.average_fraud_rate),
+                "category_merchants": category_baseline.as_ref().map(|b| b.merchant_count).unwrap_or(0),
+                "declared_category": declared_category,
+                "expected_category": expected_category,
+                "category_similarity": category_similarity,
+                "similar_risky_merchants": similar_risky.as_ref().map(|cached| cached.merchants.len()),
+                "similar_risky_matches": similar_risky.as_ref().map(|cached| &cached.merchants),
+                "similar_risky_cache_age_secs": similar_risky.as_ref().map(|cached| cached.age.as_secs()),
+                "similar_risky_abstained": similar_risky_abstained,
             }),
+            embedding: None,
         })
     }
     
+    /// Cosine similarity between the merchant name and its declared category
+    async fn merchant_category_similarity(&self, state: &AppState, transaction: &Transaction) -> Result<f64> {
+        let (merchant, category) = tokio::try_join!(
+            embed_query(state, &transaction.merchant),
+            embed_query(state, &transaction.merchant_category),
+        )
+        .map_err(|e| anyhow::anyhow!(e))?;
+
+        Ok(cosine_similarity(&merchant, &category) as f64)
+    }
+
+    /// The merchant with the transaction's acquirer id, else the one with its
+    /// name. A name match is skipped when both sides carry different ids:
+    /// that's another merchant under the same name
     async fn get_merchant_info(
         &self,
         pool: &PgPool,
-        merchant_name: &str,
+        transaction: &Transaction,
     ) -> Result<Option<MerchantInfo>> {
         let merchant = sqlx::query_as::<_, MerchantInfo>(
             r#"
             SELECT 
                 merchant_name,
+                acquirer_merchant_id,
+                category,
                 fraud_rate::float8 as fraud_rate,
-                total_transactions
-            FROM merchants
-            WHERE merchant_name = $1
+                CASE
+                    WHEN COALESCE(total_transactions, 0) > 0 THEN total_transactions::bigint
+                    ELSE (
+                        SELECT COUNT(*) FROM transactions t
+                        WHERE t.merchant = m.merchant_name
+                        OR t.merchant_id = m.acquirer_merchant_id
+                    )
+                END as transaction_count
+            FROM merchants m
+            WHERE m.acquirer_merchant_id = $2
+            OR (m.merchant_name = $1 AND (m.acquirer_merchant_id IS NULL OR $2::text IS NULL))
+            ORDER BY (m.acquirer_merchant_id = $2) IS TRUE DESC
+            LIMIT 1
             "#
         )
-        .bind(merchant_name)
+        .bind(&transaction.merchant)
+        .bind(&transaction.merchant_id)
         .fetch_optional(pool)
         .await?;
         
         Ok(merchant)
     }
     
+    /// Average fraud rate of the other merchants in a category. The merchant
+    /// itself is left out by acquirer id as well as by name
+    async fn get_category_baseline(
+        &self,
+        pool: &PgPool,
+        taxonomy: &CategoryTaxonomy,
+        category: &str,
+        merchant_name: &str,
+        merchant_id: Option<&str>,
+    ) -> Result<Option<CategoryFraudBaseline>> {
+        let (aliases, canonicals) = taxonomy.sql_aliases();
+        let baseline = sqlx::query_as::<_, CategoryFraudBaseline>(&format!(
+            r#"
+            SELECT
+                AVG(fraud_rate)::float8 as average_fraud_rate,
+                COUNT(*) as merchant_count
+            FROM merchants
+            WHERE {category} = $1
+            AND NOT COALESCE(
+                acquirer_merchant_id = $5
+                OR (merchant_name = $2 AND (acquirer_merchant_id IS NULL OR $5::text IS NULL)),
+                false
+            )
+            AND fraud_rate IS NOT NULL
+            HAVING COUNT(*) > 0
+            "#,
+            category = CategoryTaxonomy::sql_expr("category", 3)
+        ))
+        .bind(category)
+        .bind(merchant_name)
+        .bind(aliases)
+        .bind(canonicals)
+        .bind(merchant_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(baseline)
+    }
+    
     /// Use pg_text to search for fraud patterns mentioning this merchant
     async fn search_merchant_fraud_patterns(
         &self,
@@ -140,42 +435,21 @@ impl MerchantAgent {
         
         Ok(result)
     }
-    
-    /// Use pgvector to find similar merchants with high fraud rates
-    async fn find_similar_risky_merchants(
-        &self,
-        pool: &PgPool,
-        merchant_name: &str,
-    ) -> Result<i64> {
-        let result = sqlx::query_scalar::<_, i64>(
-            r#"
-            WITH current_merchant AS (
-                SELECT merchant_embedding
-                FROM merchants
-                WHERE merchant_name = $1
-                AND merchant_embedding IS NOT NULL
-            )
-            SELECT COUNT(*)
-            FROM merchants m, current_merchant cm
-            WHERE m.fraud_rate > 0.3
-            AND m.merchant_embedding IS NOT NULL
-            AND (1 - (m.merchant_embedding <=> cm.merchant_embedding)) > 0.7
-            LIMIT 10
-            "#
-        )
-        .bind(merchant_name)
-        .fetch_optional(pool)
-        .await?
-        .unwrap_or(0);
-        
-        Ok(result)
-    }
 }
 
 #[derive(sqlx::FromRow, Debug)]
 struct MerchantInfo {
     merchant_name: String,
+    acquirer_merchant_id: Option<String>,
+    category: Option<String>,
     fraud_rate: f64,
-    total_transactions: i32,
+    /// The stored counter, or a live count of its transactions when the
+    /// counter was never populated
+    transaction_count: i64,
     // Removed merchant_embedding - we'll query it separately if needed
-}
\ No newline at end of file
+}
+#[derive(sqlx::FromRow, Debug)]
+struct CategoryFraudBaseline {
+    average_fraud_rate: f64,
+    merchant_count: i64,
+}
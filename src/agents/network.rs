@@ -1,20 +1,164 @@
 use sqlx::PgPool;
 use anyhow::Result;
 
-use crate::models::transaction::{AgentScore, Transaction};
+use crate::AppState;
+use crate::circuit_breaker::QueryKind;
+use crate::db::transactions::same_merchant_sql;
+use crate::config::{self, ConfigError, ConfigSource};
+use crate::i18n::{self, Locale};
+use crate::models::transaction::{AgentScore, Reason, ReasonCode, RingSeverity, Transaction};
 
+/// Independent ring signals, each of which can abstain
+const SIGNALS: usize = 4;
 
-pub struct NetworkAgent;
+/// Tunables for the network agent
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NetworkConfig {
+    /// Window around the transaction in which other users at the same merchant count as coordinated
+    pub coordination_window_secs: i64,
+    /// Minimum distinct users in the window before it looks coordinated
+    pub coordination_min_users: i64,
+    /// How far above the merchant's usual per-window volume the count must be
+    pub coordination_volume_multiplier: f64,
+    /// Window in which one device hitting many merchants looks like card testing
+    pub card_testing_window_secs: i64,
+    /// Decay-weighted distinct merchants (the current one included) at which card testing is flagged
+    pub card_testing_min_merchants: f64,
+    /// Half-life of a merchant hit's weight, so a burst counts more than the
+    /// same merchants spread over the window. 0 weighs every hit fully
+    pub card_testing_half_life_secs: f64,
+    /// Risk added for a card-testing pattern
+    pub card_testing_risk: f64,
+    /// Other users on the device within this long of the transaction are
+    /// in an overlapping session
+    pub device_session_window_secs: i64,
+    /// Share by which a user on the device only outside the session window
+    /// (office fingerprints, privacy browsers days apart) counts less than
+    /// one sharing it concurrently. 0 counts every user fully
+    pub device_spread_discount: f64,
+    /// How many times past its threshold a ring signal must be for a LIKELY ring
+    pub ring_likely_multiple: f64,
+    /// How many times past its threshold a ring signal must be for a
+    /// CONFIRMED ring. Two signals at once are always CONFIRMED
+    pub ring_confirmed_multiple: f64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            coordination_window_secs: 3600,
+            coordination_min_users: 5,
+            coordination_volume_multiplier: 3.0,
+            card_testing_window_secs: 600,
+            card_testing_min_merchants: 5.0,
+            card_testing_half_life_secs: 300.0,
+            card_testing_risk: 0.3,
+            device_session_window_secs: 1800,
+            device_spread_discount: 0.0,
+            ring_likely_multiple: 2.0,
+            ring_confirmed_multiple: 5.0,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Read the `NETWORK_COORDINATION_*`, `NETWORK_CARD_TESTING_*`,
+    /// `NETWORK_DEVICE_*` and `NETWORK_RING_*` settings
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            coordination_window_secs: config::parsed(
+                source,
+                "NETWORK_COORDINATION_WINDOW_SECS",
+                defaults.coordination_window_secs,
+            )?,
+            coordination_min_users: config::parsed(
+                source,
+                "NETWORK_COORDINATION_MIN_USERS",
+                defaults.coordination_min_users,
+            )?,
+            coordination_volume_multiplier: config::parsed(
+                source,
+                "NETWORK_COORDINATION_VOLUME_MULTIPLIER",
+                defaults.coordination_volume_multiplier,
+            )?,
+            card_testing_window_secs: config::parsed(
+                source,
+                "NETWORK_CARD_TESTING_WINDOW_SECS",
+                defaults.card_testing_window_secs,
+            )?,
+            card_testing_min_merchants: config::parsed(
+                source,
+                "NETWORK_CARD_TESTING_MIN_MERCHANTS",
+                defaults.card_testing_min_merchants,
+            )?,
+            card_testing_half_life_secs: config::parsed(
+                source,
+                "NETWORK_CARD_TESTING_HALF_LIFE_SECS",
+                defaults.card_testing_half_life_secs,
+            )?,
+            card_testing_risk: config::parsed(
+                source,
+                "NETWORK_CARD_TESTING_RISK",
+                defaults.card_testing_risk,
+            )?,
+            device_session_window_secs: config::parsed(
+                source,
+                "NETWORK_DEVICE_SESSION_WINDOW_SECS",
+                defaults.device_session_window_secs,
+            )?,
+            device_spread_discount: config::parsed(
+                source,
+                "NETWORK_DEVICE_SPREAD_DISCOUNT",
+                defaults.device_spread_discount,
+            )?,
+            ring_likely_multiple: config::parsed(
+                source,
+                "NETWORK_RING_LIKELY_MULTIPLE",
+                defaults.ring_likely_multiple,
+            )?,
+            ring_confirmed_multiple: config::parsed(
+                source,
+                "NETWORK_RING_CONFIRMED_MULTIPLE",
+                defaults.ring_confirmed_multiple,
+            )?,
+        })
+    }
+
+    /// Severity from the ring signals that fired, each given as its value
+    /// over its threshold. `None` when none fired
+    pub fn ring_severity(&self, magnitudes: &[f64]) -> Option<RingSeverity> {
+        let strongest = magnitudes.iter().copied().reduce(f64::max)?;
+        Some(if magnitudes.len() >= 2 || strongest >= self.ring_confirmed_multiple {
+            RingSeverity::Confirmed
+        } else if strongest >= self.ring_likely_multiple {
+            RingSeverity::Likely
+        } else {
+            RingSeverity::Suspected
+        })
+    }
+}
+
+pub struct NetworkAgent {
+    config: NetworkConfig,
+}
 
 impl NetworkAgent {
     pub fn new() -> Self {
-        Self
+        Self::with_config(NetworkConfig::default())
+    }
+
+    pub fn with_config(config: NetworkConfig) -> Self {
+        Self { config }
     }
     
-    /// Detect fraud rings - multiple users sharing devices/locations
+    /// Detect fraud rings - multiple users sharing devices/locations.
+    /// Every count runs behind the network counts breaker, a signal whose
+    /// query was skipped or failed abstains
     pub async fn analyze(
         &self,
         pool: &PgPool,
+        state: &AppState,
         transaction: &Transaction,
     ) -> Result<AgentScore> {
         tracing::info!("🔍 Network Agent analyzing {}", transaction.transaction_id);
@@ -22,46 +166,101 @@ impl NetworkAgent {
         let mut risk_score:f64 = 0.0;
         let mut reasons = Vec::new();
         let mut fraud_ring_detected = false;
+        // Value over threshold of each ring signal that fired
+        let mut ring_magnitudes = Vec::new();
+        let mut abstained = Vec::new();
+        let breakers = &state.breakers;
         
-        // 1. Check device fingerprint sharing
-        let users_sharing_device = self.check_device_sharing(
-            pool,
-            &transaction.device_fingerprint,
-            &transaction.user_id
-        ).await?;
+        // 1. Check device fingerprint sharing. Users on the device in an
+        // overlapping session count fully, ones days apart are discounted
+        let device_sharing = breakers
+            .guard(QueryKind::NetworkCounts, self.check_device_sharing(pool, transaction))
+            .await?
+            .unwrap_or_else(|| {
+                abstained.push("device_sharing");
+                DeviceSharing::default()
+            });
+        let users_sharing_device = device_sharing.other_users;
+        let spread_users = device_sharing.other_users - device_sharing.concurrent_users;
+        let effective_sharing = device_sharing.concurrent_users as f64
+            + spread_users as f64 * (1.0 - self.config.device_spread_discount);
         
-        if users_sharing_device > 3 {
+        if effective_sharing > 3.0 {
             risk_score += 0.4;
             fraud_ring_detected = true;
-            reasons.push(format!("Device shared by {} users (fraud ring)", users_sharing_device));
-        } else if users_sharing_device > 1 {
+            ring_magnitudes.push(effective_sharing / 3.0);
+            reasons.push(Reason::new(ReasonCode::SharedDeviceRing).with("count", users_sharing_device));
+        } else if effective_sharing > 1.0 {
             risk_score += 0.2;
-            reasons.push(format!("Device used by {} users", users_sharing_device));
+            reasons.push(Reason::new(ReasonCode::SharedDevice).with("count", users_sharing_device));
         }
         
         // 2. Check for coordinated fraud (same merchant, multiple users, short time)
-        let coordinated_transactions = self.check_coordinated_fraud(
-            pool,
-            &transaction.merchant,
-            &transaction.timestamp.to_rfc3339()
-        ).await?;
+        let coordinated_transactions = breakers
+            .guard(QueryKind::NetworkCounts, self.check_coordinated_fraud(pool, transaction))
+            .await?;
         
-        if coordinated_transactions > 5 {
+        // Busy merchants see many distinct users per window legitimately,
+        // so the bar scales with the merchant's usual volume
+        let typical_volume = breakers
+            .guard(QueryKind::NetworkCounts, self.typical_window_volume(pool, transaction))
+            .await?;
+        let coordination_threshold = (self.config.coordination_min_users as f64)
+            .max((typical_volume.unwrap_or(0.0) * self.config.coordination_volume_multiplier).ceil())
+            as i64;
+        // Without the merchant's usual volume the threshold can't be trusted
+        let coordinated_transactions = match (coordinated_transactions, typical_volume) {
+            (Some(count), Some(_)) => count,
+            _ => {
+                abstained.push("coordination");
+                0
+            }
+        };
+        
+        if coordinated_transactions > coordination_threshold {
             risk_score += 0.3;
             fraud_ring_detected = true;
-            reasons.push(format!("{} coordinated transactions at same merchant", coordinated_transactions));
+            ring_magnitudes.push(coordinated_transactions as f64 / coordination_threshold.max(1) as f64);
+            reasons.push(
+                Reason::new(ReasonCode::CoordinatedMerchantActivity).with("count", coordinated_transactions),
+            );
         }
         
         // 3. Check for velocity fraud ring
-        let velocity_ring = self.check_velocity_ring(
-            pool,
-            &transaction.device_fingerprint
-        ).await?;
+        let velocity_ring = breakers
+            .guard(
+                QueryKind::NetworkCounts,
+                self.check_velocity_ring(pool, transaction),
+            )
+            .await?
+            .unwrap_or_else(|| {
+                abstained.push("velocity_ring");
+                0
+            });
         
         if velocity_ring > 10 {
             risk_score += 0.3;
             fraud_ring_detected = true;
-            reasons.push(format!("{} rapid transactions from this device", velocity_ring));
+            ring_magnitudes.push(velocity_ring as f64 / 10.0);
+            reasons.push(Reason::new(ReasonCode::DeviceVelocityRing).with("count", velocity_ring));
+        }
+        
+        // 4. Check for card testing: one device probing many merchants in
+        // a burst, which the per-merchant checks don't see
+        let card_testing = breakers
+            .guard(QueryKind::NetworkCounts, self.check_card_testing(pool, transaction))
+            .await?
+            .unwrap_or_else(|| {
+                abstained.push("card_testing");
+                CardTestingActivity::default()
+            });
+        if card_testing.weighted_merchants >= self.config.card_testing_min_merchants {
+            risk_score += self.config.card_testing_risk;
+            reasons.push(
+                Reason::new(ReasonCode::CardTesting)
+                    .with("count", card_testing.merchants)
+                    .with("minutes", (self.config.card_testing_window_secs as f64 / 60.0).round()),
+            );
         }
         
         risk_score = risk_score.clamp(0.0, 1.0);
@@ -69,7 +268,7 @@ impl NetworkAgent {
         let reason = if reasons.is_empty() {
             "No fraud ring indicators".to_string()
         } else {
-            reasons.join("; ")
+            i18n::render_all(Locale::English, &reasons)
         };
         
         tracing::info!("✅ Network Agent: {:.2} - {} - Ring: {}", risk_score, reason, fraud_ring_detected);
@@ -77,80 +276,210 @@ impl NetworkAgent {
         Ok(AgentScore {
             risk_score,
             reason: if fraud_ring_detected {
-                format!("⚠️ FRAUD RING DETECTED: {}", reason)
+                i18n::fraud_ring(Locale::English, &reason)
             } else {
                 reason
             },
+            reasons,
+            // Abstained signals read as "nothing found", not as evidence
+            confidence: if abstained.is_empty() { 1.0 } else { 0.5 },
+            data_available: abstained.len() < SIGNALS,
+            data_note: (!abstained.is_empty())
+                .then(|| format!("Network queries unavailable for: {}", abstained.join(", "))),
             details: serde_json::json!({
+                "abstained_signals": abstained,
                 "fraud_ring_detected": fraud_ring_detected,
+                "fraud_ring_severity": self.config.ring_severity(&ring_magnitudes),
                 "users_sharing_device": users_sharing_device,
+                "concurrent_users_sharing_device": device_sharing.concurrent_users,
+                "effective_users_sharing_device": effective_sharing,
+                "device_sharing_span_hours": device_sharing.span_hours,
                 "coordinated_transactions": coordinated_transactions,
+                "coordination_threshold": coordination_threshold,
+                "typical_window_volume": typical_volume,
+                "card_testing_merchants": card_testing.merchants,
+                "card_testing_weighted_merchants": card_testing.weighted_merchants,
             }),
+            embedding: None,
         })
     }
     
+    /// Other users on the device in the 30 days up to this transaction, how
+    /// many of them within its session window, and the time between their
+    /// first and last use
     async fn check_device_sharing(
         &self,
         pool: &PgPool,
-        device_fingerprint: &str,
-        current_user_id: &str,
-    ) -> Result<i64> {
-        let count = sqlx::query_scalar::<_, i64>(
+        transaction: &Transaction,
+    ) -> Result<DeviceSharing> {
+        let sharing = sqlx::query_as::<_, DeviceSharing>(
             r#"
-            SELECT COUNT(DISTINCT user_id)
+            SELECT
+                COUNT(DISTINCT user_id) as other_users,
+                COUNT(DISTINCT user_id) FILTER (
+                    WHERE ABS(EXTRACT(EPOCH FROM (timestamp - $3::timestamptz))) < $4
+                ) as concurrent_users,
+                (EXTRACT(EPOCH FROM (MAX(timestamp) - MIN(timestamp))) / 3600)::float8 as span_hours
             FROM transactions
             WHERE device_fingerprint = $1
             AND user_id != $2
-            AND timestamp > NOW() - INTERVAL '30 days'
+            AND timestamp > $3::timestamptz - INTERVAL '30 days'
+            AND timestamp <= $3::timestamptz
             "#
         )
-        .bind(device_fingerprint)
-        .bind(current_user_id)
+        .bind(&transaction.device_fingerprint)
+        .bind(&transaction.user_id)
+        .bind(transaction.timestamp)
+        .bind(self.config.device_session_window_secs as f64)
         .fetch_one(pool)
         .await?;
         
-        Ok(count)
+        Ok(sharing)
     }
     
+    /// Other users at the same merchant (by acquirer id when known) within the
+    /// coordination window. The current user doesn't count towards their own
+    /// ring, and the transaction itself (already stored when re-scored or
+    /// retried) is left out
     async fn check_coordinated_fraud(
         &self,
         pool: &PgPool,
-        merchant: &str,
-        timestamp: &str,
+        transaction: &Transaction,
     ) -> Result<i64> {
-        let count = sqlx::query_scalar::<_, i64>(
+        let count = sqlx::query_scalar::<_, i64>(&format!(
             r#"
             SELECT COUNT(DISTINCT user_id)
             FROM transactions
-            WHERE merchant = $1
-            AND ABS(EXTRACT(EPOCH FROM (timestamp - $2::timestamptz))) < 3600
-            "#
-        )
-        .bind(merchant)
-        .bind(timestamp)
+            WHERE {same_merchant}
+            AND ABS(EXTRACT(EPOCH FROM (timestamp - $2::timestamptz))) < $3
+            AND user_id != $4
+            AND transaction_id != $5
+            "#,
+            same_merchant = same_merchant_sql(1, 6)
+        ))
+        .bind(&transaction.merchant)
+        .bind(transaction.timestamp)
+        .bind(self.config.coordination_window_secs as f64)
+        .bind(&transaction.user_id)
+        .bind(&transaction.transaction_id)
+        .bind(&transaction.merchant_id)
         .fetch_one(pool)
         .await?;
         
         Ok(count)
     }
     
+    /// Average number of transactions the merchant (by acquirer id when known)
+    /// sees per coordination window, over the 30 days before this transaction
+    async fn typical_window_volume(
+        &self,
+        pool: &PgPool,
+        transaction: &Transaction,
+    ) -> Result<f64> {
+        let windows_in_period = (30.0 * 86400.0) / self.config.coordination_window_secs.max(1) as f64;
+        
+        let count = sqlx::query_scalar::<_, i64>(&format!(
+            r#"
+            SELECT COUNT(*)
+            FROM transactions
+            WHERE {same_merchant}
+            AND timestamp > $2::timestamptz - INTERVAL '30 days'
+            AND timestamp <= $2::timestamptz
+            "#,
+            same_merchant = same_merchant_sql(1, 3)
+        ))
+        .bind(&transaction.merchant)
+        .bind(transaction.timestamp)
+        .bind(&transaction.merchant_id)
+        .fetch_one(pool)
+        .await?;
+        
+        Ok(count as f64 / windows_in_period)
+    }
+    
+    /// Distinct merchants this device hit within the card-testing window,
+    /// counting the current transaction's merchant
+    async fn check_card_testing(
+        &self,
+        pool: &PgPool,
+        transaction: &Transaction,
+    ) -> Result<CardTestingActivity> {
+        let (merchants, weighted) = sqlx::query_as::<_, (i64, f64)>(
+            r#"
+            SELECT
+                COUNT(*),
+                COALESCE(SUM(
+                    CASE WHEN $4 > 0
+                    THEN POWER(0.5, GREATEST(EXTRACT(EPOCH FROM ($2::timestamptz - last_seen)), 0) / $4)
+                    ELSE 1 END
+                ), 0)::float8
+            FROM (
+                SELECT merchant, MAX(timestamp) as last_seen
+                FROM transactions
+                WHERE device_fingerprint = $1
+                AND timestamp > $2::timestamptz - make_interval(secs => $3)
+                AND timestamp <= $2::timestamptz
+                AND merchant != $5
+                AND transaction_id != $6
+                GROUP BY merchant
+            ) hits
+            "#
+        )
+        .bind(&transaction.device_fingerprint)
+        .bind(transaction.timestamp)
+        .bind(self.config.card_testing_window_secs as f64)
+        .bind(self.config.card_testing_half_life_secs)
+        .bind(&transaction.merchant)
+        .bind(&transaction.transaction_id)
+        .fetch_one(pool)
+        .await?;
+
+        // The current merchant, hit just now
+        Ok(CardTestingActivity {
+            merchants: merchants + 1,
+            weighted_merchants: weighted + 1.0,
+        })
+    }
+    
+    /// Other transactions on the device in the hour before this one
     async fn check_velocity_ring(
         &self,
         pool: &PgPool,
-        device_fingerprint: &str,
+        transaction: &Transaction,
     ) -> Result<i64> {
         let count = sqlx::query_scalar::<_, i64>(
             r#"
             SELECT COUNT(*)
             FROM transactions
             WHERE device_fingerprint = $1
-            AND timestamp > NOW() - INTERVAL '1 hour'
+            AND timestamp > $2::timestamptz - INTERVAL '1 hour'
+            AND timestamp <= $2::timestamptz
+            AND transaction_id != $3
             "#
         )
-        .bind(device_fingerprint)
+        .bind(&transaction.device_fingerprint)
+        .bind(transaction.timestamp)
+        .bind(&transaction.transaction_id)
         .fetch_one(pool)
         .await?;
         
         Ok(count)
     }
-}
\ No newline at end of file
+}
+
+/// Merchants one device hit within the card-testing window
+#[derive(Default)]
+struct CardTestingActivity {
+    merchants: i64,
+    /// Each merchant weighted by how recently it was hit
+    weighted_merchants: f64,
+}
+
+#[derive(sqlx::FromRow, Debug, Default)]
+struct DeviceSharing {
+    other_users: i64,
+    /// Of `other_users`, those on the device within the session window
+    concurrent_users: i64,
+    /// Hours between the other users' first and last use, `None` without any
+    span_hours: Option<f64>,
+}
@@ -1,21 +1,296 @@
 use anyhow::Result;
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
 
 use crate::{
     AppState,
-    models::transaction::{AgentScore, Transaction},
+    category::CategoryTaxonomy,
+    circuit_breaker::QueryKind,
+    config::{self, ConfigError, ConfigSource},
+    db::challenges::{self, ChallengeHistory},
+    embedding::{DistanceMetric, EmbeddingPrecision},
+    i18n::{self, Locale},
+    models::transaction::{AgentScore, Reason, ReasonCode, Transaction},
 };
 
-#[derive(sqlx::FromRow, Debug)]
+/// Number of matched transactions reported in `details`
+const SIMILAR_TRANSACTIONS_REPORTED: usize = 5;
+/// Transactions of history at which the agent is fully confident in its score
+const FULL_CONFIDENCE_HISTORY: i64 = 10;
+/// Confidence with no history at all, the similarity search still says something
+const MIN_CONFIDENCE: f64 = 0.25;
+
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
 struct SimilarTxn {
+    pub transaction_id: String,
     pub fraud_label: Option<bool>,
+    pub similarity: f64,
+}
+
+/// Tunables for the pattern agent
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PatternConfig {
+    /// ISO code of the currency all amounts are expressed in
+    pub base_currency: String,
+    /// Maximum risk added when a transaction is far from the user's spending centroid
+    pub novelty_weight: f64,
+    /// Centroid distance (1 - cosine similarity) below which a transaction is not novel
+    pub novelty_floor: f64,
+    /// Transactions folded into the centroid before it is trusted
+    pub novelty_min_history: i32,
+    /// Categories that are never penalized for being new to the user (lowercase)
+    #[serde(serialize_with = "config::sorted_set")]
+    pub low_risk_categories: HashSet<String>,
+    /// Categories that always add `high_risk_category_weight` (lowercase)
+    #[serde(serialize_with = "config::sorted_set")]
+    pub high_risk_categories: HashSet<String>,
+    pub high_risk_category_weight: f64,
+    /// Category transactions a user needs before their own average is trusted
+    /// over the population distribution
+    pub population_min_history: i64,
+    /// Population z-score above which a thin-history amount is flagged
+    pub population_z_threshold: f64,
+    /// Also compare against confirmed fraud from every user (account takeover)
+    pub global_fraud_enabled: bool,
+    /// Similarity to a confirmed fraud above which the transaction is flagged
+    pub global_fraud_similarity_threshold: f64,
+    /// Inner product with a confirmed fraud above which the transaction is
+    /// flagged, used instead of the similarity threshold with
+    /// `EMBEDDING_DISTANCE_METRIC=inner_product`. Its scale depends on the
+    /// embedding magnitudes, so it has no default
+    pub global_fraud_inner_product_threshold: Option<f64>,
+    /// Risk added when the global fraud threshold is crossed
+    pub global_fraud_weight: f64,
+    /// Percentile of the user's own amount history above which an amount is an outlier
+    pub outlier_percentile: f64,
+    /// Maximum risk for an amount above everything in the user's history
+    pub outlier_weight: f64,
+    /// Transactions of history needed before the percentile is meaningful
+    pub outlier_min_history: i64,
+    /// Skip the embedding and similarity searches, scoring only amount and
+    /// category. A fast degraded mode for when the model is slow or down
+    pub lightweight: bool,
+    /// What to embed when the merchant or category has no text to embed
+    pub empty_input_fallback: EmptyInputFallback,
+    /// Similar transactions needed before their fraud rate is taken at face
+    /// value. Fewer are shrunk toward the population fraud rate
+    pub min_similar_for_signal: usize,
+    /// Most risk removed for a user who keeps passing challenges at the
+    /// merchant, 0 ignores challenge outcomes
+    pub challenge_trust_weight: f64,
+    /// Passed challenges at the merchant for the full trust weight
+    pub challenge_trust_passes: i64,
+    /// How far back challenge outcomes count
+    pub challenge_history_days: i32,
+}
+
+/// Stand-in for the embedding of a transaction whose merchant or category is
+/// blank. The description would be the template alone, close to every other
+/// blank transaction and to nothing meaningful
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyInputFallback {
+    /// Leave out the similarity searches
+    #[default]
+    Skip,
+    /// Search with the average embedding of the category's stored
+    /// transactions, skipping when the category is blank or has none
+    CategoryDefault,
+}
+
+impl std::str::FromStr for EmptyInputFallback {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "skip" => Ok(EmptyInputFallback::Skip),
+            "category" | "category_default" => Ok(EmptyInputFallback::CategoryDefault),
+            other => Err(format!("Unknown empty input fallback '{}', expected skip or category", other)),
+        }
+    }
 }
 
-pub struct PatternAgent;
+impl Default for PatternConfig {
+    fn default() -> Self {
+        Self {
+            base_currency: "USD".to_string(),
+            novelty_weight: 0.15,
+            novelty_floor: 0.1,
+            novelty_min_history: 3,
+            low_risk_categories: HashSet::new(),
+            high_risk_categories: HashSet::new(),
+            high_risk_category_weight: 0.15,
+            population_min_history: 5,
+            population_z_threshold: 3.0,
+            global_fraud_enabled: false,
+            global_fraud_similarity_threshold: 0.9,
+            global_fraud_inner_product_threshold: None,
+            global_fraud_weight: 0.25,
+            outlier_percentile: 0.99,
+            outlier_weight: 0.2,
+            outlier_min_history: 20,
+            lightweight: false,
+            empty_input_fallback: EmptyInputFallback::Skip,
+            min_similar_for_signal: 5,
+            challenge_trust_weight: 0.15,
+            challenge_trust_passes: 3,
+            challenge_history_days: 90,
+        }
+    }
+}
+
+impl PatternConfig {
+    /// Threshold for the global fraud match on the scale `metric` reports
+    /// similarity in: a cosine in [-1, 1], or an unbounded inner product
+    pub fn global_fraud_threshold(&self, metric: DistanceMetric) -> Option<f64> {
+        match metric {
+            DistanceMetric::Cosine => Some(self.global_fraud_similarity_threshold),
+            DistanceMetric::InnerProduct => self.global_fraud_inner_product_threshold,
+        }
+    }
+
+    /// Read `BASE_CURRENCY` and the `PATTERN_*` settings
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            base_currency: config::string(source, "BASE_CURRENCY", defaults.base_currency),
+            novelty_weight: config::parsed(source, "PATTERN_NOVELTY_WEIGHT", defaults.novelty_weight)?,
+            novelty_floor: config::parsed(source, "PATTERN_NOVELTY_FLOOR", defaults.novelty_floor)?,
+            novelty_min_history: config::parsed(
+                source,
+                "PATTERN_NOVELTY_MIN_HISTORY",
+                defaults.novelty_min_history,
+            )?,
+            low_risk_categories: config::list(
+                source,
+                "PATTERN_LOW_RISK_CATEGORIES",
+                defaults.low_risk_categories,
+            ),
+            high_risk_categories: config::list(
+                source,
+                "PATTERN_HIGH_RISK_CATEGORIES",
+                defaults.high_risk_categories,
+            ),
+            high_risk_category_weight: config::parsed(
+                source,
+                "PATTERN_HIGH_RISK_CATEGORY_WEIGHT",
+                defaults.high_risk_category_weight,
+            )?,
+            population_min_history: config::parsed(
+                source,
+                "PATTERN_POPULATION_MIN_HISTORY",
+                defaults.population_min_history,
+            )?,
+            population_z_threshold: config::parsed(
+                source,
+                "PATTERN_POPULATION_Z_THRESHOLD",
+                defaults.population_z_threshold,
+            )?,
+            global_fraud_enabled: config::parsed(
+                source,
+                "PATTERN_GLOBAL_FRAUD_ENABLED",
+                defaults.global_fraud_enabled,
+            )?,
+            global_fraud_similarity_threshold: config::parsed(
+                source,
+                "PATTERN_GLOBAL_FRAUD_SIMILARITY_THRESHOLD",
+                defaults.global_fraud_similarity_threshold,
+            )?,
+            global_fraud_inner_product_threshold: config::optional(
+                source,
+                "PATTERN_GLOBAL_FRAUD_INNER_PRODUCT_THRESHOLD",
+            )?,
+            global_fraud_weight: config::parsed(
+                source,
+                "PATTERN_GLOBAL_FRAUD_WEIGHT",
+                defaults.global_fraud_weight,
+            )?,
+            outlier_percentile: config::parsed(
+                source,
+                "PATTERN_OUTLIER_PERCENTILE",
+                defaults.outlier_percentile,
+            )?,
+            outlier_weight: config::parsed(source, "PATTERN_OUTLIER_WEIGHT", defaults.outlier_weight)?,
+            outlier_min_history: config::parsed(
+                source,
+                "PATTERN_OUTLIER_MIN_HISTORY",
+                defaults.outlier_min_history,
+            )?,
+            lightweight: config::parsed(source, "PATTERN_LIGHTWEIGHT", defaults.lightweight)?,
+            empty_input_fallback: config::parsed(
+                source,
+                "PATTERN_EMPTY_INPUT_FALLBACK",
+                defaults.empty_input_fallback,
+            )?,
+            min_similar_for_signal: config::parsed(
+                source,
+                "PATTERN_MIN_SIMILAR_FOR_SIGNAL",
+                defaults.min_similar_for_signal,
+            )?,
+            challenge_trust_weight: config::parsed(
+                source,
+                "PATTERN_CHALLENGE_TRUST_WEIGHT",
+                defaults.challenge_trust_weight,
+            )?,
+            challenge_trust_passes: config::parsed(
+                source,
+                "PATTERN_CHALLENGE_TRUST_PASSES",
+                defaults.challenge_trust_passes,
+            )?,
+            challenge_history_days: config::parsed(
+                source,
+                "PATTERN_CHALLENGE_HISTORY_DAYS",
+                defaults.challenge_history_days,
+            )?,
+        })
+    }
+}
+
+/// Text the pattern agent embeds for a transaction. Stored embeddings are
+/// of this text, re-embedding must use it too
+pub fn describe(transaction: &Transaction) -> String {
+    format!(
+        "User {} spending ${} at {} in category {}",
+        transaction.user_id,
+        transaction.amount,
+        transaction.merchant,
+        transaction.merchant_category
+    )
+}
+
+/// No letters or digits to embed
+fn is_blank(value: &str) -> bool {
+    !value.chars().any(char::is_alphanumeric)
+}
+
+/// Parse a comma-separated category list, e.g. `groceries,gas,utilities`
+pub fn parse_category_list(value: &str) -> HashSet<String> {
+    value
+        .split(',')
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+pub struct PatternAgent {
+    config: PatternConfig,
+}
 
 impl PatternAgent {
     pub fn new() -> Self {
-        Self
+        Self::with_config(PatternConfig::default())
+    }
+
+    pub fn with_config(config: PatternConfig) -> Self {
+        Self { config }
+    }
+
+    fn format_amount(&self, amount: f64) -> String {
+        if self.config.base_currency == "USD" {
+            format!("${:.2}", amount)
+        } else {
+            format!("{:.2} {}", amount, self.config.base_currency)
+        }
     }
 
     /// Analyze if transaction matches user's normal spending pattern
@@ -27,57 +302,185 @@ impl PatternAgent {
     ) -> Result<AgentScore> {
         tracing::info!("🔍 Pattern Agent analyzing {}", transaction.transaction_id);
 
-        // Get user's baseline spending
-        let baseline = self.get_user_baseline(pool, &transaction.user_id).await?;
+        // Get user's baseline spending, with categories folded through the taxonomy
+        let taxonomy = &state.config.category_taxonomy;
+        let baseline = self.get_user_baseline(pool, taxonomy, transaction).await?;
+        let category = taxonomy.normalize(&transaction.merchant_category);
+
+        // Compare against the user's average for this category when they have
+        // one, a blended average across categories means nothing
+        let category_baseline = baseline.categories.get(&category);
+        let reference_amount = category_baseline
+            .map(|c| c.average_amount)
+            .unwrap_or(baseline.average_amount);
 
         // Log the baseline
         tracing::info!(
-            "Baseline for {}: avg=${:.2}, categories={:?}",
+            "Baseline for {}: avg={}, category avg={:?}, categories={:?}",
             transaction.user_id,
-            baseline.average_amount,
+            self.format_amount(baseline.average_amount),
+            category_baseline.map(|c| c.average_amount),
             baseline.common_categories
         );
 
         // Calculate amount deviation
-        let amount_deviation = if baseline.average_amount > 0.0 {
-            (transaction.amount - baseline.average_amount).abs() / baseline.average_amount
+        let amount_deviation = if reference_amount > 0.0 {
+            (transaction.amount - reference_amount).abs() / reference_amount
         } else {
             0.0
         };
 
         // Log the calculation
         tracing::info!(
-            "Transaction ${:.2} vs Average ${:.2} = Deviation {:.2}",
-            transaction.amount,
-            baseline.average_amount,
+            "Transaction {} vs Average {} = Deviation {:.2}",
+            self.format_amount(transaction.amount),
+            self.format_amount(reference_amount),
             amount_deviation
         );
 
+        // With thin history the user's own average is noise, fall back to
+        // where the amount sits in the category's population distribution
+        let thin_history = category_baseline
+            .map(|c| c.transaction_count < self.config.population_min_history)
+            .unwrap_or(true);
+        let population = state
+            .population
+            .category(pool, &transaction.merchant_category)
+            .await?;
+        let population_z_score = population.as_ref().and_then(|p| p.z_score(transaction.amount));
+
+        // Where the amount falls in the user's own distribution, independent
+        // of their spending scale
+        let amount_percentile = self
+            .get_amount_percentile(pool, transaction)
+            .await?
+            .filter(|p| p.history_count >= self.config.outlier_min_history);
+
         // Check category familiarity
-        let category_familiar = baseline
-            .common_categories
-            .contains(&transaction.merchant_category);
+        let category_familiar = baseline.common_categories.contains(&category);
 
         // Generate embedding and find similar transactions
-        let description = format!(
-            "User {} spending ${} at {} in category {}",
-            transaction.user_id,
-            transaction.amount,
-            transaction.merchant,
-            transaction.merchant_category
-        );
+        let description = describe(transaction);
+
+        // Lightweight mode leaves out everything below that needs the model.
+        // A blank merchant or category leaves nothing worth embedding, so the
+        // configured fallback stands in and nothing is stored for it
+        let empty_input = is_blank(&transaction.merchant) || is_blank(&transaction.merchant_category);
+        let mut embedding_fallback = None;
+        let embeddings = if self.config.lightweight {
+            None
+        } else if empty_input {
+            let fallback = match self.config.empty_input_fallback {
+                EmptyInputFallback::Skip => None,
+                EmptyInputFallback::CategoryDefault if is_blank(&transaction.merchant_category) => None,
+                EmptyInputFallback::CategoryDefault => {
+                    self.category_default_embedding(pool, &transaction.merchant_category)
+                        .await?
+                }
+            };
+            match fallback {
+                Some(default_embedding) => {
+                    embedding_fallback = Some("category_default");
+                    Some((default_embedding.clone(), default_embedding))
+                }
+                None => {
+                    tracing::warn!(
+                        "Transaction {} has a blank merchant or category, skipping similarity search",
+                        transaction.transaction_id
+                    );
+                    embedding_fallback = Some("skipped");
+                    None
+                }
+            }
+        } else {
+            let embedding = crate::embedding::embed_query(state, &description)
+                .await
+                .map_err(|e| anyhow::anyhow!("Embedding failed: {}", e))?;
+
+            // What gets stored (and folded into the centroid) is the document
+            // embedding, only computed separately when the prefixes differ
+            let document_embedding = if state.config.embedding_prefixes.is_symmetric() {
+                embedding.clone()
+            } else {
+                crate::embedding::embed_document(state, &description)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Embedding failed: {}", e))?
+            };
+            Some((embedding, document_embedding))
+        };
 
-        let embedding = crate::embedding::generate_embedding_internal(state, description)
-            .await
-            .map_err(|e| anyhow::anyhow!("Embedding failed: {}", e))?;
+        // Find similar past transactions. A re-scored transaction is already
+        // stored with its embedding and would be its own nearest neighbor
+        // Both searches sit behind the vector search breaker and abstain
+        // (no matches) while it's open
+        let mut vector_search_abstained = false;
+        let similar_txns = match &embeddings {
+            Some((embedding, _)) => {
+                let found = state
+                    .breakers
+                    .guard(
+                        QueryKind::VectorSearch,
+                        self.find_similar_transactions(
+                            pool,
+                            embedding,
+                            transaction,
+                            10,
+                            state.config.embedding_precision,
+                            state.config.embedding_distance_metric,
+                        ),
+                    )
+                    .await?;
+                vector_search_abstained |= found.is_none();
+                found.unwrap_or_default()
+            }
+            None => Vec::new(),
+        };
 
-        // Find similar past transactions
-        let similar_txns = self
-            .find_similar_transactions(pool, &embedding, &transaction.user_id, 10)
-            .await?;
+        // Account takeover: the user's history looks fine but the behavior
+        // matches someone else's confirmed fraud
+        let global_fraud_matches = match &embeddings {
+            Some((embedding, _)) if self.config.global_fraud_enabled => {
+                let found = state
+                    .breakers
+                    .guard(
+                        QueryKind::VectorSearch,
+                        self.find_similar_fraud_global(
+                            pool,
+                            embedding,
+                            SIMILAR_TRANSACTIONS_REPORTED as i32,
+                            Some(&transaction.transaction_id),
+                            state.config.embedding_precision,
+                            state.config.embedding_distance_metric,
+                        ),
+                    )
+                    .await?;
+                vector_search_abstained |= found.is_none();
+                found.unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+        let global_fraud_similarity = global_fraud_matches.first().map(|t| t.similarity);
+        let user_fraud_similarity = similar_txns
+            .iter()
+            .filter(|t| t.fraud_label.unwrap_or(false))
+            .map(|t| t.similarity)
+            .reduce(f64::max);
+
+        // How far this transaction sits from the user's spending profile
+        let profile = self.get_spending_profile(pool, &transaction.user_id).await?;
+        let profile_novelty = match (&profile, &embeddings) {
+            (Some((centroid, count)), Some((_, document_embedding)))
+                if *count >= self.config.novelty_min_history && embedding_fallback.is_none() =>
+            {
+                Some(1.0 - crate::embedding::cosine_similarity(document_embedding, centroid) as f64)
+            }
+            _ => None,
+        };
 
-        // Calculate fraud rate in similar transactions
-        let fraud_in_similar = if !similar_txns.is_empty() {
+        // Calculate fraud rate in similar transactions. A handful of matches
+        // says little, so below the minimum the rate is pulled toward the
+        // population's in proportion to the missing samples
+        let similar_fraud_rate = if !similar_txns.is_empty() {
             similar_txns
                 .iter()
                 .filter(|t| t.fraud_label.unwrap_or(false))
@@ -86,6 +489,15 @@ impl PatternAgent {
         } else {
             0.0
         };
+        let similar_shrunk =
+            !similar_txns.is_empty() && similar_txns.len() < self.config.min_similar_for_signal;
+        let fraud_in_similar = if similar_shrunk {
+            let prior = state.population.fraud_rate(pool).await?;
+            let weight = similar_txns.len() as f64 / self.config.min_similar_for_signal as f64;
+            weight * similar_fraud_rate + (1.0 - weight) * prior
+        } else {
+            similar_fraud_rate
+        };
 
         // Combine scores
         let mut risk_score = 0.0;
@@ -94,152 +506,422 @@ impl PatternAgent {
         // Amount deviation (30% weight)
         if amount_deviation > 3.0 {
             risk_score += 0.3;
-            reasons.push(format!(
-                "Amount ${:.2} is {:.1}x user's average ${:.2}",
-                transaction.amount,
-                transaction.amount / baseline.average_amount,
-                baseline.average_amount
-            ));
+            let scope = match category_baseline {
+                Some(_) => format!("{} ", transaction.merchant_category),
+                None => String::new(),
+            };
+            reasons.push(
+                Reason::new(ReasonCode::AmountDeviation)
+                    .with("amount", self.format_amount(transaction.amount))
+                    .with("ratio", format!("{:.1}", transaction.amount / reference_amount))
+                    .with("scope", scope)
+                    .with("average", self.format_amount(reference_amount)),
+            );
         } else if amount_deviation > 1.5 {
             risk_score += 0.15;
+        } else if let (true, Some(z)) = (thin_history, population_z_score) {
+            if z > self.config.population_z_threshold {
+                risk_score += 0.3;
+                reasons.push(
+                    Reason::new(ReasonCode::PopulationAmountOutlier)
+                        .with("amount", self.format_amount(transaction.amount))
+                        .with("z_score", format!("{:.1}", z))
+                        .with("category", &transaction.merchant_category),
+                );
+            } else if z > self.config.population_z_threshold / 2.0 {
+                risk_score += 0.15;
+            }
         }
 
-        // Category unfamiliarity (20% weight)
-        if !category_familiar {
+        // Percentile outlier: nothing at the configured percentile, growing
+        // to the full weight for an amount above everything seen before
+        let mut percentile_risk = 0.0;
+        if let Some(ref percentile) = amount_percentile
+            && percentile.rank > self.config.outlier_percentile
+        {
+            percentile_risk = self.config.outlier_weight
+                * ((percentile.rank - self.config.outlier_percentile)
+                    / (1.0 - self.config.outlier_percentile))
+                    .clamp(0.0, 1.0);
+            risk_score += percentile_risk;
+            reasons.push(
+                Reason::new(ReasonCode::AmountPercentileOutlier)
+                    .with("amount", self.format_amount(transaction.amount))
+                    .with("percentile", format!("{:.0}", self.config.outlier_percentile * 100.0))
+                    .with("threshold", self.format_amount(percentile.threshold_amount)),
+            );
+        }
+
+        // Category unfamiliarity (20% weight), except for categories
+        // configured as low-risk
+        let low_risk_category = self.config.low_risk_categories.contains(&category);
+        let high_risk_category = self.config.high_risk_categories.contains(&category);
+        if !category_familiar && !low_risk_category {
             risk_score += 0.2;
-            reasons.push(format!("New category '{}'", transaction.merchant_category));
+            reasons.push(Reason::new(ReasonCode::NewCategory).with("category", &transaction.merchant_category));
+        }
+
+        if high_risk_category {
+            risk_score += self.config.high_risk_category_weight;
+            reasons.push(Reason::new(ReasonCode::HighRiskCategory).with("category", &transaction.merchant_category));
         }
 
         // Similar fraud patterns (50% weight)
         risk_score += fraud_in_similar * 0.5;
         if fraud_in_similar > 0.3 {
-            reasons.push(format!(
-                "{:.0}% of similar transactions were fraud",
-                fraud_in_similar * 100.0
-            ));
+            reasons.push(
+                Reason::new(ReasonCode::SimilarToFraud)
+                    .with("percent", format!("{:.0}", fraud_in_similar * 100.0)),
+            );
+        }
+
+        if let Some(similarity) = global_fraud_similarity
+            && let Some(threshold) = self.config.global_fraud_threshold(state.config.embedding_distance_metric)
+            && similarity > threshold
+        {
+            risk_score += self.config.global_fraud_weight;
+            reasons.push(
+                Reason::new(ReasonCode::GlobalFraudMatch)
+                    .with("similarity", format!("{:.2}", similarity)),
+            );
+        }
+
+        // Distance from spending profile: a smooth "unusual for you" signal
+        if let Some(novelty) = profile_novelty {
+            let scaled = ((novelty - self.config.novelty_floor)
+                / (1.0 - self.config.novelty_floor))
+                .clamp(0.0, 1.0);
+            risk_score += scaled * self.config.novelty_weight;
+            if scaled > 0.5 {
+                reasons.push(
+                    Reason::new(ReasonCode::ProfileNovelty)
+                        .with("distance", format!("{:.2}", novelty)),
+                );
+            }
         }
 
+        // Passed step-up authentication is weak evidence of legitimacy: trust
+        // grows with the passes at this merchant, scaled down by the share
+        // of challenges that failed or were abandoned
+        let challenge_history = if self.config.challenge_trust_weight > 0.0 {
+            challenges::merchant_history(pool, transaction, self.config.challenge_history_days).await?
+        } else {
+            ChallengeHistory::default()
+        };
+        let challenge_trust = if challenge_history.passed > 0 {
+            self.config.challenge_trust_weight
+                * (challenge_history.passed as f64 / self.config.challenge_trust_passes as f64).min(1.0)
+                * (challenge_history.passed as f64 / challenge_history.total() as f64)
+        } else {
+            0.0
+        };
+        risk_score -= challenge_trust;
+
         risk_score = risk_score.clamp(0.0, 1.0);
 
         let reason = if reasons.is_empty() {
             "Normal spending pattern".to_string()
         } else {
-            reasons.join("; ")
+            i18n::render_all(Locale::English, &reasons)
         };
 
+        // Sparse history makes the amount and category checks guesswork
+        let history_count = baseline.categories.values().map(|c| c.transaction_count).sum::<i64>();
+        let confidence = MIN_CONFIDENCE
+            + (1.0 - MIN_CONFIDENCE) * (history_count as f64 / FULL_CONFIDENCE_HISTORY as f64).min(1.0);
+
         tracing::info!("-->Pattern Agent: {:.2} - {}", risk_score, reason);
 
         Ok(AgentScore {
             risk_score,
             reason,
+            reasons,
+            confidence,
+            data_available: history_count > 0,
+            data_note: (history_count == 0).then(|| "No transaction history for this user".to_string()),
             details: serde_json::json!({
+                "mode": if self.config.lightweight { "lightweight" } else { "full" },
+                "embedding_fallback": embedding_fallback,
                 "amount_deviation": amount_deviation,
+                "currency": self.config.base_currency,
+                "baseline_average": baseline.average_amount,
+                "normalized_category": category,
+                "category_average": category_baseline.map(|c| c.average_amount),
+                "category_transaction_count": category_baseline.map(|c| c.transaction_count).unwrap_or(0),
                 "category_familiar": category_familiar,
+                "thin_history": thin_history,
+                "history_count": history_count,
+                "amount_percentile": amount_percentile.as_ref().map(|p| p.rank),
+                "outlier_threshold_amount": amount_percentile.as_ref().map(|p| p.threshold_amount),
+                "percentile_risk": percentile_risk,
+                "population_z_score": population_z_score,
+                "population_category_average": population.as_ref().map(|p| p.mean),
+                "low_risk_category": low_risk_category,
+                "high_risk_category": high_risk_category,
                 "fraud_in_similar": fraud_in_similar,
-                "similar_count": similar_txns.len()
+                "similar_fraud_rate": similar_fraud_rate,
+                "similar_shrinkage_applied": similar_shrunk,
+                "vector_search_abstained": vector_search_abstained,
+                "similar_count": similar_txns.len(),
+                "similar_transactions": similar_txns
+                    .iter()
+                    .take(SIMILAR_TRANSACTIONS_REPORTED)
+                    .collect::<Vec<_>>(),
+                "user_fraud_similarity": user_fraud_similarity,
+                "global_fraud_similarity": global_fraud_similarity,
+                "global_fraud_matches": global_fraud_matches,
+                "profile_novelty": profile_novelty,
+                "profile_size": profile.as_ref().map(|(_, count)| *count).unwrap_or(0),
+                "challenge_history": challenge_history,
+                "challenge_trust": challenge_trust
             }),
+            embedding: embeddings
+                .filter(|_| embedding_fallback.is_none())
+                .map(|(_, document_embedding)| document_embedding),
         })
     }
 
-    async fn get_user_baseline(&self, pool: &PgPool, user_id: &str) -> Result<UserBaseline> {
-        // First, try to get actual transaction history
-        let result = sqlx::query_as::<_, UserBaseline>(
+    /// Average stored embedding of a category's transactions
+    async fn category_default_embedding(
+        &self,
+        pool: &PgPool,
+        category: &str,
+    ) -> Result<Option<Vec<f32>>> {
+        let text = sqlx::query_scalar::<_, Option<String>>(
+            r#"
+            SELECT AVG(transaction_embedding)::text
+            FROM transactions
+            WHERE LOWER(merchant_category) = LOWER($1)
+            AND transaction_embedding IS NOT NULL
+            "#,
+        )
+        .bind(category.trim())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(text.and_then(|text| crate::embedding::pgvector_to_embedding(&text)))
+    }
+
+    /// The user's history in the 90 days before the transaction, itself
+    /// excluded, so a replayed or backfilled transaction sees what was known
+    /// when it happened
+    async fn get_user_baseline(
+        &self,
+        pool: &PgPool,
+        taxonomy: &CategoryTaxonomy,
+        transaction: &Transaction,
+    ) -> Result<UserBaseline> {
+        let user_id = transaction.user_id.as_str();
+        // First, try to get actual transaction history, per normalized category
+        let (aliases, canonicals) = taxonomy.sql_aliases();
+        let result = sqlx::query_as::<_, CategoryBaseline>(&format!(
             r#"
             SELECT 
-                COALESCE(AVG(amount), 0) as average_amount,
-                COALESCE(ARRAY_AGG(DISTINCT merchant_category), ARRAY[]::TEXT[]) as common_categories
+                {category} as merchant_category,
+                AVG(amount)::float8 as average_amount,
+                COUNT(*) as transaction_count
             FROM transactions
             WHERE user_id = $1
-            AND timestamp > NOW() - INTERVAL '90 days'
+            AND transaction_id != $5
+            AND timestamp > $4::timestamptz - INTERVAL '90 days'
+            AND timestamp < $4::timestamptz
             AND (fraud_label = false OR fraud_label IS NULL)
-            "#
-        )
+            GROUP BY 1
+            "#,
+            category = CategoryTaxonomy::sql_expr("merchant_category", 2)
+        ))
         .bind(user_id)
-        .fetch_one(pool)
+        .bind(aliases)
+        .bind(canonicals)
+        .bind(transaction.timestamp)
+        .bind(&transaction.transaction_id)
+        .fetch_all(pool)
         .await;
 
         match result {
-            Ok(baseline) => {
+            Ok(rows) => {
                 // If no transactions found, use user profile data
-                if baseline.average_amount == 0.0 {
+                if rows.is_empty() {
                     tracing::warn!("No transaction history for {}, using user profile", user_id);
-                    return self.get_user_profile_baseline(pool, user_id).await;
+                    return self.get_user_profile_baseline(pool, taxonomy, user_id).await;
                 }
+                let baseline = UserBaseline::from_categories(rows);
                 tracing::info!(
-                    "User {} baseline: avg=${:.2}, categories={:?}",
+                    "User {} baseline: avg={}, categories={:?}",
                     user_id,
-                    baseline.average_amount,
+                    self.format_amount(baseline.average_amount),
                     baseline.common_categories
                 );
                 Ok(baseline)
             }
             Err(e) => {
                 tracing::warn!("Failed to get baseline: {}, using user profile", e);
-                self.get_user_profile_baseline(pool, user_id).await
+                self.get_user_profile_baseline(pool, taxonomy, user_id).await
             }
         }
     }
 
-    // Add this new method to get baseline from user profile
+    /// Baseline from the `users` profile, for users without transaction history
     async fn get_user_profile_baseline(
         &self,
         pool: &PgPool,
+        taxonomy: &CategoryTaxonomy,
         user_id: &str,
     ) -> Result<UserBaseline> {
-        let result = sqlx::query_as::<_, UserBaseline>(
+        let profile = sqlx::query_as::<_, (f64, Vec<String>)>(
             r#"
             SELECT 
-                AVG(amount)::float8 as average_amount,
-                ARRAY_AGG(DISTINCT merchant_category) as common_categories
-            FROM transactions
+                COALESCE(average_transaction_amount, 0)::float8 as average_amount,
+                COALESCE(common_categories, ARRAY[]::TEXT[]) as common_categories
+            FROM users
             WHERE user_id = $1
-            AND timestamp > NOW() - INTERVAL '90 days'
-            GROUP BY user_id
             "#,
         )
         .bind(user_id)
         .fetch_optional(pool)
-        .await?
-        .unwrap();
+        .await?;
+
+        Ok(match profile {
+            Some((average_amount, common_categories)) => UserBaseline {
+                average_amount,
+                common_categories: common_categories
+                    .iter()
+                    .map(|c| taxonomy.normalize(c))
+                    .collect(),
+                ..Default::default()
+            },
+            None => UserBaseline::default(),
+        })
+    }
+
+    /// Fraction of the user's legitimate amounts at or below this one, and the
+    /// amount at the configured outlier percentile
+    async fn get_amount_percentile(
+        &self,
+        pool: &PgPool,
+        transaction: &Transaction,
+    ) -> Result<Option<AmountPercentile>> {
+        let percentile = sqlx::query_as::<_, AmountPercentile>(
+            r#"
+            SELECT
+                AVG((amount <= $2)::int)::float8 as rank,
+                percentile_cont($3) WITHIN GROUP (ORDER BY amount::float8) as threshold_amount,
+                COUNT(*) as history_count
+            FROM transactions
+            WHERE user_id = $1
+            AND transaction_id != $4
+            AND timestamp > $5::timestamptz - INTERVAL '90 days'
+            AND timestamp < $5::timestamptz
+            AND (fraud_label = false OR fraud_label IS NULL)
+            HAVING COUNT(*) > 0
+            "#,
+        )
+        .bind(&transaction.user_id)
+        .bind(transaction.amount)
+        .bind(self.config.outlier_percentile)
+        .bind(&transaction.transaction_id)
+        .bind(transaction.timestamp)
+        .fetch_optional(pool)
+        .await?;
 
-        Ok(result)
+        Ok(percentile)
+    }
 
-        // Ok(UserBaseline {
-        //     average_amount: profile.average_transaction_amount.unwrap_or(0.0),
-        //     common_categories: profile.common_categories.unwrap_or_default(),
-        // })
+    /// The user's spending centroid and how many transactions it summarizes
+    async fn get_spending_profile(
+        &self,
+        pool: &PgPool,
+        user_id: &str,
+    ) -> Result<Option<(Vec<f32>, i32)>> {
+        let row = sqlx::query_as::<_, (String, i32)>(
+            r#"
+            SELECT spending_centroid::text, COALESCE(centroid_count, 0)
+            FROM users
+            WHERE user_id = $1
+            AND spending_centroid IS NOT NULL
+            "#,
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
 
-        // Ok(UserBaseline {
-        //     average_amount:125.00,
-        //     common_categories: vec!["groceries".to_string(), "entertainment".to_string(), "utilities".to_string()],
-        // })
+        Ok(row.and_then(|(text, count)| {
+            crate::embedding::pgvector_to_embedding(&text).map(|centroid| (centroid, count))
+        }))
     }
 
+    /// The user's stored transactions nearest to `embedding`, leaving out
+    /// `transaction` itself
     async fn find_similar_transactions(
         &self,
         pool: &PgPool,
         embedding: &[f32],
-        user_id: &str,
+        transaction: &Transaction,
         limit: i32,
+        precision: EmbeddingPrecision,
+        metric: DistanceMetric,
     ) -> Result<Vec<SimilarTxn>> {
         let embedding_str = crate::embedding::embedding_to_pgvector(embedding);
 
-        let rows = sqlx::query_as::<_, SimilarTxn>(
+        let rows = sqlx::query_as::<_, SimilarTxn>(&format!(
             r#"
             SELECT 
                 transaction_id,
                 fraud_label,
-                (1 - (transaction_embedding <=> $1::vector)) as similarity
+                {similarity} as similarity
             FROM transactions
             WHERE user_id = $2
             AND transaction_embedding IS NOT NULL
-            ORDER BY transaction_embedding <=> $1::vector
+            AND transaction_id != $4
+            ORDER BY transaction_embedding {distance} $1::{vector}
             LIMIT $3
             "#,
-        )
+            vector = precision.sql_type(),
+            similarity = metric.similarity_sql("transaction_embedding", "$1", precision),
+            distance = metric.operator()
+        ))
+        .bind(embedding_str)
+        .bind(&transaction.user_id)
+        .bind(limit)
+        .bind(&transaction.transaction_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Nearest confirmed-fraud transactions across all users
+    async fn find_similar_fraud_global(
+        &self,
+        pool: &PgPool,
+        embedding: &[f32],
+        limit: i32,
+        exclude_transaction_id: Option<&str>,
+        precision: EmbeddingPrecision,
+        metric: DistanceMetric,
+    ) -> Result<Vec<SimilarTxn>> {
+        let embedding_str = crate::embedding::embedding_to_pgvector(embedding);
+
+        let rows = sqlx::query_as::<_, SimilarTxn>(&format!(
+            r#"
+            SELECT 
+                transaction_id,
+                fraud_label,
+                {similarity} as similarity
+            FROM transactions
+            WHERE fraud_label = true
+            AND transaction_embedding IS NOT NULL
+            AND ($3::text IS NULL OR transaction_id != $3)
+            ORDER BY transaction_embedding {distance} $1::{vector}
+            LIMIT $2
+            "#,
+            vector = precision.sql_type(),
+            similarity = metric.similarity_sql("transaction_embedding", "$1", precision),
+            distance = metric.operator()
+        ))
         .bind(embedding_str)
-        .bind(user_id)
         .bind(limit)
+        .bind(exclude_transaction_id)
         .fetch_all(pool)
         .await?;
 
@@ -247,8 +929,46 @@ impl PatternAgent {
     }
 }
 
-#[derive(sqlx::FromRow, Debug, Default)]
+#[derive(sqlx::FromRow, Debug)]
+struct AmountPercentile {
+    rank: f64,
+    threshold_amount: f64,
+    history_count: i64,
+}
+
+#[derive(sqlx::FromRow, Debug, Clone)]
+struct CategoryBaseline {
+    merchant_category: String,
+    average_amount: f64,
+    transaction_count: i64,
+}
+
+#[derive(Debug, Default)]
 struct UserBaseline {
     average_amount: f64,
     common_categories: Vec<String>,
+    categories: HashMap<String, CategoryBaseline>,
+}
+
+impl UserBaseline {
+    fn from_categories(rows: Vec<CategoryBaseline>) -> Self {
+        let total_count: i64 = rows.iter().map(|c| c.transaction_count).sum();
+        let total_amount: f64 = rows
+            .iter()
+            .map(|c| c.average_amount * c.transaction_count as f64)
+            .sum();
+
+        Self {
+            average_amount: if total_count > 0 {
+                total_amount / total_count as f64
+            } else {
+                0.0
+            },
+            common_categories: rows.iter().map(|c| c.merchant_category.clone()).collect(),
+            categories: rows
+                .into_iter()
+                .map(|c| (c.merchant_category.clone(), c))
+                .collect(),
+        }
+    }
 }
@@ -1,104 +1,1110 @@
 use anyhow::Result;
 use sqlx::PgPool;
-use std::time::Instant;
+use std::{collections::HashMap, future::Future, sync::Arc, time::{Duration, Instant}};
 
-use crate::{AppState, agents::{anomaly::AnomalyAgent, geographic::GeographicAgent, merchant::MerchantAgent, network::NetworkAgent, pattern::PatternAgent}, models::transaction::{AgentScores, AnalysisResult, TransactionRequest}};
+use crate::{AppState, db::{denylist::{self, DenylistEntry}, persistence, user_overrides::UserOverride}, config::{self, Config, ConfigError, ConfigSource}, agents::{anomaly::AnomalyAgent, geographic::GeographicAgent, merchant::MerchantAgent, network::NetworkAgent, pattern::PatternAgent}, models::transaction::{AgentReason, AgentScore, AgentScores, AgentStatus, AgentWeights, AnalysisResult, AppliedWeights, Decision, DecisionSource, Reason, ReasonCode, RingSeverity, RiskBand, Transaction, TransactionRequest}, i18n::{self, Locale}, post_processing::{self, AnalysisContext, DecisionPostProcessor}};
 
+/// Aggregate score cutoffs between APPROVE, CHALLENGE and BLOCK. A score
+/// must exceed a cutoff: one exactly on it gets the lower decision
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct DecisionThresholds {
+    /// Scores above this are challenged
+    pub challenge: f64,
+    /// Scores above this are blocked
+    pub block: f64,
+}
+
+impl Default for DecisionThresholds {
+    fn default() -> Self {
+        Self {
+            challenge: 0.4,
+            block: 0.7,
+        }
+    }
+}
+
+impl DecisionThresholds {
+    /// Read `FRAUD_THRESHOLD_CHALLENGE` and `FRAUD_THRESHOLD_BLOCK`
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            challenge: config::parsed(source, "FRAUD_THRESHOLD_CHALLENGE", defaults.challenge)?,
+            block: config::parsed(source, "FRAUD_THRESHOLD_BLOCK", defaults.block)?,
+        })
+    }
+
+    /// Both within 0..=1 and `challenge` below `block`
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.challenge) || !(0.0..=1.0).contains(&self.block) {
+            return Err(format!(
+                "thresholds must be between 0 and 1, got challenge {} and block {}",
+                self.challenge, self.block
+            ));
+        }
+        if self.challenge >= self.block {
+            return Err(format!(
+                "challenge threshold {} must be below block threshold {}",
+                self.challenge, self.block
+            ));
+        }
+        Ok(())
+    }
+
+    /// Map an aggregated risk score onto its band
+    pub fn band(&self, score: f64) -> RiskBand {
+        if score > self.block {
+            RiskBand::High
+        } else if score > self.challenge {
+            RiskBand::Medium
+        } else {
+            RiskBand::Low
+        }
+    }
+
+    /// Decision for an aggregated risk score before any overrides
+    pub fn decision(&self, score: f64) -> Decision {
+        match self.band(score) {
+            RiskBand::High => Decision::Block,
+            RiskBand::Medium => Decision::Challenge,
+            RiskBand::Low => Decision::Approve,
+        }
+    }
+
+    /// Lowest aggregate score that leads to `decision`
+    fn floor(&self, decision: Decision) -> f64 {
+        match decision {
+            Decision::Approve => 0.0,
+            Decision::Challenge => self.challenge,
+            Decision::Block => self.block,
+        }
+    }
+}
+
+/// Keep `previous` unless `score` has moved at least `margin` past the
+/// threshold separating it from `current`. Only a decision one band away
+/// is held, a score that jumped across both thresholds always moves
+fn apply_hysteresis(
+    thresholds: &DecisionThresholds,
+    previous: Decision,
+    current: Decision,
+    score: f64,
+    margin: f64,
+) -> Decision {
+    let crossed = match (previous, current) {
+        (Decision::Approve, Decision::Block) | (Decision::Block, Decision::Approve) => true,
+        _ if current > previous => score > thresholds.floor(current) + margin,
+        _ if current < previous => score <= thresholds.floor(previous) - margin,
+        _ => true,
+    };
+
+    if crossed { current } else { previous }
+}
+
+/// How much evidence the pattern agent had to work with, from 0 (brand-new
+/// user, nothing to compare against) to 1 (full history and embeddings)
+fn data_sufficiency(pattern_details: &serde_json::Value, history_target: i64) -> f64 {
+    let count = |key: &str| pattern_details.get(key).and_then(|v| v.as_i64()).unwrap_or(0);
+
+    let history = if history_target > 0 {
+        (count("history_count") as f64 / history_target as f64).min(1.0)
+    } else {
+        1.0
+    };
+    let similar = if count("similar_count") > 0 { 1.0 } else { 0.0 };
+    let profile = if count("profile_size") > 0 { 1.0 } else { 0.0 };
+
+    0.5 * history + 0.3 * similar + 0.2 * profile
+}
+
+/// Weighted average of agent scores. With `use_confidence` each static
+/// weight is scaled by the agent's confidence and the weights renormalized,
+/// so agents working from thin data count for less.
+fn weighted_average(scores: &[(&AgentScore, f64)], use_confidence: bool) -> f64 {
+    let effective_weight = |score: &AgentScore, weight: f64| {
+        if use_confidence {
+            weight * score.confidence.clamp(0.0, 1.0)
+        } else {
+            weight
+        }
+    };
+
+    let total_weight: f64 = scores.iter().map(|(score, weight)| effective_weight(score, *weight)).sum();
+    // Every agent at zero confidence: nothing to renormalize, use the static
+    // weights (still renormalized, agents may be missing after a deadline)
+    if total_weight <= 0.0 {
+        let static_total: f64 = scores.iter().map(|(_, weight)| weight).sum();
+        if static_total <= 0.0 {
+            return 0.0;
+        }
+        return scores.iter().map(|(score, weight)| score.risk_score * weight).sum::<f64>() / static_total;
+    }
+
+    scores
+        .iter()
+        .map(|(score, weight)| score.risk_score * effective_weight(score, *weight))
+        .sum::<f64>()
+        / total_weight
+}
+
+/// Reason reported for a denylisted merchant
+fn denylist_reason(transaction: &Transaction, entry: &DenylistEntry) -> Reason {
+    Reason::new(ReasonCode::MerchantDenylisted)
+        .with("merchant", &transaction.merchant)
+        .with("reason", entry.reason.as_deref().unwrap_or("no reason given"))
+}
+
+/// Run an agent, giving up on it at `deadline`. `Ok(None)` when it didn't
+/// finish in time. Only an agent that yields can be given up on: CPU work
+/// such as the embedding runs off the runtime and is awaited, never inline
+async fn within_deadline(
+    deadline: Option<tokio::time::Instant>,
+    agent: impl Future<Output = Result<AgentScore>>,
+) -> Result<Option<AgentScore>> {
+    match deadline {
+        None => agent.await.map(Some),
+        Some(deadline) => match tokio::time::timeout_at(deadline, agent).await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        },
+    }
+}
+
+/// An agent's score if it finished, and how its run ended. A failure
+/// fails the analysis unless `tolerate_errors`, then the agent is left out
+/// like one that timed out and its error kept in `errors`
+fn settle(
+    agent: &str,
+    result: Result<Option<AgentScore>>,
+    tolerate_errors: bool,
+    errors: &mut Vec<anyhow::Error>,
+) -> Result<(Option<AgentScore>, AgentStatus)> {
+    match result {
+        Ok(Some(score)) => Ok((Some(score), AgentStatus::Ran)),
+        Ok(None) => Ok((None, AgentStatus::TimedOut)),
+        Err(e) if tolerate_errors => {
+            tracing::error!("❌ {} agent failed, deciding without it: {:#}", agent, e);
+            let status = AgentStatus::Errored(format!("{:#}", e));
+            errors.push(e);
+            Ok((None, status))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Reject NaN/Infinity before it reaches aggregation or the response:
+/// serde_json serializes non-finite floats as `null`, which clients can't
+/// tell apart from a missing score.
+fn ensure_finite(label: &str, value: f64) -> Result<f64> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        tracing::error!("❌ {} produced a non-finite score: {}", label, value);
+        anyhow::bail!("{} produced a non-finite score ({})", label, value)
+    }
+}
+
+/// Cheap check run before the agents that can decide on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gate {
+    /// APPROVE amounts below `min_analysis_amount` unscored
+    MinAmount,
+    /// BLOCK merchants on the denylist unscored
+    MerchantDenylist,
+}
+
+impl std::str::FromStr for Gate {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "min_amount" => Ok(Gate::MinAmount),
+            "merchant_denylist" => Ok(Gate::MerchantDenylist),
+            other => Err(format!(
+                "Unknown gate '{}', expected min_amount or merchant_denylist",
+                other
+            )),
+        }
+    }
+}
+
+/// Tunables for the orchestrator
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AnalyzerConfig {
+    /// Confidence reported when a fraud ring forces a BLOCK
+    pub fraud_ring_confidence: f64,
+    /// Network agent score required before a detected ring overrides the
+    /// aggregate decision, 0 trusts the ring flag alone
+    pub fraud_ring_min_network_score: f64,
+    /// Weakest ring severity that forces a BLOCK, weaker rings only count
+    /// through the network agent's score
+    pub fraud_ring_block_severity: RingSeverity,
+    /// Aggregate score cutoffs of the decisions
+    pub thresholds: DecisionThresholds,
+    /// How far past a threshold a resubmitted transaction's score must move
+    /// before its stored decision changes. 0 disables hysteresis.
+    pub hysteresis_margin: f64,
+    /// Data sufficiency below which an APPROVE is downgraded to CHALLENGE,
+    /// 0 never downgrades
+    pub min_approval_sufficiency: f64,
+    /// Transactions of history counted as full sufficiency
+    pub sufficiency_history_target: i64,
+    /// Risk added to the aggregate per payment method (lowercase)
+    pub payment_method_risk: HashMap<String, f64>,
+    /// Risk for payment methods missing from `payment_method_risk`
+    pub default_payment_method_risk: f64,
+    /// Global agent weights, summing to 1
+    pub agent_weights: AgentWeights,
+    /// Agent weights for transactions in a merchant category (by canonical
+    /// name), instead of the global ones
+    pub category_weights: HashMap<String, AgentWeights>,
+    /// Scale each agent's weight by its self-reported confidence
+    pub confidence_weighting: bool,
+    /// Leave agents that had no data to judge by out of the weighted
+    /// average, instead of counting their default low score as "safe"
+    pub exclude_no_data_agents: bool,
+    /// Amounts below this are approved without running the agents, 0 always analyzes
+    pub min_analysis_amount: f64,
+    /// Budget for the whole analysis in milliseconds. Agents still running
+    /// when it passes are dropped and the decision uses the ones that
+    /// finished. 0 waits for every agent
+    pub deadline_ms: u64,
+    /// Run the agents one after another in a fixed order instead of
+    /// concurrently: deterministic logs and per-agent database load. Scores
+    /// match a concurrent run, except under `deadline_ms`: the budget then
+    /// covers the agents' summed latencies, so later agents time out sooner.
+    /// For tests and debugging
+    pub sequential_agents: bool,
+    /// Decide without an agent that fails instead of failing the analysis,
+    /// renormalizing over the rest like after a deadline
+    pub tolerate_agent_errors: bool,
+    /// Checks run in order before the agents, the first that fires decides
+    /// and the agents are skipped. The denylist is still enforced when it
+    /// isn't a gate, checked alongside the agents and applied after scoring;
+    /// the amount threshold only applies as a gate
+    pub gates: Vec<Gate>,
+    /// How long after a challenge at a merchant was cleared as legitimate
+    /// the user's transactions there are dampened, 0 disables the cool-down
+    pub cooldown_window_secs: i64,
+    /// Share of the aggregate score removed during the cool-down
+    pub cooldown_dampening: f64,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            fraud_ring_confidence: 0.95,
+            fraud_ring_min_network_score: 0.0,
+            fraud_ring_block_severity: RingSeverity::Likely,
+            thresholds: DecisionThresholds::default(),
+            hysteresis_margin: 0.0,
+            min_approval_sufficiency: 0.0,
+            sufficiency_history_target: 10,
+            payment_method_risk: HashMap::from([
+                ("credit_card".to_string(), 0.0),
+                ("debit_card".to_string(), 0.0),
+                // Card-present, as the geographic agent's
+                // `GEO_CARD_PRESENT_PAYMENT_METHODS` name them. A magstripe
+                // swipe can be a cloned card, chip and contactless can't
+                ("chip".to_string(), 0.0),
+                ("contactless".to_string(), 0.0),
+                ("card_present".to_string(), 0.0),
+                ("pos".to_string(), 0.0),
+                ("swipe".to_string(), 0.02),
+                ("bank_transfer".to_string(), 0.02),
+                ("digital_wallet".to_string(), 0.02),
+                ("prepaid_card".to_string(), 0.1),
+                ("wire_transfer".to_string(), 0.1),
+                ("gift_card".to_string(), 0.15),
+                ("crypto".to_string(), 0.15),
+            ]),
+            default_payment_method_risk: 0.05,
+            agent_weights: AgentWeights::default(),
+            category_weights: HashMap::new(),
+            confidence_weighting: false,
+            exclude_no_data_agents: false,
+            min_analysis_amount: 0.0,
+            deadline_ms: 0,
+            sequential_agents: false,
+            tolerate_agent_errors: false,
+            // Denylist first, a small amount at a blocked merchant is still blocked
+            gates: vec![Gate::MerchantDenylist, Gate::MinAmount],
+            cooldown_window_secs: 0,
+            cooldown_dampening: 0.5,
+        }
+    }
+}
+
+/// Global agent weights: all five from the JSON file at `AGENT_WEIGHTS_FILE`
+/// or inline in `AGENT_WEIGHTS`, then `AGENT_WEIGHT_<AGENT>` for single agents
+fn agent_weights_from_source(source: &dyn ConfigSource, default: AgentWeights) -> Result<AgentWeights, ConfigError> {
+    let mut weights = match source.get("AGENT_WEIGHTS_FILE") {
+        Some(path) => {
+            let invalid = |reason: String| ConfigError::Invalid {
+                key: "AGENT_WEIGHTS_FILE".to_string(),
+                value: path.clone(),
+                reason,
+            };
+            let json = std::fs::read_to_string(&path).map_err(|e| invalid(e.to_string()))?;
+            serde_json::from_str(&json).map_err(|e| invalid(e.to_string()))?
+        }
+        None => config::json(source, "AGENT_WEIGHTS", default)?,
+    };
+    weights.pattern = config::parsed(source, "AGENT_WEIGHT_PATTERN", weights.pattern)?;
+    weights.anomaly = config::parsed(source, "AGENT_WEIGHT_ANOMALY", weights.anomaly)?;
+    weights.geographic = config::parsed(source, "AGENT_WEIGHT_GEOGRAPHIC", weights.geographic)?;
+    weights.merchant = config::parsed(source, "AGENT_WEIGHT_MERCHANT", weights.merchant)?;
+    weights.network = config::parsed(source, "AGENT_WEIGHT_NETWORK", weights.network)?;
+    Ok(weights)
+}
+
+impl AnalyzerConfig {
+    /// Read the orchestrator settings: fraud ring handling (`FRAUD_RING_*`),
+    /// the global agent weights (`AGENT_WEIGHTS`, `AGENT_WEIGHTS_FILE`,
+    /// `AGENT_WEIGHT_<AGENT>`) and per-category ones, the decision thresholds
+    /// (`FRAUD_THRESHOLD_*`), gates, deadline and the other `ANALYSIS_*` knobs
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            fraud_ring_confidence: config::parsed(
+                source,
+                "FRAUD_RING_CONFIDENCE",
+                defaults.fraud_ring_confidence,
+            )?,
+            fraud_ring_min_network_score: config::parsed(
+                source,
+                "FRAUD_RING_MIN_NETWORK_SCORE",
+                defaults.fraud_ring_min_network_score,
+            )?,
+            fraud_ring_block_severity: config::parsed(
+                source,
+                "FRAUD_RING_BLOCK_SEVERITY",
+                defaults.fraud_ring_block_severity,
+            )?,
+            thresholds: DecisionThresholds::from_source(source)?,
+            hysteresis_margin: config::parsed(
+                source,
+                "DECISION_HYSTERESIS_MARGIN",
+                defaults.hysteresis_margin,
+            )?,
+            min_approval_sufficiency: config::parsed(
+                source,
+                "MIN_APPROVAL_SUFFICIENCY",
+                defaults.min_approval_sufficiency,
+            )?,
+            sufficiency_history_target: config::parsed(
+                source,
+                "SUFFICIENCY_HISTORY_TARGET",
+                defaults.sufficiency_history_target,
+            )?,
+            payment_method_risk: config::float_map(
+                source,
+                "PAYMENT_METHOD_RISK",
+                defaults.payment_method_risk,
+            )?,
+            default_payment_method_risk: config::parsed(
+                source,
+                "DEFAULT_PAYMENT_METHOD_RISK",
+                defaults.default_payment_method_risk,
+            )?,
+            agent_weights: agent_weights_from_source(source, defaults.agent_weights)?,
+            category_weights: config::json(source, "CATEGORY_AGENT_WEIGHTS", defaults.category_weights)?,
+            confidence_weighting: config::parsed(
+                source,
+                "AGENT_CONFIDENCE_WEIGHTING",
+                defaults.confidence_weighting,
+            )?,
+            exclude_no_data_agents: config::parsed(
+                source,
+                "EXCLUDE_NO_DATA_AGENTS",
+                defaults.exclude_no_data_agents,
+            )?,
+            min_analysis_amount: config::parsed(
+                source,
+                "MIN_ANALYSIS_AMOUNT",
+                defaults.min_analysis_amount,
+            )?,
+            deadline_ms: config::parsed(source, "ANALYSIS_DEADLINE_MS", defaults.deadline_ms)?,
+            sequential_agents: config::parsed(
+                source,
+                "ANALYSIS_SEQUENTIAL_AGENTS",
+                defaults.sequential_agents,
+            )?,
+            tolerate_agent_errors: config::parsed(
+                source,
+                "ANALYSIS_TOLERATE_AGENT_ERRORS",
+                defaults.tolerate_agent_errors,
+            )?,
+            gates: config::parsed_list(source, "ANALYSIS_GATES", defaults.gates)?,
+            cooldown_window_secs: config::parsed(
+                source,
+                "COOLDOWN_WINDOW_SECS",
+                defaults.cooldown_window_secs,
+            )?,
+            cooldown_dampening: config::parsed(
+                source,
+                "COOLDOWN_DAMPENING",
+                defaults.cooldown_dampening,
+            )?,
+        })
+    }
+
+    /// Risk prior for a payment instrument, unknown methods get the default
+    pub fn payment_method_risk(&self, payment_method: &str) -> f64 {
+        self.payment_method_risk
+            .get(&payment_method.trim().to_lowercase())
+            .copied()
+            .unwrap_or(self.default_payment_method_risk)
+    }
+}
 
 /// Orchestrates fraud analysis using multiple agents
 pub struct FraudAnalyzer {
+    config: AnalyzerConfig,
+    model_version: String,
     pattern_agent: PatternAgent,
     anomaly_agent: AnomalyAgent,
     geographic_agent: GeographicAgent,
     merchant_agent: MerchantAgent,
     network_agent: NetworkAgent,
+    /// Custom rules run on every result, in order
+    post_processors: Vec<Arc<dyn DecisionPostProcessor>>,
 }
 
 impl FraudAnalyzer {
-    pub fn new(_pool: PgPool) -> Self {
-        Self {
-            pattern_agent: PatternAgent::new(),
-            anomaly_agent: AnomalyAgent::new(),
-            geographic_agent: GeographicAgent::new(),
-            merchant_agent: MerchantAgent::new(),
-            network_agent: NetworkAgent::new(),
+    /// Build the orchestrator and its agents from the loaded configuration.
+    /// Fails when the global agent weights don't sum to 1 or the decision
+    /// thresholds are out of order
+    pub fn new(config: &Config) -> Result<Self, ConfigError> {
+        config
+            .analyzer
+            .agent_weights
+            .validate()
+            .map_err(|e| ConfigError::Validation(format!("Invalid agent weights: {}", e)))?;
+        config
+            .analyzer
+            .thresholds
+            .validate()
+            .map_err(|e| ConfigError::Validation(format!("Invalid decision thresholds: {}", e)))?;
+
+        // Looked up by the transaction's canonical category
+        let mut analyzer_config = config.analyzer.clone();
+        analyzer_config.category_weights = analyzer_config
+            .category_weights
+            .into_iter()
+            .map(|(category, weights)| (config.category_taxonomy.normalize(&category), weights))
+            .collect();
+
+        Ok(Self {
+            config: analyzer_config,
+            model_version: config.model_version(),
+            pattern_agent: PatternAgent::with_config(config.pattern.clone()),
+            anomaly_agent: AnomalyAgent::with_config(config.anomaly.clone()),
+            geographic_agent: GeographicAgent::with_config(config.geographic.clone()),
+            merchant_agent: MerchantAgent::with_config(config.merchant.clone()),
+            network_agent: NetworkAgent::with_config(config.network.clone()),
+            post_processors: Vec::new(),
+        })
+    }
+
+    /// Register custom rules to run after each analysis, in order
+    pub fn with_post_processors(mut self, processors: Vec<Arc<dyn DecisionPostProcessor>>) -> Self {
+        self.post_processors.extend(processors);
+        self
+    }
+
+    pub fn post_processors(&self) -> &[Arc<dyn DecisionPostProcessor>] {
+        &self.post_processors
+    }
+
+    /// Weights for a transaction in `category`, the category's override if
+    /// it has one
+    fn weights_for(&self, state: &AppState, category: &str) -> AppliedWeights {
+        let category = state.config.category_taxonomy.normalize(category);
+        match self.config.category_weights.get(&category) {
+            Some(weights) => AppliedWeights {
+                category: Some(category),
+                weights: *weights,
+            },
+            None => AppliedWeights {
+                category: None,
+                weights: self.config.agent_weights,
+            },
         }
     }
 
+    /// Version stamped on every result, see `Config::model_version`
+    pub fn model_version(&self) -> &str {
+        &self.model_version
+    }
+
     /// Analyze a transaction for fraud using all 5 agents
+    ///
+    /// A high-risk transaction is reported through the decision, never as an
+    /// `Err`. Errors mean the transaction could not be scored at all.
     pub async fn analyze_transaction(
         &self,
         pool: &PgPool,
         state: &AppState,
         request: TransactionRequest,
     ) -> Result<AnalysisResult> {
-        let start = Instant::now();
         let transaction = request.to_transaction();
+        self.analyze(pool, state, &transaction).await
+    }
+
+    /// Decision made without running the agents, marked by `decision_source`
+    /// so it isn't mistaken for an evaluation
+    fn unscored_result(
+        &self,
+        transaction: &Transaction,
+        start: Instant,
+        decision: Decision,
+        decision_source: DecisionSource,
+        reasoning: String,
+    ) -> AnalysisResult {
+        AnalysisResult {
+            transaction_id: transaction.transaction_id.clone(),
+            decision,
+            decision_source,
+            overridden_decision: None,
+            risk_score: 0.0,
+            raw_aggregate_score: 0.0,
+            risk_band: RiskBand::Low,
+            confidence: 0.0,
+            data_sufficiency: 0.0,
+            payment_method_risk: 0.0,
+            model_version: self.model_version.clone(),
+            latency_ms: start.elapsed().as_millis() as u64,
+            agent_scores: AgentScores {
+                pattern: 0.0,
+                anomaly: 0.0,
+                geographic: 0.0,
+                merchant: 0.0,
+                network: 0.0,
+            },
+            fraud_ring_detected: false,
+            fraud_ring_severity: None,
+            hysteresis_applied: false,
+            cooldown_applied: false,
+            user_risk_multiplier: None,
+            reasoning,
+            agent_reasons: Vec::new(),
+            partial: false,
+            timed_out_agents: Vec::new(),
+            excluded_agents: Vec::new(),
+            agent_status: HashMap::new(),
+            persisted: false,
+            signature: None,
+            embedding: None,
+            applied_weights: None,
+        }
+    }
+
+    /// APPROVE for amounts not worth analyzing
+    fn skipped_result(&self, transaction: &Transaction, start: Instant) -> AnalysisResult {
+        self.unscored_result(
+            transaction,
+            start,
+            Decision::Approve,
+            DecisionSource::BelowAnalysisThreshold,
+            format!(
+                "Below analysis threshold: amount {:.2} is under {:.2}, agents not run",
+                transaction.amount, self.config.min_analysis_amount
+            ),
+        )
+    }
+
+    /// BLOCK for a denylisted merchant, reported as the merchant agent's reason
+    fn denylisted_result(&self, transaction: &Transaction, entry: &DenylistEntry, start: Instant) -> AnalysisResult {
+        let reason = denylist_reason(transaction, entry);
+        let rendered = i18n::render(Locale::English, &reason);
+
+        let mut result = self.unscored_result(
+            transaction,
+            start,
+            Decision::Block,
+            DecisionSource::MerchantDenylist,
+            format!("Merchant: {}", rendered),
+        );
+        result.risk_score = 1.0;
+        result.risk_band = RiskBand::High;
+        result.confidence = 1.0;
+        result.agent_scores.merchant = 1.0;
+        result.agent_reasons = vec![AgentReason {
+            agent: "merchant".to_string(),
+            score: 1.0,
+            reason: rendered,
+            reason_codes: vec![reason.code],
+            data_available: true,
+            data_note: None,
+            reasons: vec![reason],
+            details: serde_json::Value::Null,
+        }];
+        result
+    }
+
+    /// Forced decision for a user with an override, without running anything else
+    fn user_override_result(
+        &self,
+        transaction: &Transaction,
+        entry: &UserOverride,
+        decision: Decision,
+        start: Instant,
+    ) -> AnalysisResult {
+        let mut result = self.unscored_result(
+            transaction,
+            start,
+            decision,
+            DecisionSource::UserOverride,
+            format!(
+                "User override: {} for user {}{}",
+                decision,
+                entry.user_id,
+                entry.reason.as_deref().map(|reason| format!(" ({})", reason)).unwrap_or_default()
+            ),
+        );
+        result.confidence = 1.0;
+        result
+    }
+
+    /// Run one gate, `Some` when it decided
+    async fn run_gate(
+        &self,
+        gate: Gate,
+        pool: &PgPool,
+        transaction: &Transaction,
+        start: Instant,
+    ) -> Result<Option<AnalysisResult>> {
+        match gate {
+            // Micro-transactions aren't worth the queries and the embedding
+            Gate::MinAmount => {
+                if transaction.amount >= self.config.min_analysis_amount {
+                    return Ok(None);
+                }
+                tracing::info!(
+                    "⏭️ Skipping analysis of {}: amount {:.2} below {:.2}",
+                    transaction.transaction_id,
+                    transaction.amount,
+                    self.config.min_analysis_amount
+                );
+                Ok(Some(self.skipped_result(transaction, start)))
+            }
+            // Hard rule on top of the scoring, no point running the agents
+            Gate::MerchantDenylist => {
+                let Some(entry) = denylist::find_entry(pool, transaction).await? else {
+                    return Ok(None);
+                };
+                tracing::warn!(
+                    "🚫 Blocking {}: merchant '{}' is denylisted",
+                    transaction.transaction_id,
+                    transaction.merchant
+                );
+                Ok(Some(self.denylisted_result(transaction, &entry, start)))
+            }
+        }
+    }
+
+    /// Denylist lookup alongside the agents when it isn't configured as a gate
+    async fn post_scoring_denylist_check(
+        &self,
+        pool: &PgPool,
+        transaction: &Transaction,
+    ) -> Result<Option<DenylistEntry>> {
+        if self.config.gates.contains(&Gate::MerchantDenylist) {
+            return Ok(None);
+        }
+        denylist::find_entry(pool, transaction).await
+    }
+
+    /// Whether the user/merchant pair is in its cool-down after a cleared challenge
+    async fn cooldown_active(&self, pool: &PgPool, transaction: &Transaction) -> Result<bool> {
+        if self.config.cooldown_window_secs <= 0 {
+            return Ok(false);
+        }
+        persistence::recently_cleared_challenge(pool, transaction, self.config.cooldown_window_secs).await
+    }
+
+    /// Score an already-built transaction, e.g. one replayed from history
+    pub async fn analyze(
+        &self,
+        pool: &PgPool,
+        state: &AppState,
+        transaction: &Transaction,
+    ) -> Result<AnalysisResult> {
+        let mut result = self.score(pool, state, transaction).await?;
+        if !self.post_processors.is_empty() {
+            let ctx = AnalysisContext { transaction, state };
+            result = post_processing::apply_chain(&self.post_processors, &ctx, result);
+        }
+        tracing::info!("🧾 {}", result.log_summary());
+        Ok(result)
+    }
+
+    /// The orchestrator's own decision, before any post-processor
+    async fn score(
+        &self,
+        pool: &PgPool,
+        state: &AppState,
+        transaction: &Transaction,
+    ) -> Result<AnalysisResult> {
+        let start = Instant::now();
+
+        // Operator decisions about this user come before anything else:
+        // test accounts and accounts under manual handling
+        // A failed lookup costs the override, not the analysis. Operator
+        // decisions (overrides, the denylist, cleared challenges) are read
+        // from the primary like the hysteresis below: a lagging replica
+        // would score as if a just-made edit hadn't happened
+        let user_override = match state.user_overrides.find(&state.pool, &transaction.user_id).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("User override lookup failed for {}, scoring without: {}", transaction.user_id, e);
+                None
+            }
+        };
+        if let Some(entry) = &user_override && let Some(decision) = entry.forced_decision() {
+            tracing::info!(
+                "👤 User override for {}: {} without scoring",
+                transaction.user_id,
+                decision
+            );
+            return Ok(self.user_override_result(transaction, entry, decision, start));
+        }
+        let user_risk_multiplier = user_override.and_then(|entry| entry.risk_multiplier);
+
+        // Gate stage: cheap checks that make the embedding and agent queries
+        // unnecessary on obvious cases
+        for &gate in &self.config.gates {
+            if let Some(result) = self.run_gate(gate, &state.pool, transaction, start).await? {
+                return Ok(result);
+            }
+        }
 
         tracing::info!("🔍 Analyzing transaction: {}", transaction.transaction_id);
-        tracing::info!("🤖 Running all 5 fraud detection agents in parallel...");
-
-        // Run all agents in parallel for maximum performance
-        let (pattern_result, anomaly_result, geo_result, merchant_result, network_result) = tokio::join!(
-            self.pattern_agent.analyze(pool, state, &transaction),
-            self.anomaly_agent.analyze(pool, &transaction),
-            self.geographic_agent.analyze(pool, &transaction),
-            self.merchant_agent.analyze(pool, state, &transaction),
-            self.network_agent.analyze(pool, &transaction),
+        // Run all agents in parallel for maximum performance, within the
+        // latency budget when one is configured. Sequential runs await the
+        // same futures in the same order, so only scheduling differs
+        let deadline = (self.config.deadline_ms > 0).then(|| {
+            tokio::time::Instant::from_std(start) + Duration::from_millis(self.config.deadline_ms)
+        });
+        let (pattern, anomaly, geographic, merchant, network, denylist, cooldown) = (
+            within_deadline(deadline, self.pattern_agent.analyze(pool, state, transaction)),
+            within_deadline(deadline, self.anomaly_agent.analyze(pool, transaction)),
+            within_deadline(deadline, self.geographic_agent.analyze(pool, transaction)),
+            within_deadline(deadline, self.merchant_agent.analyze(pool, state, transaction)),
+            within_deadline(deadline, self.network_agent.analyze(pool, state, transaction)),
+            self.post_scoring_denylist_check(&state.pool, transaction),
+            self.cooldown_active(&state.pool, transaction),
         );
+        let (
+            pattern_result,
+            anomaly_result,
+            geo_result,
+            merchant_result,
+            network_result,
+            denylist_result,
+            cooldown_result,
+        ) = if self.config.sequential_agents {
+            tracing::info!("🤖 Running all 5 fraud detection agents sequentially...");
+            (
+                pattern.await,
+                anomaly.await,
+                geographic.await,
+                merchant.await,
+                network.await,
+                denylist.await,
+                cooldown.await,
+            )
+        } else {
+            tracing::info!("🤖 Running all 5 fraud detection agents in parallel...");
+            tokio::join!(pattern, anomaly, geographic, merchant, network, denylist, cooldown)
+        };
+        let denylisted = denylist_result?;
+        let cooldown_applied = cooldown_result?;
+
+        // `None` for agents cut off by the deadline, or that failed when
+        // failures are tolerated
+        let tolerate = self.config.tolerate_agent_errors;
+        let mut agent_errors = Vec::new();
+        let (pattern_score, pattern_status) = settle("pattern", pattern_result, tolerate, &mut agent_errors)?;
+        let (anomaly_score, anomaly_status) = settle("anomaly", anomaly_result, tolerate, &mut agent_errors)?;
+        let (geographic_score, geographic_status) = settle("geographic", geo_result, tolerate, &mut agent_errors)?;
+        let (merchant_score, merchant_status) = settle("merchant", merchant_result, tolerate, &mut agent_errors)?;
+        let (network_score, network_status) = settle("network", network_result, tolerate, &mut agent_errors)?;
+        let mut agent_status = HashMap::from([
+            ("pattern".to_string(), pattern_status),
+            ("anomaly".to_string(), anomaly_status),
+            ("geographic".to_string(), geographic_status),
+            ("merchant".to_string(), merchant_status),
+            ("network".to_string(), network_status),
+        ]);
+
+        // Global weights (by default Pattern 25%, Anomaly 20%, Geographic 15%,
+        // Merchant 25%, Network 15%) unless the merchant category has its own
+        let applied_weights = self.weights_for(state, &transaction.merchant_category);
+        let weights = applied_weights.weights;
+        let agents = [
+            ("pattern", "Pattern", pattern_score.as_ref(), weights.pattern),
+            ("anomaly", "Anomaly", anomaly_score.as_ref(), weights.anomaly),
+            ("geographic", "Geographic", geographic_score.as_ref(), weights.geographic),
+            ("merchant", "Merchant", merchant_score.as_ref(), weights.merchant),
+            ("network", "Network", network_score.as_ref(), weights.network),
+        ];
+
+        let mut completed = Vec::new();
+        let mut timed_out_agents = Vec::new();
+        for (agent, label, score, weight) in agents {
+            match score {
+                Some(score) => {
+                    ensure_finite(&format!("{} agent", label), score.risk_score)?;
+                    ensure_finite(&format!("{} agent confidence", label), score.confidence)?;
+                    completed.push((score, weight));
+                }
+                None if agent_status[agent] == AgentStatus::TimedOut => timed_out_agents.push(agent.to_string()),
+                None => {}
+            }
+        }
+        let partial = completed.len() < agents.len();
 
-        // Unwrap all results
-        let pattern_score = pattern_result?;
-        let anomaly_score = anomaly_result?;
-        let geographic_score = geo_result?;
-        let merchant_score = merchant_result?;
-        let network_score = network_result?;
+        // No agent finished and some failed: report the failure itself, as
+        // is (a database outage stays a 503), rather than blame the deadline
+        if completed.is_empty() && !agent_errors.is_empty() {
+            return Err(agent_errors.swap_remove(0));
+        }
+
+        // Agents without data only dilute the aggregate with a default low
+        // score. If none had data, all of them still count
+        let mut excluded_agents = Vec::new();
+        if self.config.exclude_no_data_agents
+            && completed.iter().any(|(score, _)| !score.abstains_for_lack_of_data())
+        {
+            for (agent, _, score, _) in &agents {
+                if let Some(score) = score.filter(|score| score.abstains_for_lack_of_data()) {
+                    excluded_agents.push(agent.to_string());
+                    let note = score.data_note.clone().unwrap_or_else(|| "No data".to_string());
+                    agent_status.insert(agent.to_string(), AgentStatus::Abstained(note));
+                }
+            }
+            completed.retain(|(score, _)| !score.abstains_for_lack_of_data());
+        }
+        if !excluded_agents.is_empty() {
+            tracing::info!("Excluding agents without data from the aggregate: {}", excluded_agents.join(", "));
+        }
+        if !timed_out_agents.is_empty() {
+            tracing::warn!(
+                "⏱️ Deadline of {}ms passed for {}, deciding without: {}",
+                self.config.deadline_ms,
+                transaction.transaction_id,
+                timed_out_agents.join(", ")
+            );
+        }
 
         tracing::info!(
-            "📊 Agent Scores - Pattern: {:.2}, Anomaly: {:.2}, Geographic: {:.2}, Merchant: {:.2}, Network: {:.2}",
-            pattern_score.risk_score,
-            anomaly_score.risk_score,
-            geographic_score.risk_score,
-            merchant_score.risk_score,
-            network_score.risk_score
+            "📊 Agent Scores - {}",
+            agents
+                .iter()
+                .map(|(agent, label, score, _)| match score {
+                    Some(score) => format!("{}: {:.2}", label, score.risk_score),
+                    None => format!("{}: {}", label, agent_status[*agent].label()),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
         );
 
-        // Weighted average of all agents
-        // Pattern (25%) + Anomaly (20%) + Geographic (15%) + Merchant (25%) + Network (15%)
-        let avg_score = (
-            pattern_score.risk_score * 0.25 +
-            anomaly_score.risk_score * 0.20 +
-            geographic_score.risk_score * 0.15 +
-            merchant_score.risk_score * 0.25 +
-            network_score.risk_score * 0.15
-        );
+        // Weighted average of the agents that completed, scaled by each
+        // agent's confidence when enabled
+        let avg_score = weighted_average(&completed, self.config.confidence_weighting);
+        let raw_aggregate_score = ensure_finite("Aggregate", avg_score)?;
+
+        // Payment instrument prior (gift cards, crypto, ...) on top of the agents
+        let payment_method_risk = self.config.payment_method_risk(&transaction.payment_method);
+        let avg_score = (raw_aggregate_score + payment_method_risk).min(1.0);
+
+        // The user already proved a transaction at this merchant legitimate,
+        // don't put them through the same challenge every time
+        let avg_score = if cooldown_applied {
+            tracing::info!(
+                "Cool-down for {} at {}: dampening score {:.2} by {:.0}%",
+                transaction.user_id,
+                transaction.merchant,
+                avg_score,
+                self.config.cooldown_dampening * 100.0
+            );
+            avg_score * (1.0 - self.config.cooldown_dampening)
+        } else {
+            avg_score
+        };
+
+        // Per-user scaling set by an operator
+        let avg_score = match user_risk_multiplier {
+            Some(multiplier) => (avg_score * multiplier).min(1.0),
+            None => avg_score,
+        };
 
         // Check if fraud ring detected by network agent
-        let fraud_ring_detected = network_score.reason.contains("FRAUD RING DETECTED");
+        let fraud_ring_detected = network_score
+            .as_ref()
+            .and_then(|score| score.details.get("fraud_ring_detected"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let fraud_ring_severity = network_score
+            .as_ref()
+            .and_then(|score| score.details.get("fraud_ring_severity"))
+            .and_then(|v| serde_json::from_value::<RingSeverity>(v.clone()).ok());
+        let network_risk = network_score.as_ref().map_or(0.0, |score| score.risk_score);
+
+        let band = self.config.thresholds.band(avg_score);
+
+        // Ring detection has false positives of its own (busy merchants),
+        // only let it override the ensemble when the ring is strong enough
+        // and the network score backs it
+        let severe_ring = fraud_ring_severity
+            .is_some_and(|severity| severity >= self.config.fraud_ring_block_severity);
+        let ring_override = fraud_ring_detected
+            && severe_ring
+            && network_risk >= self.config.fraud_ring_min_network_score;
+        if fraud_ring_detected && !severe_ring {
+            tracing::info!(
+                "Fraud ring flagged as {:?}, below {:?}, using aggregate decision",
+                fraud_ring_severity,
+                self.config.fraud_ring_block_severity
+            );
+        } else if fraud_ring_detected && !ring_override {
+            tracing::info!(
+                "Fraud ring flagged but network score {:.2} is below {:.2}, using aggregate decision",
+                network_risk,
+                self.config.fraud_ring_min_network_score
+            );
+        }
+
+        // Without the pattern agent there's no evidence to speak of
+        let sufficiency = data_sufficiency(
+            pattern_score.as_ref().map_or(&serde_json::Value::Null, |score| &score.details),
+            self.config.sufficiency_history_target,
+        );
 
         // Make decision based on aggregated score
-        let (decision, confidence) = if fraud_ring_detected {
-            // Always block fraud rings with high confidence
-            ("BLOCK".to_string(), 0.95)
-        } else if avg_score > 0.7 {
-            ("BLOCK".to_string(), 0.90)
-        } else if avg_score > 0.4 {
-            ("CHALLENGE".to_string(), 0.75)
+        let mut hysteresis_applied = false;
+        let mut decision_source = DecisionSource::Threshold;
+        let (decision, confidence) = if denylisted.is_some() {
+            decision_source = DecisionSource::MerchantDenylist;
+            (Decision::Block, 1.0)
+        } else if completed.is_empty() {
+            // Nothing finished in time, don't wave it through
+            decision_source = DecisionSource::Deadline;
+            (Decision::Challenge, 0.0)
+        } else if ring_override {
+            decision_source = DecisionSource::FraudRing;
+            (Decision::Block, self.config.fraud_ring_confidence)
         } else {
-            ("APPROVE".to_string(), 0.85)
+            let mut decision = self.config.thresholds.decision(avg_score);
+
+            // A resubmission hovering around a threshold keeps its earlier
+            // decision instead of flip-flopping. Read from the primary, a
+            // lagging replica would miss the last decision
+            if self.config.hysteresis_margin > 0.0
+                && let Some(previous) = persistence::latest_decision(
+                    &state.pool,
+                    &transaction.transaction_id,
+                    &transaction.user_id,
+                )
+                .await?
+            {
+                let held = apply_hysteresis(
+                    &self.config.thresholds,
+                    previous,
+                    decision,
+                    avg_score,
+                    self.config.hysteresis_margin,
+                );
+                if held != decision {
+                    tracing::info!(
+                        "Keeping previous decision {} for {} (score {:.2} within hysteresis margin)",
+                        previous,
+                        transaction.transaction_id,
+                        avg_score
+                    );
+                    hysteresis_applied = true;
+                    decision_source = DecisionSource::Hysteresis;
+                    decision = held;
+                }
+            }
+
+            // Don't confidently approve on no evidence
+            if decision == Decision::Approve && sufficiency < self.config.min_approval_sufficiency {
+                tracing::info!(
+                    "Downgrading APPROVE to CHALLENGE for {}: data sufficiency {:.2} below {:.2}",
+                    transaction.transaction_id,
+                    sufficiency,
+                    self.config.min_approval_sufficiency
+                );
+                decision = Decision::Challenge;
+                decision_source = DecisionSource::DataSufficiency;
+            }
+
+            let confidence = match decision {
+                Decision::Block => 0.90,
+                Decision::Challenge => 0.75,
+                Decision::Approve => 0.85,
+            };
+            (decision, confidence)
         };
 
+        ensure_finite("Confidence", confidence)?;
+
         let total_latency = start.elapsed();
 
+        let mut agent_reasons: Vec<AgentReason> = agents
+            .iter()
+            .filter_map(|(agent, _, score, _)| Some((agent, (*score)?)))
+            .map(|(agent, score)| AgentReason {
+                agent: agent.to_string(),
+                score: score.risk_score,
+                reason: score.reason.clone(),
+                reason_codes: score.reasons.iter().map(|r| r.code).collect(),
+                data_available: score.data_available,
+                data_note: score.data_note.clone(),
+                reasons: score.reasons.clone(),
+                details: score.details.clone(),
+            })
+            .collect();
+
+        // The denylist match leads the merchant's reasons
+        if let Some(entry) = &denylisted {
+            let reason = denylist_reason(transaction, entry);
+            match agent_reasons.iter_mut().find(|r| r.agent == "merchant") {
+                Some(merchant) => {
+                    merchant.score = 1.0;
+                    merchant.reason_codes.insert(0, reason.code);
+                    merchant.reasons.insert(0, reason);
+                    merchant.reason = i18n::render_all(Locale::English, &merchant.reasons);
+                }
+                None => agent_reasons.push(AgentReason {
+                    agent: "merchant".to_string(),
+                    score: 1.0,
+                    reason: i18n::render(Locale::English, &reason),
+                    reason_codes: vec![reason.code],
+                    data_available: true,
+                    data_note: None,
+                    reasons: vec![reason],
+                    details: serde_json::Value::Null,
+                }),
+            }
+        }
+
         // Build comprehensive reasoning from all agents
-        let reasoning = format!(
-            "Pattern: {} | Anomaly: {} | Geographic: {} | Merchant: {} | Network: {}",
-            pattern_score.reason,
-            anomaly_score.reason,
-            geographic_score.reason,
-            merchant_score.reason,
-            network_score.reason
-        );
+        let reasoning = agents
+            .iter()
+            .map(|(agent, label, _, _)| {
+                let reason = match agent_reasons.iter().find(|r| r.agent == *agent) {
+                    Some(r) => r.reason.as_str(),
+                    None if matches!(agent_status[*agent], AgentStatus::Errored(_)) => "Failed",
+                    None => "Timed out",
+                };
+                format!("{}: {}", label, reason)
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
 
         tracing::info!(
             "✅ Analysis complete in {:.2}ms - Decision: {} (confidence: {:.0}%) - Avg Risk: {:.2}",
@@ -113,17 +1119,220 @@ impl FraudAnalyzer {
         }
 
         Ok(AnalysisResult {
+            transaction_id: transaction.transaction_id.clone(),
             decision,
+            decision_source,
+            overridden_decision: None,
+            risk_score: avg_score,
+            raw_aggregate_score,
+            risk_band: band,
             confidence,
+            data_sufficiency: sufficiency,
+            payment_method_risk,
+            model_version: self.model_version.clone(),
             latency_ms: total_latency.as_millis() as u64,
             agent_scores: AgentScores {
-                pattern: pattern_score.risk_score,
-                anomaly: anomaly_score.risk_score,
-                geographic: geographic_score.risk_score,
-                merchant: merchant_score.risk_score,
+                pattern: pattern_score.as_ref().map_or(0.0, |score| score.risk_score),
+                anomaly: anomaly_score.as_ref().map_or(0.0, |score| score.risk_score),
+                geographic: geographic_score.as_ref().map_or(0.0, |score| score.risk_score),
+                merchant: merchant_score.as_ref().map_or(0.0, |score| score.risk_score),
+                network: network_risk,
             },
             fraud_ring_detected,
+            fraud_ring_severity,
+            hysteresis_applied,
+            cooldown_applied,
+            user_risk_multiplier,
             reasoning,
+            agent_reasons,
+            partial,
+            timed_out_agents,
+            excluded_agents,
+            agent_status,
+            persisted: false,
+            signature: None,
+            embedding: pattern_score.and_then(|score| score.embedding),
+            applied_weights: Some(applied_weights),
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent_score(risk_score: f64) -> AgentScore {
+        AgentScore {
+            risk_score,
+            reason: String::new(),
+            reasons: Vec::new(),
+            confidence: 1.0,
+            data_available: true,
+            data_note: None,
+            details: serde_json::Value::Null,
+            embedding: None,
+        }
+    }
+
+    #[test]
+    fn only_agents_without_data_or_risk_abstain() {
+        let mut unknown_merchant = agent_score(0.3);
+        unknown_merchant.data_available = false;
+        assert!(!unknown_merchant.abstains_for_lack_of_data());
+
+        let mut no_history = agent_score(0.0);
+        no_history.data_available = false;
+        assert!(no_history.abstains_for_lack_of_data());
+
+        assert!(!agent_score(0.0).abstains_for_lack_of_data());
+    }
+
+    #[test]
+    fn card_present_methods_have_their_own_prior() {
+        let config = AnalyzerConfig::default();
+        let card_present = crate::agents::geographic::GeographicConfig::default().card_present_payment_methods;
+        for method in &card_present {
+            assert!(config.payment_method_risk.contains_key(method), "no prior for {}", method);
+            assert!(config.payment_method_risk(method) < config.default_payment_method_risk, "{}", method);
+        }
+        assert_eq!(config.payment_method_risk("Contactless"), 0.0);
+    }
+
+    #[test]
+    fn weighted_average_uses_custom_agent_weights() {
+        let weights = AgentWeights {
+            pattern: 0.4,
+            anomaly: 0.1,
+            geographic: 0.1,
+            merchant: 0.3,
+            network: 0.1,
+        };
+        weights.validate().unwrap();
+
+        // pattern, anomaly, geographic, merchant, network
+        let scores = [0.5, 1.0, 0.0, 0.2, 0.8].map(agent_score);
+        let weighted: Vec<(&AgentScore, f64)> = scores.iter().zip(weights.as_array()).collect();
+
+        // 0.4 * 0.5 + 0.1 * 1.0 + 0.1 * 0.0 + 0.3 * 0.2 + 0.1 * 0.8
+        let average = weighted_average(&weighted, false);
+        assert!((average - 0.44).abs() < 1e-12, "got {}", average);
+    }
+
+    #[test]
+    fn agent_weights_must_sum_to_one() {
+        assert!(AgentWeights::default().validate().is_ok());
+
+        let short = AgentWeights {
+            pattern: 0.3,
+            anomaly: 0.2,
+            geographic: 0.1,
+            merchant: 0.2,
+            network: 0.1,
+        };
+        assert!(short.validate().is_err());
+
+        let negative = AgentWeights {
+            pattern: 0.6,
+            anomaly: -0.1,
+            geographic: 0.2,
+            merchant: 0.2,
+            network: 0.1,
+        };
+        assert!(negative.validate().is_err());
+    }
+
+    #[test]
+    fn scores_on_a_threshold_keep_the_lower_decision() {
+        let thresholds = DecisionThresholds::default();
+        assert_eq!(thresholds.decision(0.4), Decision::Approve);
+        assert_eq!(thresholds.decision(0.7), Decision::Challenge);
+        assert_eq!(thresholds.band(0.4), RiskBand::Low);
+        assert_eq!(thresholds.band(0.7), RiskBand::Medium);
+    }
+
+    #[test]
+    fn scores_just_above_a_threshold_move_up_a_band() {
+        let thresholds = DecisionThresholds::default();
+        assert_eq!(thresholds.decision(0.4 + 1e-9), Decision::Challenge);
+        assert_eq!(thresholds.decision(0.7 + 1e-9), Decision::Block);
+        assert_eq!(thresholds.band(0.4 + 1e-9), RiskBand::Medium);
+        assert_eq!(thresholds.band(0.7 + 1e-9), RiskBand::High);
+
+        let custom = DecisionThresholds { challenge: 0.2, block: 0.5 };
+        assert_eq!(custom.decision(0.2), Decision::Approve);
+        assert_eq!(custom.decision(0.21), Decision::Challenge);
+        assert_eq!(custom.decision(0.5), Decision::Challenge);
+        assert_eq!(custom.decision(0.51), Decision::Block);
+    }
+
+    #[test]
+    fn thresholds_validate_order_and_range() {
+        assert!(DecisionThresholds::default().validate().is_ok());
+        assert!(DecisionThresholds { challenge: 0.7, block: 0.7 }.validate().is_err());
+        assert!(DecisionThresholds { challenge: 0.8, block: 0.6 }.validate().is_err());
+        assert!(DecisionThresholds { challenge: -0.1, block: 0.7 }.validate().is_err());
+        assert!(DecisionThresholds { challenge: 0.4, block: 1.1 }.validate().is_err());
+        assert!(DecisionThresholds { challenge: f64::NAN, block: 0.7 }.validate().is_err());
+    }
+
+    #[test]
+    fn finite_scores_pass_through() {
+        assert_eq!(ensure_finite("pattern agent", 0.35).unwrap(), 0.35);
+        assert_eq!(ensure_finite("Aggregate", 0.0).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn nan_agent_score_is_rejected() {
+        let err = ensure_finite("pattern agent", f64::NAN).unwrap_err();
+        assert!(err.to_string().contains("pattern agent produced a non-finite score"), "{}", err);
+    }
+
+    #[test]
+    fn infinite_aggregate_is_rejected() {
+        let weights = AgentWeights::default();
+        let mut scores = [0.2, 0.1, 0.3, 0.0, 0.4].map(agent_score);
+        scores[1].risk_score = f64::INFINITY;
+        let weighted: Vec<(&AgentScore, f64)> = scores.iter().zip(weights.as_array()).collect();
+
+        let average = weighted_average(&weighted, false);
+        assert!(average.is_infinite());
+        let err = ensure_finite("Aggregate", average).unwrap_err();
+        assert!(err.to_string().contains("Aggregate produced a non-finite score (inf)"), "{}", err);
+        assert!(ensure_finite("Aggregate", f64::NEG_INFINITY).is_err());
+    }
+
+    #[test]
+    fn hysteresis_holds_a_decision_one_band_away() {
+        let thresholds = DecisionThresholds::default();
+        // Just over CHALLENGE, within the margin: stays APPROVE
+        assert_eq!(
+            apply_hysteresis(&thresholds, Decision::Approve, Decision::Challenge, 0.42, 0.05),
+            Decision::Approve
+        );
+        // Just under BLOCK, within the margin: stays BLOCK
+        assert_eq!(
+            apply_hysteresis(&thresholds, Decision::Block, Decision::Challenge, 0.68, 0.05),
+            Decision::Block
+        );
+        // Past the margin: moves
+        assert_eq!(
+            apply_hysteresis(&thresholds, Decision::Approve, Decision::Challenge, 0.46, 0.05),
+            Decision::Challenge
+        );
+    }
+
+    #[test]
+    fn hysteresis_never_holds_across_two_bands() {
+        let thresholds = DecisionThresholds::default();
+        // Within the margin of the BLOCK floor, but two bands up from APPROVE
+        assert_eq!(
+            apply_hysteresis(&thresholds, Decision::Approve, Decision::Block, 0.72, 0.05),
+            Decision::Block
+        );
+        // Within the margin of the CHALLENGE floor, but two bands down from BLOCK
+        assert_eq!(
+            apply_hysteresis(&thresholds, Decision::Block, Decision::Approve, 0.38, 0.05),
+            Decision::Approve
+        );
+    }
+}
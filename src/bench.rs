@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    config,
+    embedding::{EmbeddingModelConfig, embed_with_model, load_model},
+};
+
+/// Iterations per input length when `BENCH_ITERATIONS` isn't set
+const DEFAULT_ITERATIONS: usize = 200;
+/// Untimed runs before measuring, so lazy initialization isn't counted
+const WARMUP_ITERATIONS: usize = 5;
+
+/// Inputs of increasing length, the middle one shaped like the pattern agent's descriptions
+const INPUTS: &[(&str, &str)] = &[
+    ("short", "coffee shop"),
+    (
+        "transaction",
+        "User user_0042 spending $127.50 at Amazon Marketplace in category online",
+    ),
+    (
+        "long",
+        "User user_0042 spending $2,499.00 at Northside Electronics Superstore in category \
+         electronics. Purchase of a laptop, two monitors, a docking station, wireless keyboard \
+         and mouse, extended warranty and same-day delivery to a new shipping address in a \
+         different city from the billing address, paid with a card added to the account \
+         earlier the same day from a device never seen before on this account.",
+    ),
+];
+
+/// Time single embeddings across the input lengths and print latency
+/// percentiles and throughput, for capacity planning. Only the model is
+/// loaded, no database is needed.
+///
+/// Run with `cargo run --release -- --bench-embedding`.
+pub async fn run_embedding_benchmark() -> anyhow::Result<()> {
+    // The layered sources rather than a full `Config`, which would require
+    // the database settings
+    let source = config::layered_source()?;
+    let iterations = config::parsed(&source, "BENCH_ITERATIONS", DEFAULT_ITERATIONS)?.max(1);
+
+    let model_config = EmbeddingModelConfig::from_source(&source);
+    let (tensors, tokenizer, device) = load_model(&model_config)?;
+
+    println!(
+        "Embedding benchmark: model '{}', {} iterations per input",
+        model_config.model_name, iterations
+    );
+    println!(
+        "{:<12} {:>7} {:>10} {:>10} {:>10} {:>12}",
+        "input", "tokens", "mean", "p50", "p95", "emb/sec"
+    );
+
+    for (name, text) in INPUTS {
+        let tokens = tokenizer
+            .encode(*text, true)
+            .map(|encoding| encoding.get_ids().len())
+            .unwrap_or(0);
+
+        for _ in 0..WARMUP_ITERATIONS {
+            embed_with_model(&tensors, &tokenizer, &device, text.to_string())
+                .map_err(|e| anyhow::anyhow!(e))?;
+        }
+
+        let mut timings = Vec::with_capacity(iterations);
+        let total_start = Instant::now();
+        for _ in 0..iterations {
+            let start = Instant::now();
+            embed_with_model(&tensors, &tokenizer, &device, text.to_string())
+                .map_err(|e| anyhow::anyhow!(e))?;
+            timings.push(start.elapsed());
+        }
+        let total = total_start.elapsed();
+
+        timings.sort();
+        let mean = total / iterations as u32;
+        println!(
+            "{:<12} {:>7} {:>10} {:>10} {:>10} {:>12.1}",
+            name,
+            tokens,
+            format_duration(mean),
+            format_duration(percentile(&timings, 0.50)),
+            format_duration(percentile(&timings, 0.95)),
+            iterations as f64 / total.as_secs_f64()
+        );
+    }
+
+    Ok(())
+}
+
+/// Nearest-rank percentile of sorted timings
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use crate::config::{ConfigError, ConfigSource};
+
+/// Maps the free-text `merchant_category` values onto canonical names, so
+/// "Food", "dining" and "restaurants" build one baseline instead of three.
+///
+/// Categories are compared trimmed and lowercased, categories without an
+/// alias pass through otherwise unchanged.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct CategoryTaxonomy {
+    aliases: HashMap<String, String>,
+}
+
+impl CategoryTaxonomy {
+    pub fn new(aliases: HashMap<String, String>) -> Self {
+        Self {
+            aliases: aliases
+                .into_iter()
+                .map(|(alias, canonical)| (fold(&alias), fold(&canonical)))
+                .collect(),
+        }
+    }
+
+    /// Read `CATEGORY_ALIASES`, comma-separated `alias=canonical` pairs,
+    /// e.g. `dining=food,restaurants=food`
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let Some(value) = source.get("CATEGORY_ALIASES") else {
+            return Ok(Self::default());
+        };
+
+        let aliases = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (alias, canonical) = entry
+                    .split_once('=')
+                    .filter(|(alias, canonical)| !alias.trim().is_empty() && !canonical.trim().is_empty())
+                    .ok_or_else(|| ConfigError::Invalid {
+                        key: "CATEGORY_ALIASES".to_string(),
+                        value: value.clone(),
+                        reason: format!("expected alias=canonical in '{}'", entry),
+                    })?;
+                Ok((alias.to_string(), canonical.to_string()))
+            })
+            .collect::<Result<HashMap<_, _>, ConfigError>>()?;
+
+        Ok(Self::new(aliases))
+    }
+
+    /// Canonical name of `category`
+    pub fn normalize(&self, category: &str) -> String {
+        let folded = fold(category);
+        self.aliases.get(&folded).cloned().unwrap_or(folded)
+    }
+
+    /// Alias and canonical names as parallel arrays, to bind alongside [`Self::sql_expr`]
+    pub fn sql_aliases(&self) -> (Vec<String>, Vec<String>) {
+        self.aliases
+            .iter()
+            .map(|(alias, canonical)| (alias.clone(), canonical.clone()))
+            .unzip()
+    }
+
+    /// SQL expression normalizing `column` the same way as [`Self::normalize`],
+    /// reading the alias arrays from parameters `$first_param` and `$first_param + 1`
+    pub fn sql_expr(column: &str, first_param: usize) -> String {
+        format!(
+            "COALESCE((SELECT a.canonical FROM UNNEST(${alias}::text[], ${canonical}::text[]) AS a(alias, canonical) \
+             WHERE a.alias = LOWER(TRIM({column}))), LOWER(TRIM({column})))",
+            alias = first_param,
+            canonical = first_param + 1,
+            column = column,
+        )
+    }
+}
+
+fn fold(category: &str) -> String {
+    category.trim().to_lowercase()
+}
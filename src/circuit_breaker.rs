@@ -0,0 +1,379 @@
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use axum::{Json, extract::State};
+use serde::Serialize;
+
+use crate::{
+    AppState,
+    config::{self, ConfigError, ConfigSource},
+};
+
+/// Agent queries that fail independently, each behind its own breaker so a
+/// slow vector index doesn't take the count queries down with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryKind {
+    /// Pattern agent's nearest-neighbour searches
+    VectorSearch,
+    /// Network agent's device and merchant counts
+    NetworkCounts,
+    /// Merchant agent's similar-risky-merchant lookup
+    MerchantSimilarity,
+}
+
+impl QueryKind {
+    pub const ALL: [QueryKind; 3] = [
+        QueryKind::VectorSearch,
+        QueryKind::NetworkCounts,
+        QueryKind::MerchantSimilarity,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            QueryKind::VectorSearch => 0,
+            QueryKind::NetworkCounts => 1,
+            QueryKind::MerchantSimilarity => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Queries run
+    Closed,
+    /// Queries are skipped and their signal abstains
+    Open,
+    /// The open period is over, one trial query decides whether to close
+    HalfOpen,
+}
+
+/// Tunables shared by every breaker
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open a breaker, 0 never opens
+    pub failure_threshold: u32,
+    /// How long an open breaker skips its queries before a trial
+    pub open_secs: u64,
+    /// Queries slower than this count as failed, 0 waits indefinitely
+    pub timeout_ms: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_secs: 30,
+            timeout_ms: 0,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Read the `CIRCUIT_BREAKER_*` settings
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            failure_threshold: config::parsed(
+                source,
+                "CIRCUIT_BREAKER_FAILURE_THRESHOLD",
+                defaults.failure_threshold,
+            )?,
+            open_secs: config::parsed(source, "CIRCUIT_BREAKER_OPEN_SECS", defaults.open_secs)?,
+            timeout_ms: config::parsed(source, "CIRCUIT_BREAKER_TIMEOUT_MS", defaults.timeout_ms)?,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+/// A breaker's state as reported by `/health`
+#[derive(Debug, Serialize)]
+pub struct BreakerStatus {
+    pub query: QueryKind,
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    /// Until the next trial query, while open
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+}
+
+/// One breaker per `QueryKind`
+pub struct CircuitBreakers {
+    config: CircuitBreakerConfig,
+    breakers: [Mutex<Breaker>; 3],
+}
+
+impl CircuitBreakers {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            breakers: Default::default(),
+        }
+    }
+
+    /// Run `query` unless its breaker is open. `Ok(None)` when it was
+    /// skipped, or failed or timed out with the breaker open, and the caller
+    /// abstains from the signal it feeds. A failure while the breaker stays
+    /// closed is returned as is; every failure counts towards opening it
+    pub async fn guard<T, F>(&self, kind: QueryKind, query: F) -> anyhow::Result<Option<T>>
+    where
+        F: Future<Output = anyhow::Result<T>>,
+    {
+        let Some(_permit) = self.try_acquire(kind) else {
+            return Ok(None);
+        };
+
+        let outcome = if self.config.timeout_ms > 0 {
+            match tokio::time::timeout(Duration::from_millis(self.config.timeout_ms), query).await {
+                Ok(outcome) => outcome,
+                Err(_) => Err(anyhow::anyhow!("timed out after {}ms", self.config.timeout_ms)),
+            }
+        } else {
+            query.await
+        };
+
+        match outcome {
+            Ok(value) => {
+                self.record(kind, true);
+                Ok(Some(value))
+            }
+            Err(e) => {
+                self.record(kind, false);
+                if self.state(kind) == BreakerState::Closed {
+                    return Err(e);
+                }
+                tracing::warn!("⚡ {:?} query failed, abstaining: {}", kind, e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Permission to run a query now, holding the trial slot when half-open
+    fn try_acquire(&self, kind: QueryKind) -> Option<Permit<'_>> {
+        let mut breaker = self.lock(kind);
+        let trial = match self.state_of(&breaker) {
+            BreakerState::Closed => false,
+            BreakerState::Open => return None,
+            BreakerState::HalfOpen if breaker.trial_in_flight => return None,
+            BreakerState::HalfOpen => {
+                breaker.trial_in_flight = true;
+                true
+            }
+        };
+        Some(Permit {
+            breakers: self,
+            kind,
+            trial,
+        })
+    }
+
+    fn record(&self, kind: QueryKind, success: bool) {
+        let mut breaker = self.lock(kind);
+        if success {
+            if breaker.opened_at.is_some() {
+                tracing::info!("⚡ {:?} breaker closed", kind);
+            }
+            breaker.consecutive_failures = 0;
+            breaker.opened_at = None;
+            return;
+        }
+
+        breaker.consecutive_failures += 1;
+        // A failed trial reopens for another full period
+        let threshold = self.config.failure_threshold;
+        if breaker.opened_at.is_some() || (threshold > 0 && breaker.consecutive_failures >= threshold) {
+            if breaker.opened_at.is_none() {
+                tracing::warn!(
+                    "⚡ {:?} breaker opened after {} consecutive failures",
+                    kind,
+                    breaker.consecutive_failures
+                );
+            }
+            breaker.opened_at = Some(Instant::now());
+        }
+    }
+
+    fn state(&self, kind: QueryKind) -> BreakerState {
+        self.state_of(&self.lock(kind))
+    }
+
+    fn state_of(&self, breaker: &Breaker) -> BreakerState {
+        match breaker.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.open_duration() => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    fn open_duration(&self) -> Duration {
+        Duration::from_secs(self.config.open_secs)
+    }
+
+    fn lock(&self, kind: QueryKind) -> std::sync::MutexGuard<'_, Breaker> {
+        // A panic while holding the lock leaves plain counters behind, still usable
+        self.breakers[kind.index()]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn statuses(&self) -> Vec<BreakerStatus> {
+        QueryKind::ALL
+            .iter()
+            .map(|&kind| {
+                let breaker = self.lock(kind);
+                let state = self.state_of(&breaker);
+                BreakerStatus {
+                    query: kind,
+                    state,
+                    consecutive_failures: breaker.consecutive_failures,
+                    retry_in_secs: breaker
+                        .opened_at
+                        .filter(|_| state == BreakerState::Open)
+                        .map(|opened_at| self.open_duration().saturating_sub(opened_at.elapsed()).as_secs()),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A claim to run one query. A trial releases the half-open slot when
+/// dropped, also when the query future is cancelled before it finishes, so
+/// the breaker can't stay half-open with no trial ever running
+struct Permit<'a> {
+    breakers: &'a CircuitBreakers,
+    kind: QueryKind,
+    trial: bool,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.trial {
+            self.breakers.lock(self.kind).trial_in_flight = false;
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    /// `degraded` while any breaker isn't closed: transactions are still
+    /// scored, without the affected signals
+    pub status: &'static str,
+    pub breakers: Vec<BreakerStatus>,
+}
+
+/// GET /health
+pub async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
+    let breakers = state.breakers.statuses();
+    let status = if breakers.iter().all(|b| b.state == BreakerState::Closed) {
+        "ok"
+    } else {
+        "degraded"
+    };
+    Json(HealthResponse { status, breakers })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breakers(failure_threshold: u32, open_secs: u64) -> CircuitBreakers {
+        CircuitBreakers::new(CircuitBreakerConfig {
+            failure_threshold,
+            open_secs,
+            timeout_ms: 0,
+        })
+    }
+
+    async fn fail(breakers: &CircuitBreakers) -> anyhow::Result<Option<()>> {
+        breakers
+            .guard(QueryKind::VectorSearch, async { Err::<(), _>(anyhow::anyhow!("boom")) })
+            .await
+    }
+
+    async fn succeed(breakers: &CircuitBreakers) -> anyhow::Result<Option<()>> {
+        breakers.guard(QueryKind::VectorSearch, async { Ok(()) }).await
+    }
+
+    #[tokio::test]
+    async fn failures_propagate_until_the_breaker_opens() {
+        let breakers = breakers(3, 60);
+
+        assert!(fail(&breakers).await.is_err());
+        assert!(fail(&breakers).await.is_err());
+        assert_eq!(breakers.state(QueryKind::VectorSearch), BreakerState::Closed);
+
+        // The failure that opens the breaker abstains
+        assert!(fail(&breakers).await.unwrap().is_none());
+        assert_eq!(breakers.state(QueryKind::VectorSearch), BreakerState::Open);
+
+        // Open: skipped without running
+        let ran = std::sync::atomic::AtomicBool::new(false);
+        let skipped = breakers
+            .guard(QueryKind::VectorSearch, async {
+                ran.store(true, std::sync::atomic::Ordering::Relaxed);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert!(skipped.is_none());
+        assert!(!ran.load(std::sync::atomic::Ordering::Relaxed));
+
+        // Other kinds have their own breaker
+        assert_eq!(breakers.state(QueryKind::NetworkCounts), BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn a_success_resets_the_failure_count() {
+        let breakers = breakers(2, 60);
+
+        assert!(fail(&breakers).await.is_err());
+        assert_eq!(succeed(&breakers).await.unwrap(), Some(()));
+        assert!(fail(&breakers).await.is_err());
+        assert_eq!(breakers.state(QueryKind::VectorSearch), BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn half_open_runs_one_trial() {
+        // Open for zero seconds: half-open as soon as it opens
+        let breakers = breakers(1, 0);
+        assert!(fail(&breakers).await.unwrap().is_none());
+        assert_eq!(breakers.state(QueryKind::VectorSearch), BreakerState::HalfOpen);
+
+        let trial = breakers.try_acquire(QueryKind::VectorSearch);
+        assert!(trial.is_some());
+        assert!(breakers.try_acquire(QueryKind::VectorSearch).is_none());
+        drop(trial);
+
+        // A failed trial reopens and abstains, a successful one closes
+        assert!(fail(&breakers).await.unwrap().is_none());
+        assert_eq!(breakers.state(QueryKind::VectorSearch), BreakerState::HalfOpen);
+        assert_eq!(succeed(&breakers).await.unwrap(), Some(()));
+        assert_eq!(breakers.state(QueryKind::VectorSearch), BreakerState::Closed);
+    }
+
+    #[tokio::test]
+    async fn a_cancelled_trial_releases_the_slot() {
+        let breakers = breakers(1, 0);
+        assert!(fail(&breakers).await.unwrap().is_none());
+
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(10),
+            breakers.guard(QueryKind::VectorSearch, std::future::pending::<anyhow::Result<()>>()),
+        )
+        .await;
+        assert!(cancelled.is_err());
+
+        assert!(!breakers.lock(QueryKind::VectorSearch).trial_in_flight);
+        assert_eq!(succeed(&breakers).await.unwrap(), Some(()));
+    }
+}
@@ -0,0 +1,743 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    agents::{
+        anomaly::AnomalyConfig, geographic::GeographicConfig, merchant::MerchantConfig,
+        network::NetworkConfig,
+        pattern::{PatternConfig, parse_category_list},
+    },
+    analysis::AnalyzerConfig,
+    circuit_breaker::CircuitBreakerConfig,
+    category::CategoryTaxonomy,
+    db::{persistence::PersistenceMode, schema::VectorIndexConfig, vector_search::RecencyBoost},
+    kill_switch::OverrideMode,
+    embedding::{DistanceMetric, EmbeddingModelConfig, EmbeddingPrecision, EmbeddingPrefixes},
+    models::transaction::FieldCase,
+};
+
+/// Somewhere configuration values can be looked up by key
+pub trait ConfigSource: Send + Sync {
+    fn get(&self, key: &str) -> Option<String>;
+}
+
+/// Process environment (including anything loaded from `.env`)
+pub struct EnvSource;
+
+impl ConfigSource for EnvSource {
+    fn get(&self, key: &str) -> Option<String> {
+        std::env::var(key).ok()
+    }
+}
+
+/// One file per key in a directory, the layout Docker and Kubernetes use
+/// for mounted secrets (e.g. `/run/secrets/DATABASE_URL`)
+pub struct SecretsDirSource {
+    dir: PathBuf,
+}
+
+impl SecretsDirSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl ConfigSource for SecretsDirSource {
+    fn get(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.dir.join(key))
+            .ok()
+            .map(|v| v.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+/// `KEY=VALUE` lines from a file (`.env` syntax), read when constructed
+pub struct FileSource {
+    values: HashMap<String, String>,
+}
+
+impl FileSource {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let invalid = |reason: String| ConfigError::Invalid {
+            key: "CONFIG_FILE".to_string(),
+            value: path.display().to_string(),
+            reason,
+        };
+        let values = dotenvy::from_path_iter(path)
+            .map_err(|e| invalid(e.to_string()))?
+            .collect::<Result<HashMap<_, _>, _>>()
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(Self { values })
+    }
+}
+
+impl ConfigSource for FileSource {
+    fn get(&self, key: &str) -> Option<String> {
+        self.values.get(key).cloned()
+    }
+}
+
+/// Sources consulted in order, the first one holding a key wins
+pub struct LayeredSource {
+    sources: Vec<Box<dyn ConfigSource>>,
+}
+
+impl LayeredSource {
+    pub fn new(sources: Vec<Box<dyn ConfigSource>>) -> Self {
+        Self { sources }
+    }
+}
+
+impl ConfigSource for LayeredSource {
+    fn get(&self, key: &str) -> Option<String> {
+        self.sources.iter().find_map(|s| s.get(key))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("{key} is required but not set")]
+    Missing { key: String },
+    #[error("{key}={value:?} is invalid: {reason}")]
+    Invalid {
+        key: String,
+        value: String,
+        reason: String,
+    },
+    #[error("Invalid configuration: {0}")]
+    Validation(String),
+}
+
+pub fn required(source: &dyn ConfigSource, key: &str) -> Result<String, ConfigError> {
+    source.get(key).ok_or_else(|| ConfigError::Missing {
+        key: key.to_string(),
+    })
+}
+
+pub fn string(source: &dyn ConfigSource, key: &str, default: String) -> String {
+    source.get(key).unwrap_or(default)
+}
+
+/// Parse `key` if set; a set but unparseable value is an error, not the default
+pub fn parsed<T>(source: &dyn ConfigSource, key: &str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match source.get(key) {
+        Some(value) => value.trim().parse().map_err(|e: T::Err| ConfigError::Invalid {
+            key: key.to_string(),
+            value,
+            reason: e.to_string(),
+        }),
+        None => Ok(default),
+    }
+}
+
+/// Parse `key` if set, for settings without a default
+pub fn optional<T>(source: &dyn ConfigSource, key: &str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    source
+        .get(key)
+        .map(|value| {
+            value.trim().parse().map_err(|e: T::Err| ConfigError::Invalid {
+                key: key.to_string(),
+                value,
+                reason: e.to_string(),
+            })
+        })
+        .transpose()
+}
+
+/// JSON value of `key` if set, for settings that don't fit `name=value`
+/// pairs such as nested tables
+pub fn json<T>(source: &dyn ConfigSource, key: &str, default: T) -> Result<T, ConfigError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    match source.get(key) {
+        Some(value) => serde_json::from_str(&value).map_err(|e| ConfigError::Invalid {
+            key: key.to_string(),
+            value,
+            reason: e.to_string(),
+        }),
+        None => Ok(default),
+    }
+}
+
+/// Comma-separated `name=value` pairs with lowercased names, e.g. `crypto=0.3,gift_card=0.3`
+pub fn float_map(
+    source: &dyn ConfigSource,
+    key: &str,
+    default: HashMap<String, f64>,
+) -> Result<HashMap<String, f64>, ConfigError> {
+    let Some(value) = source.get(key) else {
+        return Ok(default);
+    };
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let invalid = |reason: &str| ConfigError::Invalid {
+                key: key.to_string(),
+                value: value.clone(),
+                reason: format!("{} in '{}'", reason, entry),
+            };
+            let (name, number) = entry.split_once('=').ok_or_else(|| invalid("expected name=value"))?;
+            let number = number.trim().parse::<f64>().map_err(|_| invalid("not a number"))?;
+            Ok((name.trim().to_lowercase(), number))
+        })
+        .collect()
+}
+
+/// Comma-separated, lowercased list
+pub fn list(source: &dyn ConfigSource, key: &str, default: HashSet<String>) -> HashSet<String> {
+    source
+        .get(key)
+        .map(|v| parse_category_list(&v))
+        .unwrap_or(default)
+}
+
+/// Comma-separated values in order, each parsed with `FromStr`
+pub fn parsed_list<T>(source: &dyn ConfigSource, key: &str, default: Vec<T>) -> Result<Vec<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let Some(value) = source.get(key) else {
+        return Ok(default);
+    };
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry.parse().map_err(|e: T::Err| ConfigError::Invalid {
+                key: key.to_string(),
+                value: value.clone(),
+                reason: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `serialize_with` for `HashSet` settings: serialized sorted, so equal sets
+/// compare and hash into the model version the same whatever their hash
+/// order. Order-sensitive lists stay `Vec`s and keep their order
+pub fn sorted_set<S: serde::Serializer>(set: &HashSet<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(set.iter().collect::<BTreeSet<_>>())
+}
+
+/// The sources `Config::load` reads, in order. For tools that read their
+/// own settings alongside, so they see the same `CONFIG_FILE` and secrets
+pub fn layered_source() -> Result<LayeredSource, ConfigError> {
+    let mut sources: Vec<Box<dyn ConfigSource>> = Vec::new();
+    if let Some(path) = config_file_path() {
+        sources.push(Box::new(FileSource::new(path)?));
+    }
+    sources.push(Box::new(EnvSource));
+    if let Ok(dir) = std::env::var("CONFIG_SECRETS_DIR") {
+        sources.push(Box::new(SecretsDirSource::new(dir)));
+    }
+    Ok(LayeredSource::new(sources))
+}
+
+/// Settings file named by `CONFIG_FILE`, watched for changes at runtime
+pub fn config_file_path() -> Option<PathBuf> {
+    std::env::var("CONFIG_FILE")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+/// 64-bit FNV-1a, stable across builds and platforms unlike `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// All runtime configuration, loaded and validated once at startup
+#[derive(Clone)]
+pub struct Config {
+    pub database_url: String,
+    /// Optional read replica for agent queries
+    pub database_read_url: Option<String>,
+    pub port: u16,
+    pub pool_max_connections: u32,
+    pub max_request_body_bytes: usize,
+    pub request_timeout_secs: u64,
+    /// Limit for the admin endpoints, which scan stored history
+    /// (backtests, re-analysis, ROC) and need longer than scoring
+    pub admin_request_timeout_secs: u64,
+    pub population_stats_ttl_secs: u64,
+    pub merchant_similarity_ttl_secs: u64,
+    pub user_overrides_ttl_secs: u64,
+    /// Never write to the database: nothing is persisted and admin writes,
+    /// forks and seeding are refused
+    pub read_only: bool,
+    /// Whether `/api/analyze` waits for its write to commit
+    pub persistence_mode: PersistenceMode,
+    /// Field name casing of analyze responses, unless the request asks
+    pub response_field_case: FieldCase,
+    /// Override mode at startup, `NORMAL` unless an incident is ongoing
+    pub override_mode: OverrideMode,
+    /// Shared secret for signing decisions, unsigned when unset
+    pub decision_signing_secret: Option<String>,
+    /// Bearer token for the `/api/admin` endpoints, disabled when unset
+    pub admin_api_token: Option<String>,
+    /// JSON file of users, merchants and transactions to seed, the bundled
+    /// scenarios when unset
+    pub seed_scenario_file: Option<String>,
+    pub embedding_model: EmbeddingModelConfig,
+    pub embedding_prefixes: EmbeddingPrefixes,
+    /// Storage type of transaction embeddings
+    pub embedding_precision: EmbeddingPrecision,
+    /// Store embeddings with their magnitude instead of unit-length, only
+    /// valid with inner-product search
+    pub embedding_normalize: bool,
+    /// How transaction embeddings are compared
+    pub embedding_distance_metric: DistanceMetric,
+    /// Embeddings computed at once, defaults to the available parallelism
+    pub embedding_max_concurrency: usize,
+    /// pgvector indexes on the embedding columns
+    pub vector_index: VectorIndexConfig,
+    /// Recency term of the hybrid transaction search
+    pub hybrid_recency: RecencyBoost,
+    /// Aliases folding free-text merchant categories together
+    pub category_taxonomy: CategoryTaxonomy,
+    pub analyzer: AnalyzerConfig,
+    /// Breakers around the agents' database queries
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub pattern: PatternConfig,
+    pub anomaly: AnomalyConfig,
+    pub geographic: GeographicConfig,
+    pub merchant: MerchantConfig,
+    pub network: NetworkConfig,
+}
+
+impl Config {
+    /// Load from `CONFIG_FILE` when set, then the environment, falling back
+    /// to `CONFIG_SECRETS_DIR` when set. The file comes first so that edits
+    /// to it take effect on a reload
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::from_source(&layered_source()?)
+    }
+
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let config = Self {
+            database_url: required(source, "DATABASE_URL")?,
+            database_read_url: source.get("DATABASE_READ_URL").filter(|url| !url.trim().is_empty()),
+            port: parsed(source, "PORT", 2008)?,
+            pool_max_connections: parsed(source, "DATABASE_MAX_CONNECTIONS", 20)?,
+            max_request_body_bytes: parsed(source, "MAX_REQUEST_BODY_BYTES", 1024 * 1024)?,
+            request_timeout_secs: parsed(source, "REQUEST_TIMEOUT_SECS", 30)?,
+            admin_request_timeout_secs: parsed(source, "ADMIN_REQUEST_TIMEOUT_SECS", 600)?,
+            population_stats_ttl_secs: parsed(source, "POPULATION_STATS_TTL_SECS", 300)?,
+            merchant_similarity_ttl_secs: parsed(source, "MERCHANT_SIMILARITY_TTL_SECS", 600)?,
+            user_overrides_ttl_secs: parsed(source, "USER_OVERRIDES_TTL_SECS", 60)?,
+            read_only: parsed(source, "READ_ONLY", false)?,
+            persistence_mode: parsed(source, "PERSISTENCE_MODE", PersistenceMode::default())?,
+            response_field_case: parsed(source, "RESPONSE_FIELD_CASE", FieldCase::default())?,
+            override_mode: parsed(source, "DECISION_OVERRIDE_MODE", OverrideMode::default())?,
+            decision_signing_secret: source
+                .get("DECISION_SIGNING_SECRET")
+                .filter(|secret| !secret.is_empty()),
+            admin_api_token: source.get("ADMIN_API_TOKEN").filter(|token| !token.trim().is_empty()),
+            seed_scenario_file: source.get("SEED_SCENARIO_FILE").filter(|path| !path.trim().is_empty()),
+            embedding_model: EmbeddingModelConfig::from_source(source),
+            embedding_prefixes: EmbeddingPrefixes::from_source(source),
+            embedding_precision: parsed(
+                source,
+                "EMBEDDING_STORAGE_PRECISION",
+                EmbeddingPrecision::default(),
+            )?,
+            embedding_normalize: parsed(source, "EMBEDDING_NORMALIZE", true)?,
+            embedding_distance_metric: parsed(source, "EMBEDDING_DISTANCE_METRIC", DistanceMetric::default())?,
+            embedding_max_concurrency: parsed(
+                source,
+                "EMBEDDING_MAX_CONCURRENCY",
+                std::thread::available_parallelism().map_or(4, |n| n.get()),
+            )?,
+            vector_index: VectorIndexConfig::from_source(source)?,
+            hybrid_recency: RecencyBoost::from_source(source)?,
+            category_taxonomy: CategoryTaxonomy::from_source(source)?,
+            analyzer: AnalyzerConfig::from_source(source)?,
+            circuit_breaker: CircuitBreakerConfig::from_source(source)?,
+            pattern: PatternConfig::from_source(source)?,
+            anomaly: AnomalyConfig::from_source(source)?,
+            geographic: GeographicConfig::from_source(source)?,
+            merchant: MerchantConfig::from_source(source)?,
+            network: NetworkConfig::from_source(source)?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Settings that differ from `other` and only take effect on a restart:
+    /// everything the server reads once at startup rather than through the
+    /// analyzer. The embedding model has its own reload endpoint
+    pub fn restart_only_changes(&self, other: &Config) -> Vec<&'static str> {
+        fn fixed(config: &Config) -> [(&'static str, serde_json::Value); 23] {
+            [
+                ("DATABASE_URL", serde_json::json!(config.database_url)),
+                ("DATABASE_READ_URL", serde_json::json!(config.database_read_url)),
+                ("PORT", serde_json::json!(config.port)),
+                ("DATABASE_MAX_CONNECTIONS", serde_json::json!(config.pool_max_connections)),
+                ("MAX_REQUEST_BODY_BYTES", serde_json::json!(config.max_request_body_bytes)),
+                ("REQUEST_TIMEOUT_SECS", serde_json::json!(config.request_timeout_secs)),
+                ("ADMIN_REQUEST_TIMEOUT_SECS", serde_json::json!(config.admin_request_timeout_secs)),
+                ("POPULATION_STATS_TTL_SECS", serde_json::json!(config.population_stats_ttl_secs)),
+                ("MERCHANT_SIMILARITY_TTL_SECS", serde_json::json!(config.merchant_similarity_ttl_secs)),
+                ("USER_OVERRIDES_TTL_SECS", serde_json::json!(config.user_overrides_ttl_secs)),
+                ("READ_ONLY", serde_json::json!(config.read_only)),
+                ("PERSISTENCE_MODE", serde_json::json!(config.persistence_mode)),
+                ("RESPONSE_FIELD_CASE", serde_json::json!(config.response_field_case)),
+                ("DECISION_OVERRIDE_MODE", serde_json::json!(config.override_mode)),
+                ("DECISION_SIGNING_SECRET", serde_json::json!(config.decision_signing_secret)),
+                ("ADMIN_API_TOKEN", serde_json::json!(config.admin_api_token)),
+                ("EMBEDDING_PREFIXES", serde_json::json!(config.embedding_prefixes)),
+                ("EMBEDDING_STORAGE_PRECISION", serde_json::json!(config.embedding_precision)),
+                ("EMBEDDING_NORMALIZE", serde_json::json!(config.embedding_normalize)),
+                ("EMBEDDING_DISTANCE_METRIC", serde_json::json!(config.embedding_distance_metric)),
+                ("EMBEDDING_MAX_CONCURRENCY", serde_json::json!(config.embedding_max_concurrency)),
+                ("CATEGORY_ALIASES", serde_json::json!(config.category_taxonomy)),
+                ("CIRCUIT_BREAKER", serde_json::json!(config.circuit_breaker)),
+            ]
+        }
+
+        fixed(self)
+            .into_iter()
+            .zip(fixed(other))
+            .filter(|((_, ours), (_, theirs))| ours != theirs)
+            .map(|((key, _), _)| key)
+            .collect()
+    }
+
+    /// Identifies the scoring logic: crate version plus a hash of every
+    /// setting that affects scores (agent tunables, thresholds, embedding
+    /// model and prefixes, category aliases). Changing any of them changes
+    /// the version.
+    pub fn model_version(&self) -> String {
+        let scoring = serde_json::json!({
+            "analyzer": self.analyzer,
+            "pattern": self.pattern,
+            "anomaly": self.anomaly,
+            "geographic": self.geographic,
+            "merchant": self.merchant,
+            "network": self.network,
+            "embedding_model": {
+                "name": self.embedding_model.model_name,
+                "tokenizer_file": self.embedding_model.tokenizer_file,
+                "weights_file": self.embedding_model.weights_file,
+            },
+            "embedding_prefixes": self.embedding_prefixes,
+            "embedding_precision": self.embedding_precision,
+            "embedding_normalize": self.embedding_normalize,
+            "embedding_distance_metric": self.embedding_distance_metric,
+            "category_taxonomy": self.category_taxonomy,
+        });
+
+        format!(
+            "{}+{:016x}",
+            env!("CARGO_PKG_VERSION"),
+            fnv1a(scoring.to_string().as_bytes())
+        )
+    }
+
+    /// Catch values that parse but can't work, before anything is served
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        fn check(ok: bool, message: &str) -> Result<(), ConfigError> {
+            if ok {
+                Ok(())
+            } else {
+                Err(ConfigError::Validation(message.to_string()))
+            }
+        }
+        let unit = |v: f64| (0.0..=1.0).contains(&v);
+
+        check(self.pool_max_connections > 0, "DATABASE_MAX_CONNECTIONS must be at least 1")?;
+        check(self.max_request_body_bytes > 0, "MAX_REQUEST_BODY_BYTES must be positive")?;
+        check(self.request_timeout_secs > 0, "REQUEST_TIMEOUT_SECS must be positive")?;
+        check(self.admin_request_timeout_secs > 0, "ADMIN_REQUEST_TIMEOUT_SECS must be positive")?;
+        check(
+            self.decision_signing_secret.as_ref().is_none_or(|secret| secret.len() >= 32),
+            "DECISION_SIGNING_SECRET must be at least 32 bytes",
+        )?;
+        check(
+            unit(self.analyzer.fraud_ring_confidence),
+            "FRAUD_RING_CONFIDENCE must be between 0 and 1",
+        )?;
+        check(
+            unit(self.analyzer.fraud_ring_min_network_score),
+            "FRAUD_RING_MIN_NETWORK_SCORE must be between 0 and 1",
+        )?;
+        check(
+            (0.0..0.5).contains(&self.analyzer.hysteresis_margin),
+            "DECISION_HYSTERESIS_MARGIN must be in [0, 0.5)",
+        )?;
+        check(
+            unit(self.analyzer.min_approval_sufficiency),
+            "MIN_APPROVAL_SUFFICIENCY must be between 0 and 1",
+        )?;
+        check(
+            self.analyzer.sufficiency_history_target >= 0,
+            "SUFFICIENCY_HISTORY_TARGET must not be negative",
+        )?;
+        check(
+            self.analyzer.min_analysis_amount >= 0.0,
+            "MIN_ANALYSIS_AMOUNT must not be negative",
+        )?;
+        check(
+            self.analyzer.cooldown_window_secs >= 0,
+            "COOLDOWN_WINDOW_SECS must not be negative",
+        )?;
+        check(
+            unit(self.analyzer.cooldown_dampening),
+            "COOLDOWN_DAMPENING must be between 0 and 1",
+        )?;
+        check(
+            self.analyzer
+                .payment_method_risk
+                .values()
+                .chain([&self.analyzer.default_payment_method_risk])
+                .all(|v| unit(*v)),
+            "PAYMENT_METHOD_RISK and DEFAULT_PAYMENT_METHOD_RISK values must be between 0 and 1",
+        )?;
+        check(
+            (0.0..1.0).contains(&self.pattern.novelty_floor),
+            "PATTERN_NOVELTY_FLOOR must be in [0, 1)",
+        )?;
+        check(
+            unit(self.pattern.novelty_weight),
+            "PATTERN_NOVELTY_WEIGHT must be between 0 and 1",
+        )?;
+        check(
+            unit(self.pattern.high_risk_category_weight),
+            "PATTERN_HIGH_RISK_CATEGORY_WEIGHT must be between 0 and 1",
+        )?;
+        check(
+            self.pattern.population_z_threshold > 0.0,
+            "PATTERN_POPULATION_Z_THRESHOLD must be positive",
+        )?;
+        check(
+            unit(self.pattern.global_fraud_similarity_threshold),
+            "PATTERN_GLOBAL_FRAUD_SIMILARITY_THRESHOLD must be between 0 and 1",
+        )?;
+        check(
+            !self.pattern.global_fraud_enabled
+                || self.embedding_distance_metric != DistanceMetric::InnerProduct
+                || self.pattern.global_fraud_inner_product_threshold.is_some_and(f64::is_finite),
+            "PATTERN_GLOBAL_FRAUD_ENABLED with EMBEDDING_DISTANCE_METRIC=inner_product requires PATTERN_GLOBAL_FRAUD_INNER_PRODUCT_THRESHOLD",
+        )?;
+        check(
+            unit(self.pattern.global_fraud_weight),
+            "PATTERN_GLOBAL_FRAUD_WEIGHT must be between 0 and 1",
+        )?;
+        check(
+            (0.0..1.0).contains(&self.pattern.outlier_percentile),
+            "PATTERN_OUTLIER_PERCENTILE must be in [0, 1)",
+        )?;
+        check(
+            unit(self.pattern.outlier_weight),
+            "PATTERN_OUTLIER_WEIGHT must be between 0 and 1",
+        )?;
+        check(
+            unit(self.pattern.challenge_trust_weight),
+            "PATTERN_CHALLENGE_TRUST_WEIGHT must be between 0 and 1",
+        )?;
+        check(
+            self.pattern.challenge_trust_passes >= 1 && self.pattern.challenge_history_days >= 1,
+            "PATTERN_CHALLENGE_TRUST_PASSES and PATTERN_CHALLENGE_HISTORY_DAYS must be at least 1",
+        )?;
+        check(
+            self.anomaly.night_start_hour < 24 && self.anomaly.night_end_hour < 24,
+            "ANOMALY_NIGHT_START_HOUR and ANOMALY_NIGHT_END_HOUR must be 0-23",
+        )?;
+        check(
+            self.anomaly.recent_transactions_limit > 0,
+            "ANOMALY_RECENT_TRANSACTIONS_LIMIT must be at least 1",
+        )?;
+        check(
+            unit(self.hybrid_recency.weight),
+            "HYBRID_SEARCH_RECENCY_WEIGHT must be between 0 and 1",
+        )?;
+        check(
+            self.hybrid_recency.half_life_secs >= 0.0,
+            "HYBRID_SEARCH_RECENCY_HALF_LIFE_SECS must not be negative",
+        )?;
+        check(
+            !(self.read_only && self.vector_index.ensure_at_startup),
+            "VECTOR_INDEX_ENSURE can't create indexes with READ_ONLY set",
+        )?;
+        check(
+            self.vector_index.params.lists > 0,
+            "VECTOR_INDEX_IVFFLAT_LISTS must be at least 1",
+        )?;
+        check(
+            (2..=100).contains(&self.vector_index.params.m),
+            "VECTOR_INDEX_HNSW_M must be 2-100",
+        )?;
+        check(
+            self.vector_index.params.ef_construction >= 2 * self.vector_index.params.m,
+            "VECTOR_INDEX_HNSW_EF_CONSTRUCTION must be at least twice VECTOR_INDEX_HNSW_M",
+        )?;
+        check(
+            self.embedding_normalize || self.embedding_distance_metric == DistanceMetric::InnerProduct,
+            "EMBEDDING_NORMALIZE=false requires EMBEDDING_DISTANCE_METRIC=inner_product",
+        )?;
+        check(
+            self.embedding_max_concurrency > 0,
+            "EMBEDDING_MAX_CONCURRENCY must be at least 1",
+        )?;
+        check(
+            self.analyzer.category_weights.values().all(|weights| {
+                weights.as_array().iter().all(|w| w.is_finite() && *w >= 0.0)
+                    && weights.as_array().iter().sum::<f64>() > 0.0
+            }),
+            "CATEGORY_AGENT_WEIGHTS must be non-negative with a positive total for every category",
+        )?;
+        check(
+            unit(self.anomaly.joint_amount_hour_weight),
+            "ANOMALY_JOINT_AMOUNT_HOUR_WEIGHT must be between 0 and 1",
+        )?;
+        check(
+            unit(self.anomaly.round_amount_risk),
+            "ANOMALY_ROUND_AMOUNT_RISK must be between 0 and 1",
+        )?;
+        check(
+            self.anomaly.round_amount_threshold >= 0.0 && self.anomaly.round_amount_unit >= 0.01,
+            "ANOMALY_ROUND_AMOUNT_THRESHOLD must be non-negative and ANOMALY_ROUND_AMOUNT_UNIT at least 0.01",
+        )?;
+        check(
+            self.geographic.travel_max_comparison_age_hours >= 0.0,
+            "GEO_TRAVEL_MAX_COMPARISON_AGE_HOURS must not be negative",
+        )?;
+        check(
+            self.geographic.recent_locations_limit > 0,
+            "GEO_RECENT_LOCATIONS_LIMIT must be at least 1",
+        )?;
+        check(
+            self.geographic.travel_distance_tolerance_km >= 0.0,
+            "GEO_TRAVEL_DISTANCE_TOLERANCE_KM must not be negative",
+        )?;
+        check(
+            self.geographic.max_location_accuracy_km >= 0.0,
+            "GEO_MAX_LOCATION_ACCURACY_KM must not be negative",
+        )?;
+        check(
+            self.geographic.max_travel_speed_kmh > 0.0 && self.geographic.max_ground_speed_kmh > 0.0,
+            "GEO_MAX_TRAVEL_SPEED_KMH and GEO_MAX_GROUND_SPEED_KMH must be positive",
+        )?;
+        check(
+            self.geographic.travel_transit_buffer_hours >= 0.0,
+            "GEO_TRAVEL_TRANSIT_BUFFER_HOURS must not be negative",
+        )?;
+        check(
+            unit(self.geographic.online_location_weight),
+            "GEO_ONLINE_LOCATION_WEIGHT must be between 0 and 1",
+        )?;
+        check(
+            unit(self.geographic.missing_location_risk),
+            "GEO_MISSING_LOCATION_RISK must be between 0 and 1",
+        )?;
+        check(
+            self.geographic.card_present_travel_weight >= 0.0,
+            "GEO_CARD_PRESENT_TRAVEL_WEIGHT must not be negative",
+        )?;
+        check(
+            self.merchant.category_ratio_threshold > 0.0,
+            "MERCHANT_CATEGORY_RATIO_THRESHOLD must be positive",
+        )?;
+        check(
+            unit(self.merchant.category_mismatch_risk),
+            "MERCHANT_CATEGORY_MISMATCH_RISK must be between 0 and 1",
+        )?;
+        check(
+            unit(self.merchant.category_similarity_floor),
+            "MERCHANT_CATEGORY_SIMILARITY_FLOOR must be between 0 and 1",
+        )?;
+        check(
+            self.merchant.new_merchant_threshold >= 0,
+            "MERCHANT_NEW_THRESHOLD must not be negative",
+        )?;
+        check(
+            unit(self.merchant.similar_min_similarity) && unit(self.merchant.similar_risky_fraud_rate),
+            "MERCHANT_SIMILAR_MIN_SIMILARITY and MERCHANT_SIMILAR_RISKY_FRAUD_RATE must be between 0 and 1",
+        )?;
+        check(
+            self.network.coordination_window_secs > 0,
+            "NETWORK_COORDINATION_WINDOW_SECS must be positive",
+        )?;
+        check(
+            self.network.coordination_volume_multiplier > 0.0,
+            "NETWORK_COORDINATION_VOLUME_MULTIPLIER must be positive",
+        )?;
+        check(
+            self.network.card_testing_window_secs > 0,
+            "NETWORK_CARD_TESTING_WINDOW_SECS must be positive",
+        )?;
+        check(
+            self.network.card_testing_half_life_secs >= 0.0,
+            "NETWORK_CARD_TESTING_HALF_LIFE_SECS must not be negative",
+        )?;
+        check(
+            unit(self.network.card_testing_risk),
+            "NETWORK_CARD_TESTING_RISK must be between 0 and 1",
+        )?;
+        check(
+            self.network.device_session_window_secs > 0,
+            "NETWORK_DEVICE_SESSION_WINDOW_SECS must be positive",
+        )?;
+        check(
+            unit(self.network.device_spread_discount),
+            "NETWORK_DEVICE_SPREAD_DISCOUNT must be between 0 and 1",
+        )?;
+        check(
+            self.network.ring_likely_multiple >= 1.0
+                && self.network.ring_confirmed_multiple >= self.network.ring_likely_multiple,
+            "NETWORK_RING_LIKELY_MULTIPLE must be at least 1 and NETWORK_RING_CONFIRMED_MULTIPLE at least as large",
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl ConfigSource for HashMap<&'static str, &'static str> {
+        fn get(&self, key: &str) -> Option<String> {
+            HashMap::get(self, key).map(|value| value.to_string())
+        }
+    }
+
+    fn model_version(settings: &[(&'static str, &'static str)]) -> String {
+        let mut source: HashMap<_, _> = settings.iter().copied().collect();
+        source.insert("DATABASE_URL", "postgres://localhost/fraudswarn");
+        Config::from_source(&source).unwrap().model_version()
+    }
+
+    #[test]
+    fn model_version_ignores_set_order_but_not_list_order() {
+        assert_eq!(
+            model_version(&[("GEO_ONLINE_CATEGORIES", "gaming,streaming,software")]),
+            model_version(&[("GEO_ONLINE_CATEGORIES", "software,gaming,streaming")]),
+        );
+        assert_ne!(
+            model_version(&[("ANALYSIS_GATES", "merchant_denylist,min_amount")]),
+            model_version(&[("ANALYSIS_GATES", "min_amount,merchant_denylist")]),
+        );
+    }
+}
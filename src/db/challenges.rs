@@ -0,0 +1,145 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{db::read_only, models::transaction::Transaction};
+
+/// How a challenged user's step-up authentication ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChallengeOutcome {
+    Passed,
+    Failed,
+    /// The user gave up, neither proof of fraud nor of legitimacy
+    Abandoned,
+}
+
+impl ChallengeOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChallengeOutcome::Passed => "passed",
+            ChallengeOutcome::Failed => "failed",
+            ChallengeOutcome::Abandoned => "abandoned",
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, serde::Serialize)]
+pub struct ChallengeRecord {
+    pub id: i64,
+    pub transaction_id: String,
+    pub user_id: String,
+    pub merchant: String,
+    /// "passed" / "failed" / "abandoned"
+    pub outcome: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A user's challenge outcomes at one merchant
+#[derive(sqlx::FromRow, Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct ChallengeHistory {
+    pub passed: i64,
+    pub failed: i64,
+    pub abandoned: i64,
+}
+
+impl ChallengeHistory {
+    pub fn total(&self) -> i64 {
+        self.passed + self.failed + self.abandoned
+    }
+}
+
+/// What became of an outcome report
+#[derive(Debug)]
+pub enum Recorded {
+    Stored(ChallengeRecord),
+    /// No stored transaction with this id
+    UnknownTransaction,
+    /// The transaction was never decided CHALLENGE, there was nothing to pass
+    NotChallenged,
+    /// The transaction already has an outcome, the first one stands
+    AlreadyRecorded,
+}
+
+/// Record how the challenge of a stored transaction ended. The user and
+/// merchant are taken from the transaction. Only a transaction that was
+/// challenged, by scoring or by an override, gets an outcome, and only one,
+/// so repeated reports can't pile up trust
+pub async fn record_outcome(pool: &PgPool, transaction_id: &str, outcome: ChallengeOutcome) -> Result<Recorded> {
+    read_only::ensure_writable("recording challenge outcomes")?;
+    let record = sqlx::query_as::<_, ChallengeRecord>(
+        r#"
+        INSERT INTO challenge_outcomes (transaction_id, user_id, merchant, outcome)
+        SELECT t.transaction_id, t.user_id, t.merchant, $2
+        FROM transactions t
+        WHERE t.transaction_id = $1
+        AND EXISTS (
+            SELECT 1
+            FROM analysis_results a
+            WHERE a.transaction_id = t.transaction_id
+            AND a.user_id = t.user_id
+            AND COALESCE(a.override_decision, a.decision) = 'CHALLENGE'
+        )
+        ON CONFLICT (transaction_id) DO NOTHING
+        RETURNING id, transaction_id, user_id, merchant, outcome, created_at
+        "#
+    )
+    .bind(transaction_id)
+    .bind(outcome.as_str())
+    .fetch_optional(pool)
+    .await?;
+    if let Some(record) = record {
+        return Ok(Recorded::Stored(record));
+    }
+
+    // Nothing inserted, find out why
+    let (stored, recorded) = sqlx::query_as::<_, (bool, bool)>(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM transactions WHERE transaction_id = $1),
+            EXISTS (SELECT 1 FROM challenge_outcomes WHERE transaction_id = $1)
+        "#
+    )
+    .bind(transaction_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(match (stored, recorded) {
+        (false, _) => Recorded::UnknownTransaction,
+        (true, true) => Recorded::AlreadyRecorded,
+        (true, false) => Recorded::NotChallenged,
+    })
+}
+
+/// Outcomes of the user's challenges at the merchant recorded within
+/// `window_days` before `transaction`, so a replay only sees what was known
+/// when it happened
+pub async fn merchant_history(
+    pool: &PgPool,
+    transaction: &Transaction,
+    window_days: i32,
+) -> Result<ChallengeHistory> {
+    let history = sqlx::query_as::<_, ChallengeHistory>(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE outcome = 'passed') as passed,
+            COUNT(*) FILTER (WHERE outcome = 'failed') as failed,
+            COUNT(*) FILTER (WHERE outcome = 'abandoned') as abandoned
+        FROM challenge_outcomes
+        WHERE user_id = $1
+        AND LOWER(merchant) = LOWER($2)
+        AND transaction_id != $5
+        AND created_at > $4::timestamptz - make_interval(days => $3)
+        AND created_at < $4::timestamptz
+        "#
+    )
+    .bind(&transaction.user_id)
+    .bind(&transaction.merchant)
+    .bind(window_days)
+    .bind(transaction.timestamp)
+    .bind(&transaction.transaction_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(history)
+}
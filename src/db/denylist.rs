@@ -0,0 +1,88 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{db::read_only, models::transaction::Transaction};
+
+/// A merchant that is blocked outright. Names are matched case-insensitively
+#[derive(sqlx::FromRow, Debug, Clone, serde::Serialize)]
+pub struct DenylistEntry {
+    pub merchant_name: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Key a merchant name is stored and looked up under
+fn normalize(merchant_name: &str) -> String {
+    merchant_name.trim().to_lowercase()
+}
+
+/// The denylist entry for a transaction's merchant, if it is on the list.
+/// Entries are keyed by name; a transaction carrying an acquirer merchant id
+/// also matches the entry of the merchant on record with that id, so a
+/// listed merchant stays blocked when it trades under a new name
+pub async fn find_entry(pool: &PgPool, transaction: &Transaction) -> Result<Option<DenylistEntry>> {
+    let entry = sqlx::query_as::<_, DenylistEntry>(
+        r#"
+        SELECT merchant_name, reason, created_at
+        FROM merchant_denylist
+        WHERE merchant_name = $1
+        OR merchant_name IN (
+            SELECT LOWER(TRIM(merchant_name)) FROM merchants WHERE acquirer_merchant_id = $2
+        )
+        ORDER BY merchant_name = $1 DESC
+        LIMIT 1
+        "#
+    )
+    .bind(normalize(&transaction.merchant))
+    .bind(&transaction.merchant_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(entry)
+}
+
+pub async fn list_entries(pool: &PgPool) -> Result<Vec<DenylistEntry>> {
+    let entries = sqlx::query_as::<_, DenylistEntry>(
+        r#"
+        SELECT merchant_name, reason, created_at
+        FROM merchant_denylist
+        ORDER BY merchant_name
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(entries)
+}
+
+/// Add a merchant, or update the reason of one already listed
+pub async fn add_entry(pool: &PgPool, merchant_name: &str, reason: Option<&str>) -> Result<DenylistEntry> {
+    read_only::ensure_writable("editing the merchant denylist")?;
+    let entry = sqlx::query_as::<_, DenylistEntry>(
+        r#"
+        INSERT INTO merchant_denylist (merchant_name, reason)
+        VALUES ($1, $2)
+        ON CONFLICT (merchant_name) DO UPDATE
+        SET reason = EXCLUDED.reason
+        RETURNING merchant_name, reason, created_at
+        "#
+    )
+    .bind(normalize(merchant_name))
+    .bind(reason)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(entry)
+}
+
+/// Remove a merchant, returning whether it was listed
+pub async fn remove_entry(pool: &PgPool, merchant_name: &str) -> Result<bool> {
+    read_only::ensure_writable("editing the merchant denylist")?;
+    let result = sqlx::query("DELETE FROM merchant_denylist WHERE merchant_name = $1")
+        .bind(normalize(merchant_name))
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
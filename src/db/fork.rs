@@ -1,17 +1,25 @@
-use sqlx::PgPool;
+use sqlx::{Executor, PgPool, postgres::PgPoolOptions};
 use anyhow::Result;
 
+use crate::db::read_only;
+
+#[derive(Clone)]
 pub struct ForkManager {
     main_pool: PgPool,
+    database_url: String,
 }
 
 impl ForkManager {
-    pub fn new(pool: PgPool) -> Self {
-        Self { main_pool: pool }
+    pub fn new(pool: PgPool, database_url: String) -> Self {
+        Self {
+            main_pool: pool,
+            database_url,
+        }
     }
     
     /// Create a new database fork for user analysis
     pub async fn create_fork(&self, fork_name: &str) -> Result<String> {
+        read_only::ensure_writable("creating database forks")?;
         tracing::info!("Creating fork: {}", fork_name);
         
         // Tiger Cloud fork creation
@@ -26,15 +34,19 @@ impl ForkManager {
     
     /// Connect to a specific fork
     pub async fn connect_to_fork(&self, fork_name: &str) -> Result<PgPool> {
-        let base_url = std::env::var("DATABASE_URL")?;
-        
         // Modify connection to use the fork
-        // Tiger Cloud uses schema-based forks
-        let fork_pool = PgPool::connect(&base_url).await?;
-        
-        // Set search path to the fork schema
-        sqlx::query(&format!("SET search_path TO {}", fork_name))
-            .execute(&fork_pool)
+        // Tiger Cloud uses schema-based forks. The search path is per
+        // connection, so it has to be set on every connection the pool opens
+        let set_search_path = format!("SET search_path TO \"{}\"", fork_name.replace('"', "\"\""));
+        let fork_pool = PgPoolOptions::new()
+            .after_connect(move |conn, _meta| {
+                let set_search_path = set_search_path.clone();
+                Box::pin(async move {
+                    conn.execute(set_search_path.as_str()).await?;
+                    Ok(())
+                })
+            })
+            .connect(&self.database_url)
             .await?;
         
         tracing::info!("✅ Connected to fork: {}", fork_name);
@@ -54,6 +66,22 @@ impl ForkManager {
         Ok(())
     }
     
+    /// Create a fork and connect to it. The fork is deleted when the
+    /// returned guard is cleaned up or dropped, including on early returns.
+    pub async fn open_fork(&self, fork_name: &str) -> Result<ForkGuard> {
+        self.create_fork(fork_name).await?;
+
+        // From here on the fork exists, the guard owns its cleanup
+        let mut guard = ForkGuard {
+            manager: self.clone(),
+            fork_name: fork_name.to_string(),
+            pool: None,
+        };
+        guard.pool = Some(self.connect_to_fork(fork_name).await?);
+
+        Ok(guard)
+    }
+    
     /// Generate unique fork name for transaction
     pub fn generate_fork_name(user_id: &str, transaction_id: &str) -> String {
         format!("user_{}_txn_{}", 
@@ -61,4 +89,60 @@ impl ForkManager {
             &transaction_id[..8]
         )
     }
-}
\ No newline at end of file
+}
+
+/// A live fork that is deleted when the guard goes away.
+///
+/// Prefer `cleanup().await` so failures are reported, dropping the guard
+/// (e.g. on `?`) falls back to deleting the fork in a background task.
+pub struct ForkGuard {
+    manager: ForkManager,
+    fork_name: String,
+    pool: Option<PgPool>,
+}
+
+impl ForkGuard {
+    pub fn name(&self) -> &str {
+        &self.fork_name
+    }
+
+    /// Connection pool scoped to the fork
+    pub fn pool(&self) -> &PgPool {
+        self.pool.as_ref().expect("fork pool is set until cleanup")
+    }
+
+    /// Close the fork's connections and delete it
+    pub async fn cleanup(mut self) -> Result<()> {
+        if let Some(pool) = self.pool.take() {
+            pool.close().await;
+        }
+        let fork_name = std::mem::take(&mut self.fork_name);
+        self.manager.cleanup_fork(&fork_name).await
+    }
+}
+
+impl Drop for ForkGuard {
+    fn drop(&mut self) {
+        // Already cleaned up explicitly
+        if self.fork_name.is_empty() {
+            return;
+        }
+
+        let manager = self.manager.clone();
+        let fork_name = std::mem::take(&mut self.fork_name);
+        let pool = self.pool.take();
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if let Some(pool) = pool {
+                        pool.close().await;
+                    }
+                    if let Err(e) = manager.cleanup_fork(&fork_name).await {
+                        tracing::error!("❌ Failed to delete fork {}: {}", fork_name, e);
+                    }
+                });
+            }
+            Err(_) => tracing::error!("❌ Fork {} dropped outside a runtime, delete it manually", fork_name),
+        }
+    }
+}
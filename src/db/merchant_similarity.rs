@@ -0,0 +1,145 @@
+use anyhow::Result;
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+/// What makes another merchant a similar risky one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimilarityCriteria {
+    /// Embedding similarity above which two merchants count as alike
+    pub min_similarity: f64,
+    /// Fraud rate above which a merchant counts as risky
+    pub min_fraud_rate: f64,
+    /// Whether a merchant can be among its own matches
+    pub exclude_self: bool,
+}
+
+/// A risky merchant alike to the one being looked up
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SimilarMerchant {
+    pub merchant_name: String,
+    pub similarity: f64,
+    pub fraud_rate: f64,
+}
+
+/// Cached matches, most similar first, and how old the snapshot they came
+/// from is
+#[derive(Debug, Clone)]
+pub struct SimilarRiskyMatches {
+    pub merchants: Vec<SimilarMerchant>,
+    pub age: Duration,
+}
+
+type Snapshot = (Instant, SimilarityCriteria, HashMap<String, Vec<SimilarMerchant>>);
+
+/// Risky merchants similar to each merchant, recomputed at most once per
+/// `ttl`, after `invalidate` or when asked with different criteria.
+///
+/// The lookup compares a merchant's embedding with every other merchant's,
+/// far too expensive per transaction, while embeddings and fraud rates only
+/// change when merchants are (re)loaded.
+pub struct SimilarRiskyMerchants {
+    ttl: Duration,
+    cache: RwLock<Option<Snapshot>>,
+}
+
+impl SimilarRiskyMerchants {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: RwLock::new(None),
+        }
+    }
+
+    /// Drop the snapshot, the next lookup recomputes. Call after changing
+    /// merchant embeddings or fraud rates
+    pub async fn invalidate(&self) {
+        *self.cache.write().await = None;
+    }
+
+    /// Risky merchants similar to `merchant_name` under `criteria`, none for
+    /// a merchant without an embedding, refreshing the snapshot if stale
+    pub async fn find(
+        &self,
+        pool: &PgPool,
+        merchant_name: &str,
+        criteria: SimilarityCriteria,
+    ) -> Result<SimilarRiskyMatches> {
+        let fresh = |snapshot: &Snapshot| snapshot.0.elapsed() < self.ttl && snapshot.1 == criteria;
+
+        {
+            let cache = self.cache.read().await;
+            if let Some(snapshot) = cache.as_ref().filter(|snapshot| fresh(snapshot)) {
+                return Ok(lookup(&snapshot.2, merchant_name, snapshot.0));
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+        // Another request may have refreshed while we waited for the lock
+        if let Some(snapshot) = cache.as_ref().filter(|snapshot| fresh(snapshot)) {
+            return Ok(lookup(&snapshot.2, merchant_name, snapshot.0));
+        }
+
+        let matches = fetch_similar_risky_merchants(pool, criteria).await?;
+        tracing::debug!("🏪 Refreshed similar risky merchants for {} merchants", matches.len());
+        let loaded_at = Instant::now();
+        let found = lookup(&matches, merchant_name, loaded_at);
+        *cache = Some((loaded_at, criteria, matches));
+
+        Ok(found)
+    }
+}
+
+fn lookup(
+    matches: &HashMap<String, Vec<SimilarMerchant>>,
+    merchant_name: &str,
+    loaded_at: Instant,
+) -> SimilarRiskyMatches {
+    SimilarRiskyMatches {
+        merchants: matches.get(merchant_name).cloned().unwrap_or_default(),
+        age: loaded_at.elapsed(),
+    }
+}
+
+/// For every merchant with an embedding, the other risky merchants with a
+/// similar one, most similar first
+pub async fn fetch_similar_risky_merchants(
+    pool: &PgPool,
+    criteria: SimilarityCriteria,
+) -> Result<HashMap<String, Vec<SimilarMerchant>>> {
+    let rows = sqlx::query_as::<_, (String, String, f64, f64)>(
+        r#"
+        SELECT m.merchant_name,
+               r.merchant_name,
+               (1 - (r.merchant_embedding <=> m.merchant_embedding))::float8 as similarity,
+               r.fraud_rate::float8
+        FROM merchants m
+        JOIN merchants r
+            ON r.fraud_rate > $1
+            AND NOT ($3 AND r.merchant_name = m.merchant_name)
+            AND r.merchant_embedding IS NOT NULL
+            AND (1 - (r.merchant_embedding <=> m.merchant_embedding)) > $2
+        WHERE m.merchant_embedding IS NOT NULL
+        ORDER BY m.merchant_name, similarity DESC
+        "#,
+    )
+    .bind(criteria.min_fraud_rate)
+    .bind(criteria.min_similarity)
+    .bind(criteria.exclude_self)
+    .fetch_all(pool)
+    .await?;
+
+    let mut matches: HashMap<String, Vec<SimilarMerchant>> = HashMap::new();
+    for (merchant_name, similar_name, similarity, fraud_rate) in rows {
+        matches.entry(merchant_name).or_default().push(SimilarMerchant {
+            merchant_name: similar_name,
+            similarity,
+            fraud_rate,
+        });
+    }
+
+    Ok(matches)
+}
@@ -1,4 +1,13 @@
+pub mod challenges;
+pub mod denylist;
 pub mod fork;
+pub mod merchant_similarity;
+pub mod persistence;
 pub mod pool;
+pub mod population;
+pub mod profile;
+pub mod read_only;
 pub mod schema;
-pub mod vector_search;
\ No newline at end of file
+pub mod transactions;
+pub mod user_overrides;
+pub mod vector_search;
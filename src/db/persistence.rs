@@ -0,0 +1,288 @@
+use anyhow::Result;
+use sqlx::PgPool;
+
+use crate::{
+    db::read_only,
+    embedding::{EmbeddingPrecision, embedding_to_pgvector, pgvector_to_embedding, update_centroid},
+    models::transaction::{AnalysisResult, Decision, Transaction},
+};
+
+/// When the analyze endpoint writes its result
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PersistenceMode {
+    /// Respond after the commit, `persisted` reports whether it succeeded
+    #[default]
+    Sync,
+    /// Respond immediately and write in the background, `persisted` is false
+    Async,
+}
+
+impl std::str::FromStr for PersistenceMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "sync" => Ok(PersistenceMode::Sync),
+            "async" => Ok(PersistenceMode::Async),
+            other => Err(format!("Unknown persistence mode '{}', expected sync or async", other)),
+        }
+    }
+}
+
+/// Store a scored transaction according to `mode`, returning whether it is
+/// committed. Failures are logged, the decision stands either way.
+pub async fn persist_analysis(
+    pool: &PgPool,
+    transaction: &Transaction,
+    result: &AnalysisResult,
+    override_decision: Option<Decision>,
+    precision: EmbeddingPrecision,
+    mode: PersistenceMode,
+) -> bool {
+    if read_only::is_read_only() {
+        return false;
+    }
+
+    match mode {
+        PersistenceMode::Sync => match save_analysis(pool, transaction, result, override_decision, precision).await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("⚠️ Failed to persist analysis for {}: {}", transaction.transaction_id, e);
+                false
+            }
+        },
+        PersistenceMode::Async => {
+            let pool = pool.clone();
+            let transaction = transaction.clone();
+            let result = result.clone();
+            tokio::spawn(async move {
+                if let Err(e) = save_analysis(&pool, &transaction, &result, override_decision, precision).await {
+                    tracing::warn!("⚠️ Failed to persist analysis for {}: {}", transaction.transaction_id, e);
+                }
+            });
+            false
+        }
+    }
+}
+
+/// Store a scored transaction.
+///
+/// The `transactions` row is upserted with the latest decision and agent
+/// scores (refused when the id belongs to another user), and the full result is appended to `analysis_results` so that
+/// re-scoring keeps history instead of overwriting it.
+///
+/// `result` is the decision as scored. An operator override only changes the
+/// response, it is recorded in `override_decision` so hysteresis, backtests
+/// and exports keep seeing what the model decided.
+pub async fn save_analysis(
+    pool: &PgPool,
+    transaction: &Transaction,
+    result: &AnalysisResult,
+    override_decision: Option<Decision>,
+    precision: EmbeddingPrecision,
+) -> Result<()> {
+    read_only::ensure_writable("storing analyses")?;
+    let mut tx = pool.begin().await?;
+
+    // transactions.user_id references users, make sure the user exists
+    sqlx::query(
+        r#"
+        INSERT INTO users (user_id)
+        VALUES ($1)
+        ON CONFLICT (user_id) DO NOTHING
+        "#
+    )
+    .bind(&transaction.user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // xmax = 0 only for freshly inserted rows, rescoring must not fold the
+    // same transaction into the spending profile twice
+    let inserted = sqlx::query_scalar::<_, bool>(&format!(
+        r#"
+        INSERT INTO transactions (
+            transaction_id, user_id, amount, merchant, merchant_category,
+            location, timestamp, payment_method, device_fingerprint,
+            risk_score, decision,
+            pattern_score, anomaly_score, geographic_score, merchant_score,
+            transaction_embedding, metadata, merchant_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16::{vector}, $17, $18)
+        ON CONFLICT (transaction_id) DO UPDATE
+        SET risk_score = EXCLUDED.risk_score,
+            decision = EXCLUDED.decision,
+            pattern_score = EXCLUDED.pattern_score,
+            anomaly_score = EXCLUDED.anomaly_score,
+            geographic_score = EXCLUDED.geographic_score,
+            merchant_score = EXCLUDED.merchant_score,
+            transaction_embedding = COALESCE(EXCLUDED.transaction_embedding, transactions.transaction_embedding),
+            metadata = COALESCE(EXCLUDED.metadata, transactions.metadata)
+        WHERE transactions.user_id = EXCLUDED.user_id
+        RETURNING (xmax = 0)
+        "#,
+        vector = precision.sql_type()
+    ))
+    .bind(&transaction.transaction_id)
+    .bind(&transaction.user_id)
+    .bind(transaction.amount)
+    .bind(&transaction.merchant)
+    .bind(&transaction.merchant_category)
+    .bind(transaction.location.as_ref().map(serde_json::to_value).transpose()?)
+    .bind(transaction.timestamp)
+    .bind(&transaction.payment_method)
+    .bind(&transaction.device_fingerprint)
+    .bind(result.risk_score)
+    .bind(result.decision.as_str())
+    .bind(result.agent_scores.pattern)
+    .bind(result.agent_scores.anomaly)
+    .bind(result.agent_scores.geographic)
+    .bind(result.agent_scores.merchant)
+    .bind(result.embedding.as_deref().map(embedding_to_pgvector))
+    .bind(&transaction.metadata)
+    .bind(&transaction.merchant_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    // Nothing returned: the id is taken by another user's transaction,
+    // which must keep its own decision
+    let Some(inserted) = inserted else {
+        anyhow::bail!(
+            "Transaction id {} already belongs to another user",
+            transaction.transaction_id
+        );
+    };
+
+    if inserted && let Some(embedding) = &result.embedding {
+        update_spending_profile(&mut tx, &transaction.user_id, embedding).await?;
+    }
+
+    // The weights aren't part of the response unless verbose, the audit
+    // trail keeps them for contribution stats
+    let mut stored = serde_json::to_value(result)?;
+    if let Some(applied) = &result.applied_weights {
+        stored["agent_weights"] = serde_json::to_value(applied)?;
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO analysis_results (
+            transaction_id, user_id, decision, risk_score, confidence, model_version, result, override_decision
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#
+    )
+    .bind(&transaction.transaction_id)
+    .bind(&transaction.user_id)
+    .bind(result.decision.as_str())
+    .bind(result.risk_score)
+    .bind(result.confidence)
+    .bind(&result.model_version)
+    .bind(stored)
+    .bind(override_decision.map(|decision| decision.as_str()))
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    tracing::debug!("💾 Persisted analysis for {}", transaction.transaction_id);
+
+    Ok(())
+}
+
+/// Most recent stored decision for a user's transaction, if it was scored
+/// before. Transaction ids come from clients, another user's transaction
+/// under the same id doesn't count
+pub async fn latest_decision(pool: &PgPool, transaction_id: &str, user_id: &str) -> Result<Option<Decision>> {
+    let decision = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT decision
+        FROM analysis_results
+        WHERE transaction_id = $1
+        AND user_id = $2
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#
+    )
+    .bind(transaction_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(decision.and_then(|d| d.parse().ok()))
+}
+
+/// Whether the user had a transaction at this merchant within the window
+/// before `transaction` that was challenged and then confirmed legitimate
+pub async fn recently_cleared_challenge(pool: &PgPool, transaction: &Transaction, window_secs: i64) -> Result<bool> {
+    let cleared = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM transactions
+            WHERE user_id = $1
+            AND merchant = $2
+            AND decision = 'CHALLENGE'
+            AND fraud_label = false
+            AND transaction_id != $5
+            AND timestamp > $4::timestamptz - make_interval(secs => $3)
+            AND timestamp < $4::timestamptz
+        )
+        "#
+    )
+    .bind(&transaction.user_id)
+    .bind(&transaction.merchant)
+    .bind(window_secs as f64)
+    .bind(transaction.timestamp)
+    .bind(&transaction.transaction_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(cleared)
+}
+
+/// Fold a new transaction embedding into the user's spending centroid
+async fn update_spending_profile(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user_id: &str,
+    embedding: &[f32],
+) -> Result<()> {
+    let current = sqlx::query_as::<_, (Option<String>, i32)>(
+        r#"
+        SELECT spending_centroid::text, COALESCE(centroid_count, 0)
+        FROM users
+        WHERE user_id = $1
+        FOR UPDATE
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let (centroid, count) = match current {
+        Some((Some(text), count)) => (pgvector_to_embedding(&text), count),
+        _ => (None, 0),
+    };
+
+    let (updated, new_count) = match centroid {
+        Some(centroid) if centroid.len() == embedding.len() && count > 0 => {
+            (update_centroid(&centroid, count as i64, embedding), count + 1)
+        }
+        _ => (embedding.to_vec(), 1),
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE users
+        SET spending_centroid = $2::vector,
+            centroid_count = $3
+        WHERE user_id = $1
+        "#
+    )
+    .bind(user_id)
+    .bind(embedding_to_pgvector(&updated))
+    .bind(new_count)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
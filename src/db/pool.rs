@@ -1,10 +1,18 @@
-use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
 use anyhow::Result;
 
-pub async fn create_pool(database_url: &str) -> Result<PgPool> {
+use crate::db::read_only;
+
+pub async fn create_pool(database_url: &str, max_connections: u32) -> Result<PgPool> {
+    let mut options: PgConnectOptions = database_url.parse()?;
+    // In read-only mode Postgres itself refuses writes on these connections
+    if read_only::is_read_only() {
+        options = options.options([("default_transaction_read_only", "on")]);
+    }
+
     let pool = PgPoolOptions::new()
-        .max_connections(20)
-        .connect(database_url)
+        .max_connections(max_connections)
+        .connect_with(options)
         .await?;
     
     tracing::info!("-->Connected to Tiger Cloud database");
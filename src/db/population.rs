@@ -0,0 +1,147 @@
+use anyhow::Result;
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+use crate::category::CategoryTaxonomy;
+
+/// Amount distribution of one merchant category across all users
+#[derive(sqlx::FromRow, Debug, Clone, serde::Serialize)]
+pub struct CategoryStats {
+    pub merchant_category: String,
+    pub mean: f64,
+    pub stddev: f64,
+    pub transaction_count: i64,
+}
+
+impl CategoryStats {
+    /// Standard score of `amount` within this category, `None` when the
+    /// category has no spread to measure against
+    pub fn z_score(&self, amount: f64) -> Option<f64> {
+        if self.stddev > 0.0 {
+            Some((amount - self.mean) / self.stddev)
+        } else {
+            None
+        }
+    }
+}
+
+/// Per-category statistics, recomputed at most once per `ttl`.
+///
+/// The aggregate scans every recent transaction, which is far too expensive
+/// to run per request, and the distribution moves slowly anyway.
+pub struct PopulationStats {
+    ttl: Duration,
+    taxonomy: CategoryTaxonomy,
+    cache: RwLock<Option<(Instant, HashMap<String, CategoryStats>)>>,
+    fraud_rate: RwLock<Option<(Instant, f64)>>,
+}
+
+impl PopulationStats {
+    pub fn new(ttl: Duration, taxonomy: CategoryTaxonomy) -> Self {
+        Self {
+            ttl,
+            taxonomy,
+            cache: RwLock::new(None),
+            fraud_rate: RwLock::new(None),
+        }
+    }
+
+    /// Statistics for `merchant_category`, refreshing the cache if stale
+    pub async fn category(&self, pool: &PgPool, merchant_category: &str) -> Result<Option<CategoryStats>> {
+        let merchant_category = self.taxonomy.normalize(merchant_category);
+        let merchant_category = merchant_category.as_str();
+        {
+            let cache = self.cache.read().await;
+            if let Some((loaded_at, stats)) = cache.as_ref() && loaded_at.elapsed() < self.ttl {
+                return Ok(stats.get(merchant_category).cloned());
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+        // Another request may have refreshed while we waited for the lock
+        if let Some((loaded_at, stats)) = cache.as_ref() && loaded_at.elapsed() < self.ttl {
+            return Ok(stats.get(merchant_category).cloned());
+        }
+
+        let stats = fetch_category_stats(pool, &self.taxonomy).await?;
+        tracing::debug!("📊 Refreshed population stats for {} categories", stats.len());
+        let found = stats.get(merchant_category).cloned();
+        *cache = Some((Instant::now(), stats));
+
+        Ok(found)
+    }
+
+    /// Share of labeled transactions that are fraud, cached for the same
+    /// `ttl` as the category statistics
+    pub async fn fraud_rate(&self, pool: &PgPool) -> Result<f64> {
+        if let Some((loaded_at, rate)) = *self.fraud_rate.read().await
+            && loaded_at.elapsed() < self.ttl
+        {
+            return Ok(rate);
+        }
+
+        let mut cache = self.fraud_rate.write().await;
+        // Another request may have refreshed while we waited for the lock
+        if let Some((loaded_at, rate)) = *cache
+            && loaded_at.elapsed() < self.ttl
+        {
+            return Ok(rate);
+        }
+
+        let rate = fetch_fraud_rate(pool).await?;
+        *cache = Some((Instant::now(), rate));
+        Ok(rate)
+    }
+}
+
+/// Share of labeled transactions that are fraud, 0 without labels
+pub async fn fetch_fraud_rate(pool: &PgPool) -> Result<f64> {
+    let rate = sqlx::query_scalar::<_, Option<f64>>(
+        r#"
+        SELECT AVG(CASE WHEN fraud_label THEN 1.0 ELSE 0.0 END)::float8
+        FROM transactions
+        WHERE fraud_label IS NOT NULL
+        "#,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(rate.unwrap_or(0.0))
+}
+
+/// Mean and standard deviation of legitimate amounts per normalized category
+/// over the last 90 days
+pub async fn fetch_category_stats(
+    pool: &PgPool,
+    taxonomy: &CategoryTaxonomy,
+) -> Result<HashMap<String, CategoryStats>> {
+    let (aliases, canonicals) = taxonomy.sql_aliases();
+    let rows = sqlx::query_as::<_, CategoryStats>(&format!(
+        r#"
+        SELECT
+            {category} as merchant_category,
+            AVG(amount)::float8 as mean,
+            COALESCE(STDDEV_SAMP(amount), 0)::float8 as stddev,
+            COUNT(*) as transaction_count
+        FROM transactions
+        WHERE timestamp > NOW() - INTERVAL '90 days'
+        AND (fraud_label = false OR fraud_label IS NULL)
+        GROUP BY 1
+        HAVING COUNT(*) >= 2
+        "#,
+        category = CategoryTaxonomy::sql_expr("merchant_category", 1)
+    ))
+    .bind(aliases)
+    .bind(canonicals)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.merchant_category.clone(), row))
+        .collect())
+}
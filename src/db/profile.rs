@@ -0,0 +1,195 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Stored profile columns from `users`
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
+pub struct UserRecord {
+    pub user_id: String,
+    pub email: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub average_transaction_amount: f64,
+    pub common_categories: Vec<String>,
+    pub home_location: Option<serde_json::Value>,
+    pub centroid_count: i32,
+}
+
+/// Per-category spending over the last 90 days, as the pattern agent sees it
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
+pub struct CategorySpending {
+    pub merchant_category: String,
+    pub average_amount: f64,
+    pub transaction_count: i64,
+}
+
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
+pub struct RecentLocation {
+    pub city: String,
+    pub country: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
+pub struct DeviceUsage {
+    pub device_fingerprint: String,
+    pub transaction_count: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
+pub struct TransactionCounts {
+    pub total: i64,
+    pub confirmed_fraud: i64,
+}
+
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
+pub struct RecentDecision {
+    pub transaction_id: String,
+    pub decision: String,
+    pub risk_score: f64,
+    pub confidence: f64,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+pub async fn fetch_user(pool: &PgPool, user_id: &str) -> Result<Option<UserRecord>> {
+    let user = sqlx::query_as::<_, UserRecord>(
+        r#"
+        SELECT
+            user_id,
+            email,
+            created_at,
+            COALESCE(average_transaction_amount, 0)::float8 as average_transaction_amount,
+            COALESCE(common_categories, ARRAY[]::TEXT[]) as common_categories,
+            home_location,
+            COALESCE(centroid_count, 0) as centroid_count
+        FROM users
+        WHERE user_id = $1
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(user)
+}
+
+pub async fn fetch_category_spending(pool: &PgPool, user_id: &str) -> Result<Vec<CategorySpending>> {
+    let rows = sqlx::query_as::<_, CategorySpending>(
+        r#"
+        SELECT
+            merchant_category,
+            AVG(amount)::float8 as average_amount,
+            COUNT(*) as transaction_count
+        FROM transactions
+        WHERE user_id = $1
+        AND timestamp > NOW() - INTERVAL '90 days'
+        AND (fraud_label = false OR fraud_label IS NULL)
+        GROUP BY merchant_category
+        ORDER BY transaction_count DESC
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn fetch_recent_locations(
+    pool: &PgPool,
+    user_id: &str,
+    limit: i64,
+) -> Result<Vec<RecentLocation>> {
+    let rows = sqlx::query_as::<_, RecentLocation>(
+        r#"
+        SELECT
+            COALESCE(location->>'city', 'Unknown') as city,
+            COALESCE(location->>'country', 'Unknown') as country,
+            COALESCE((location->>'lat')::float8, 0.0) as lat,
+            COALESCE((location->>'lon')::float8, 0.0) as lon,
+            timestamp
+        FROM transactions
+        WHERE user_id = $1
+        AND location IS NOT NULL
+        AND timestamp IS NOT NULL
+        ORDER BY timestamp DESC
+        LIMIT $2
+        "#
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn fetch_devices(pool: &PgPool, user_id: &str) -> Result<Vec<DeviceUsage>> {
+    let rows = sqlx::query_as::<_, DeviceUsage>(
+        r#"
+        SELECT
+            device_fingerprint,
+            COUNT(*) as transaction_count,
+            MIN(timestamp) as first_seen,
+            MAX(timestamp) as last_seen
+        FROM transactions
+        WHERE user_id = $1
+        AND device_fingerprint IS NOT NULL
+        AND timestamp IS NOT NULL
+        GROUP BY device_fingerprint
+        ORDER BY last_seen DESC
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn fetch_transaction_counts(pool: &PgPool, user_id: &str) -> Result<TransactionCounts> {
+    let counts = sqlx::query_as::<_, TransactionCounts>(
+        r#"
+        SELECT
+            COUNT(*) as total,
+            COUNT(*) FILTER (WHERE fraud_label = true) as confirmed_fraud
+        FROM transactions
+        WHERE user_id = $1
+        "#
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(counts)
+}
+
+pub async fn fetch_recent_decisions(
+    pool: &PgPool,
+    user_id: &str,
+    limit: i64,
+) -> Result<Vec<RecentDecision>> {
+    let rows = sqlx::query_as::<_, RecentDecision>(
+        r#"
+        SELECT
+            transaction_id,
+            decision,
+            COALESCE(risk_score, 0) as risk_score,
+            COALESCE(confidence, 0) as confidence,
+            created_at
+        FROM analysis_results
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
@@ -0,0 +1,37 @@
+//! Crate-wide read-only mode, for running against a production replica or
+//! during an investigation.
+//!
+//! Every function that writes checks [`ensure_writable`] before touching the
+//! database, and pools opened in this mode default their sessions to
+//! read-only transactions, so a write that slips past the check is still
+//! refused by Postgres.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// A write attempted in read-only mode
+#[derive(Debug, thiserror::Error)]
+#[error("Read-only mode: {operation} is disabled")]
+pub struct ReadOnlyError {
+    pub operation: String,
+}
+
+/// Switch the mode, done once at startup before any pool is created
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// Refuse `operation` in read-only mode
+pub fn ensure_writable(operation: &str) -> Result<(), ReadOnlyError> {
+    if is_read_only() {
+        return Err(ReadOnlyError {
+            operation: operation.to_string(),
+        });
+    }
+    Ok(())
+}
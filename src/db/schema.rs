@@ -0,0 +1,220 @@
+use anyhow::Result;
+use sqlx::PgPool;
+
+use crate::config::{self, ConfigError, ConfigSource};
+use crate::db::read_only;
+use crate::embedding::DistanceMetric;
+
+/// Embedding columns with a vector index: (table, column, whether it is
+/// searched by `EMBEDDING_DISTANCE_METRIC` rather than always cosine)
+const VECTOR_COLUMNS: &[(&str, &str, bool)] = &[
+    ("transactions", "transaction_embedding", true),
+    ("merchants", "merchant_embedding", false),
+];
+
+/// Stored embeddings sampled by [`check_embedding_normalization`]
+const NORMALIZATION_SAMPLE: i64 = 200;
+
+/// pgvector approximate index method
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorIndexType {
+    /// Better recall and no training step, slower to build
+    #[default]
+    Hnsw,
+    /// Quick to build, but clusters are fixed from the rows present at build time
+    IvfFlat,
+}
+
+impl VectorIndexType {
+    fn method(self) -> &'static str {
+        match self {
+            VectorIndexType::Hnsw => "hnsw",
+            VectorIndexType::IvfFlat => "ivfflat",
+        }
+    }
+}
+
+impl std::str::FromStr for VectorIndexType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "hnsw" => Ok(VectorIndexType::Hnsw),
+            "ivfflat" => Ok(VectorIndexType::IvfFlat),
+            other => Err(format!("Unknown vector index type '{}', expected hnsw or ivfflat", other)),
+        }
+    }
+}
+
+/// Build parameters, each only used by its index type
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VectorIndexParams {
+    /// HNSW: connections per node
+    pub m: u32,
+    /// HNSW: candidate list size while building
+    pub ef_construction: u32,
+    /// IVFFlat: number of clusters
+    pub lists: u32,
+}
+
+impl Default for VectorIndexParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 64,
+            lists: 100,
+        }
+    }
+}
+
+/// Whether and how to create missing vector indexes at startup
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct VectorIndexConfig {
+    pub ensure_at_startup: bool,
+    pub index_type: VectorIndexType,
+    pub params: VectorIndexParams,
+}
+
+impl VectorIndexConfig {
+    /// Read the `VECTOR_INDEX_*` settings
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            ensure_at_startup: config::parsed(source, "VECTOR_INDEX_ENSURE", defaults.ensure_at_startup)?,
+            index_type: config::parsed(source, "VECTOR_INDEX_TYPE", defaults.index_type)?,
+            params: VectorIndexParams {
+                m: config::parsed(source, "VECTOR_INDEX_HNSW_M", defaults.params.m)?,
+                ef_construction: config::parsed(
+                    source,
+                    "VECTOR_INDEX_HNSW_EF_CONSTRUCTION",
+                    defaults.params.ef_construction,
+                )?,
+                lists: config::parsed(source, "VECTOR_INDEX_IVFFLAT_LISTS", defaults.params.lists)?,
+            },
+        })
+    }
+}
+
+/// Create an index for the column's distance metric on every embedding
+/// column that has no pgvector index with the metric's operator class yet,
+/// returning the names of the indexes created. Columns already indexed for
+/// the metric are left alone, whatever their index type
+pub async fn ensure_vector_indexes(
+    pool: &PgPool,
+    index_type: VectorIndexType,
+    params: &VectorIndexParams,
+    metric: DistanceMetric,
+) -> Result<Vec<String>> {
+    read_only::ensure_writable("creating vector indexes")?;
+    let mut created = Vec::new();
+
+    for &(table, column, follows_metric) in VECTOR_COLUMNS {
+        // The column's type (vector or halfvec, per the storage precision)
+        // picks the operator class
+        let column_type = sqlx::query_scalar::<_, Option<String>>(
+            r#"
+            SELECT format_type(a.atttypid, a.atttypmod)
+            FROM pg_attribute a
+            WHERE a.attrelid = to_regclass($1)
+            AND a.attname = $2
+            AND NOT a.attisdropped
+            "#,
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_optional(pool)
+        .await?
+        .flatten();
+
+        let Some(column_type) = column_type else {
+            tracing::warn!("⚠️ {}.{} doesn't exist, no vector index created", table, column);
+            continue;
+        };
+
+        let metric = if follows_metric { metric } else { DistanceMetric::Cosine };
+        let opclass = metric.opclass(if column_type.starts_with("halfvec") { "halfvec" } else { "vector" });
+
+        let existing = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT indexdef
+            FROM pg_indexes
+            WHERE tablename = $1
+            AND indexdef ~* ('USING (hnsw|ivfflat) \(' || $2 || ' ')
+            "#,
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_all(pool)
+        .await?;
+
+        // An index with another operator class can't serve this metric's
+        // operator, the queries would fall back to a sequential scan
+        if existing.iter().any(|def| def.contains(&opclass)) {
+            continue;
+        }
+        let mut name = format!("idx_{}_{}_{}", table, column, index_type.method());
+        if !existing.is_empty() {
+            tracing::warn!(
+                "⚠️ {}.{} is indexed without {}, creating one for the configured metric. Drop the old index once it's built",
+                table,
+                column,
+                opclass
+            );
+            name = format!("{}_{}", name, opclass);
+        }
+
+        let with = match index_type {
+            VectorIndexType::Hnsw => format!("m = {}, ef_construction = {}", params.m, params.ef_construction),
+            VectorIndexType::IvfFlat => format!("lists = {}", params.lists),
+        };
+        tracing::info!("🏗️ Creating {} index {} on {}.{}", index_type.method(), name, table, column);
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {name} ON {table} USING {method} ({column} {opclass}) WITH ({with})",
+            method = index_type.method(),
+        ))
+        .execute(pool)
+        .await?;
+        created.push(name);
+    }
+
+    Ok(created)
+}
+
+/// Refuse to run against stored transaction embeddings from the other
+/// normalization mode. Unit and raw vectors in one table can't be compared
+/// meaningfully, and nearest-neighbour results would silently favour one
+/// kind; `--reembed-all` rewrites them all in the configured mode
+pub async fn check_embedding_normalization(pool: &PgPool, normalize: bool) -> Result<()> {
+    let (unit, total) = sqlx::query_as::<_, (i64, i64)>(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE ABS(vector_norm(transaction_embedding::vector) - 1) < 1e-3),
+               COUNT(*)
+        FROM (
+            SELECT transaction_embedding
+            FROM transactions
+            WHERE transaction_embedding IS NOT NULL
+            ORDER BY timestamp DESC
+            LIMIT $1
+        ) sample
+        "#,
+    )
+    .bind(NORMALIZATION_SAMPLE)
+    .fetch_one(pool)
+    .await?;
+
+    // Raw mean-pooled embeddings are practically never exactly unit length
+    let mismatched = if normalize { total - unit } else { unit };
+    if mismatched > 0 {
+        anyhow::bail!(
+            "{} of the {} most recent stored embeddings are {}, but EMBEDDING_NORMALIZE={}. \
+             Mixing normalized and un-normalized embeddings is invalid: run --reembed-all first",
+            mismatched,
+            total,
+            if normalize { "not unit length" } else { "unit length" },
+            normalize
+        );
+    }
+
+    Ok(())
+}
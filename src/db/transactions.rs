@@ -0,0 +1,264 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    db::read_only,
+    models::transaction::{Location, Transaction},
+};
+
+/// Condition on `transactions` rows at the same merchant as a transaction
+/// whose name and merchant id are bound to `$name` and `$id`: the same
+/// acquirer id, or the same name where either side has no id, as the
+/// merchant agent's lookup matches them
+pub(crate) fn same_merchant_sql(name: usize, id: usize) -> String {
+    format!(
+        "(merchant_id = ${id} OR (merchant = ${name} AND (merchant_id IS NULL OR ${id}::text IS NULL)))",
+        id = id,
+        name = name,
+    )
+}
+
+/// Columns needed to rebuild a `Transaction` from the `transactions` table
+pub(crate) const STORED_TRANSACTION_COLUMNS: &str = r#"
+    transaction_id,
+    COALESCE(user_id, '') as user_id,
+    amount::float8 as amount,
+    merchant,
+    merchant_id,
+    merchant_category,
+    location,
+    COALESCE(timestamp, NOW()) as timestamp,
+    payment_method,
+    device_fingerprint,
+    fraud_label,
+    decision,
+    metadata
+"#;
+
+/// A transaction row as persisted, including its label
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
+pub struct StoredTransaction {
+    pub transaction_id: String,
+    pub user_id: String,
+    pub amount: f64,
+    pub merchant: String,
+    pub merchant_id: Option<String>,
+    pub merchant_category: String,
+    pub location: Option<serde_json::Value>,
+    pub timestamp: DateTime<Utc>,
+    pub payment_method: Option<String>,
+    pub device_fingerprint: Option<String>,
+    pub fraud_label: Option<bool>,
+    /// Latest stored decision, if the transaction has been scored
+    pub decision: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl StoredTransaction {
+    pub fn to_transaction(&self) -> Transaction {
+        let location = self
+            .location
+            .clone()
+            .and_then(|l| serde_json::from_value::<Location>(l).ok());
+
+        Transaction {
+            transaction_id: self.transaction_id.clone(),
+            user_id: self.user_id.clone(),
+            amount: self.amount,
+            merchant: self.merchant.clone(),
+            merchant_id: self.merchant_id.clone(),
+            merchant_category: self.merchant_category.clone(),
+            location,
+            timestamp: self.timestamp,
+            payment_method: self.payment_method.clone().unwrap_or_default(),
+            device_fingerprint: self.device_fingerprint.clone().unwrap_or_default(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+/// Fetch transactions that carry a fraud label, most recent first
+pub async fn fetch_labeled_transactions(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<StoredTransaction>> {
+    let rows = sqlx::query_as::<_, StoredTransaction>(&format!(
+        r#"
+        SELECT {}
+        FROM transactions
+        WHERE fraud_label IS NOT NULL
+        ORDER BY timestamp DESC
+        LIMIT $1
+        "#,
+        STORED_TRANSACTION_COLUMNS
+    ))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Fetch scored transactions oldest first, resuming after `after`
+/// (timestamp, transaction_id) so large histories can be paged through.
+pub async fn fetch_decided_transactions(
+    pool: &PgPool,
+    after: Option<(DateTime<Utc>, String)>,
+    limit: i64,
+) -> Result<Vec<StoredTransaction>> {
+    let (after_timestamp, after_id) = match after {
+        Some((timestamp, id)) => (Some(timestamp), Some(id)),
+        None => (None, None),
+    };
+
+    let rows = sqlx::query_as::<_, StoredTransaction>(&format!(
+        r#"
+        SELECT {}
+        FROM transactions
+        WHERE decision IS NOT NULL
+        AND timestamp IS NOT NULL
+        AND ($1::timestamptz IS NULL OR (timestamp, transaction_id) > ($1, $2))
+        ORDER BY timestamp, transaction_id
+        LIMIT $3
+        "#,
+        STORED_TRANSACTION_COLUMNS
+    ))
+    .bind(after_timestamp)
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Most recent transactions whose metadata contains `metadata` (JSONB `@>`),
+/// optionally for one user
+pub async fn search_transactions(
+    pool: &PgPool,
+    user_id: Option<&str>,
+    metadata: Option<&serde_json::Value>,
+    limit: i64,
+) -> Result<Vec<StoredTransaction>> {
+    let rows = sqlx::query_as::<_, StoredTransaction>(&format!(
+        r#"
+        SELECT {}
+        FROM transactions
+        WHERE ($1::text IS NULL OR user_id = $1)
+        AND ($2::jsonb IS NULL OR metadata @> $2)
+        ORDER BY timestamp DESC
+        LIMIT $3
+        "#,
+        STORED_TRANSACTION_COLUMNS
+    ))
+    .bind(user_id)
+    .bind(metadata)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// One stored scoring event for a transaction
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
+pub struct AnalysisRecord {
+    pub id: i64,
+    pub decision: String,
+    pub risk_score: Option<f64>,
+    pub confidence: Option<f64>,
+    pub model_version: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// The full `AnalysisResult` as returned at the time
+    pub result: serde_json::Value,
+}
+
+/// Every stored analysis of a transaction, oldest first
+pub async fn fetch_analysis_history(pool: &PgPool, transaction_id: &str) -> Result<Vec<AnalysisRecord>> {
+    let rows = sqlx::query_as::<_, AnalysisRecord>(
+        r#"
+        SELECT id, decision, risk_score, confidence, model_version,
+               COALESCE(created_at, NOW()) as created_at, result
+        FROM analysis_results
+        WHERE transaction_id = $1
+        ORDER BY created_at, id
+        "#
+    )
+    .bind(transaction_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Stored results (`analysis_results.result`) of analyses since `since`,
+/// most recent first
+pub async fn fetch_recent_results(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<serde_json::Value>> {
+    let rows = sqlx::query_scalar::<_, serde_json::Value>(
+        r#"
+        SELECT result
+        FROM analysis_results
+        WHERE created_at >= $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#
+    )
+    .bind(since)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+/// Fields of a stored transaction that may change after it was scored, as
+/// settlement or investigation fills them in. `None` leaves a field as is
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct TransactionPatch {
+    pub fraud_label: Option<bool>,
+    pub location: Option<Location>,
+    pub merchant_category: Option<String>,
+    pub payment_method: Option<String>,
+    pub device_fingerprint: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// Apply a patch to a stored transaction, returning the updated row or
+/// `None` when the transaction isn't stored
+pub async fn update_transaction(
+    pool: &PgPool,
+    transaction_id: &str,
+    patch: &TransactionPatch,
+) -> Result<Option<StoredTransaction>> {
+    read_only::ensure_writable("updating transactions")?;
+    let row = sqlx::query_as::<_, StoredTransaction>(&format!(
+        r#"
+        UPDATE transactions
+        SET fraud_label = COALESCE($2, fraud_label),
+            location = COALESCE($3, location),
+            merchant_category = COALESCE($4, merchant_category),
+            payment_method = COALESCE($5, payment_method),
+            device_fingerprint = COALESCE($6, device_fingerprint),
+            metadata = COALESCE($7, metadata)
+        WHERE transaction_id = $1
+        RETURNING {}
+        "#,
+        STORED_TRANSACTION_COLUMNS
+    ))
+    .bind(transaction_id)
+    .bind(patch.fraud_label)
+    .bind(patch.location.as_ref().map(serde_json::to_value).transpose()?)
+    .bind(&patch.merchant_category)
+    .bind(&patch.payment_method)
+    .bind(&patch.device_fingerprint)
+    .bind(&patch.metadata)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
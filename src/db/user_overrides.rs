@@ -0,0 +1,146 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+use crate::{db::read_only, models::transaction::Decision};
+
+/// Per-user special handling: a decision forced regardless of score, or a
+/// multiplier on the aggregate score
+#[derive(sqlx::FromRow, Debug, Clone, serde::Serialize)]
+pub struct UserOverride {
+    pub user_id: String,
+    /// "APPROVE" / "CHALLENGE" / "BLOCK"
+    pub action: Option<String>,
+    pub risk_multiplier: Option<f64>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl UserOverride {
+    pub fn forced_decision(&self) -> Option<Decision> {
+        self.action.as_deref().and_then(|action| action.parse().ok())
+    }
+}
+
+/// Every override keyed by user, reloaded at most once per `ttl` or after
+/// `invalidate`.
+///
+/// Overrides are few and rarely change, a query per analysis isn't worth
+/// it. When a reload fails the previous snapshot keeps serving.
+pub struct UserOverrideCache {
+    ttl: Duration,
+    cache: RwLock<Option<(Instant, HashMap<String, UserOverride>)>>,
+}
+
+impl UserOverrideCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cache: RwLock::new(None),
+        }
+    }
+
+    /// Drop the snapshot, the next lookup reloads. Call after editing overrides
+    pub async fn invalidate(&self) {
+        *self.cache.write().await = None;
+    }
+
+    /// The user's override, reloading the snapshot if stale. Errors only
+    /// when there is no snapshot to fall back on
+    pub async fn find(&self, pool: &PgPool, user_id: &str) -> Result<Option<UserOverride>> {
+        {
+            let cache = self.cache.read().await;
+            if let Some((loaded_at, entries)) = cache.as_ref() && loaded_at.elapsed() < self.ttl {
+                return Ok(entries.get(user_id).cloned());
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+        // Another request may have reloaded while we waited for the lock
+        if let Some((loaded_at, entries)) = cache.as_ref() && loaded_at.elapsed() < self.ttl {
+            return Ok(entries.get(user_id).cloned());
+        }
+
+        match list_overrides(pool).await {
+            Ok(entries) => {
+                let entries: HashMap<String, UserOverride> = entries
+                    .into_iter()
+                    .map(|entry| (entry.user_id.clone(), entry))
+                    .collect();
+                tracing::debug!("👤 Reloaded {} user overrides", entries.len());
+                let found = entries.get(user_id).cloned();
+                *cache = Some((Instant::now(), entries));
+                Ok(found)
+            }
+            Err(e) => match cache.as_mut() {
+                Some((loaded_at, entries)) => {
+                    tracing::warn!("Failed to reload user overrides, keeping the previous ones: {}", e);
+                    // Retried after another ttl rather than on every request
+                    *loaded_at = Instant::now();
+                    Ok(entries.get(user_id).cloned())
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
+
+pub async fn list_overrides(pool: &PgPool) -> Result<Vec<UserOverride>> {
+    let entries = sqlx::query_as::<_, UserOverride>(
+        r#"
+        SELECT user_id, action, risk_multiplier, reason, created_at
+        FROM user_overrides
+        ORDER BY user_id
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(entries)
+}
+
+/// Set a user's override, replacing any previous one
+pub async fn set_override(
+    pool: &PgPool,
+    user_id: &str,
+    action: Option<Decision>,
+    risk_multiplier: Option<f64>,
+    reason: Option<&str>,
+) -> Result<UserOverride> {
+    read_only::ensure_writable("editing user overrides")?;
+    let entry = sqlx::query_as::<_, UserOverride>(
+        r#"
+        INSERT INTO user_overrides (user_id, action, risk_multiplier, reason)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id) DO UPDATE
+        SET action = EXCLUDED.action,
+            risk_multiplier = EXCLUDED.risk_multiplier,
+            reason = EXCLUDED.reason
+        RETURNING user_id, action, risk_multiplier, reason, created_at
+        "#
+    )
+    .bind(user_id)
+    .bind(action.map(|decision| decision.as_str()))
+    .bind(risk_multiplier)
+    .bind(reason)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(entry)
+}
+
+/// Remove a user's override, returning whether there was one
+pub async fn remove_override(pool: &PgPool, user_id: &str) -> Result<bool> {
+    read_only::ensure_writable("editing user overrides")?;
+    let result = sqlx::query("DELETE FROM user_overrides WHERE user_id = $1")
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -1,12 +1,52 @@
 use sqlx::PgPool;
 use anyhow::Result;
 
-/// Search for similar transactions using pgvector
+use crate::config::{self, ConfigError, ConfigSource};
+use crate::embedding::{DistanceMetric, EmbeddingPrecision};
+
+/// Extra weight for recent matches in the hybrid search
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RecencyBoost {
+    /// Weight of the recency term in `combined_score`, 0 ranks by relevance only
+    pub weight: f64,
+    /// Age at which a match's recency term has halved
+    pub half_life_secs: f64,
+}
+
+impl Default for RecencyBoost {
+    fn default() -> Self {
+        Self {
+            weight: 0.0,
+            half_life_secs: 7.0 * 86400.0,
+        }
+    }
+}
+
+impl RecencyBoost {
+    /// Read `HYBRID_SEARCH_RECENCY_WEIGHT` and `HYBRID_SEARCH_RECENCY_HALF_LIFE_SECS`
+    pub fn from_source(source: &dyn ConfigSource) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        Ok(Self {
+            weight: config::parsed(source, "HYBRID_SEARCH_RECENCY_WEIGHT", defaults.weight)?,
+            half_life_secs: config::parsed(
+                source,
+                "HYBRID_SEARCH_RECENCY_HALF_LIFE_SECS",
+                defaults.half_life_secs,
+            )?,
+        })
+    }
+}
+
+/// Search for similar transactions using pgvector, optionally leaving out
+/// one transaction (the one being re-scored)
 pub async fn find_similar_transactions(
     pool: &PgPool,
     embedding: &[f32],
     user_id: &str,
     limit: i32,
+    exclude_transaction_id: Option<&str>,
+    precision: EmbeddingPrecision,
+    metric: DistanceMetric,
 ) -> Result<Vec<SimilarTransaction>> {
     let embedding_str = format!(
         "[{}]",
@@ -16,36 +56,80 @@ pub async fn find_similar_transactions(
             .join(",")
     );
     
-    let rows = sqlx::query_as::<_, SimilarTransaction>(
+    let rows = sqlx::query_as::<_, SimilarTransaction>(&format!(
         r#"
         SELECT 
             transaction_id,
             merchant,
             amount::float8 as amount,
             fraud_label,
-            (1 - (transaction_embedding <=> $1::vector)) as similarity
+            {similarity} as similarity
         FROM transactions
         WHERE user_id = $2
         AND transaction_embedding IS NOT NULL
-        ORDER BY transaction_embedding <=> $1::vector
+        AND ($4::text IS NULL OR transaction_id != $4)
+        ORDER BY transaction_embedding {distance} $1::{vector}
         LIMIT $3
-        "#
-    )
+        "#,
+        vector = precision.sql_type(),
+        similarity = metric.similarity_sql("transaction_embedding", "$1", precision),
+        distance = metric.operator()
+    ))
     .bind(embedding_str)
     .bind(user_id)
     .bind(limit)
+    .bind(exclude_transaction_id)
     .fetch_all(pool)
     .await?;
     
     Ok(rows)
 }
 
-/// Hybrid search: Combine pg_text full-text search + pgvector similarity
+/// Search for similar transactions across all users, for embedding debugging
+pub async fn find_similar_transactions_global(
+    pool: &PgPool,
+    embedding: &[f32],
+    limit: i32,
+    precision: EmbeddingPrecision,
+    metric: DistanceMetric,
+) -> Result<Vec<SimilarTransaction>> {
+    let embedding_str = crate::embedding::embedding_to_pgvector(embedding);
+    
+    let rows = sqlx::query_as::<_, SimilarTransaction>(&format!(
+        r#"
+        SELECT 
+            transaction_id,
+            merchant,
+            amount::float8 as amount,
+            fraud_label,
+            {similarity} as similarity
+        FROM transactions
+        WHERE transaction_embedding IS NOT NULL
+        ORDER BY transaction_embedding {distance} $1::{vector}
+        LIMIT $2
+        "#,
+        vector = precision.sql_type(),
+        similarity = metric.similarity_sql("transaction_embedding", "$1", precision),
+        distance = metric.operator()
+    ))
+    .bind(embedding_str)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    
+    Ok(rows)
+}
+
+/// Hybrid search: Combine pg_text full-text search + pgvector similarity,
+/// plus a recency term decaying with the match's age when `recency` has a weight
 pub async fn hybrid_search_transactions(
     pool: &PgPool,
     text_query: &str,
     embedding: &[f32],
     limit: i32,
+    precision: EmbeddingPrecision,
+    metric: DistanceMetric,
+    recency: &RecencyBoost,
 ) -> Result<Vec<HybridSearchResult>> {
     let embedding_str = format!(
         "[{}]",
@@ -55,7 +139,7 @@ pub async fn hybrid_search_transactions(
             .join(",")
     );
     
-    let rows = sqlx::query_as::<_, HybridSearchResult>(
+    let rows = sqlx::query_as::<_, HybridSearchResult>(&format!(
         r#"
         WITH text_matches AS (
             SELECT 
@@ -67,10 +151,10 @@ pub async fn hybrid_search_transactions(
         vector_matches AS (
             SELECT 
                 transaction_id,
-                (1 - (transaction_embedding <=> $2::vector)) as vector_score
+                {similarity} as vector_score
             FROM transactions
             WHERE transaction_embedding IS NOT NULL
-            ORDER BY transaction_embedding <=> $2::vector
+            ORDER BY transaction_embedding {distance} $2::{vector}
             LIMIT 50
         )
         SELECT 
@@ -78,21 +162,34 @@ pub async fn hybrid_search_transactions(
             t.merchant,
             t.amount::float8 as amount,
             t.fraud_label,
+            t.timestamp,
             (COALESCE(tm.text_score, 0) * 0.3 + 
-             COALESCE(vm.vector_score, 0) * 0.7) as combined_score,
+             COALESCE(vm.vector_score, 0) * 0.7 +
+             $4 * r.recency_score) as combined_score,
             COALESCE(tm.text_score, 0) as text_score,
-            COALESCE(vm.vector_score, 0) as vector_score
+            COALESCE(vm.vector_score, 0) as vector_score,
+            r.recency_score
         FROM transactions t
         LEFT JOIN text_matches tm USING (transaction_id)
         LEFT JOIN vector_matches vm USING (transaction_id)
+        CROSS JOIN LATERAL (
+            SELECT CASE WHEN $5 > 0
+                THEN POWER(0.5, GREATEST(EXTRACT(EPOCH FROM (NOW() - t.timestamp)), 0) / $5)
+                ELSE 1 END::float8 as recency_score
+        ) r
         WHERE tm.transaction_id IS NOT NULL OR vm.transaction_id IS NOT NULL
         ORDER BY combined_score DESC
         LIMIT $3
-        "#
-    )
+        "#,
+        vector = precision.sql_type(),
+        similarity = metric.similarity_sql("transaction_embedding", "$2", precision),
+        distance = metric.operator()
+    ))
     .bind(text_query)
     .bind(embedding_str)
     .bind(limit)
+    .bind(recency.weight)
+    .bind(recency.half_life_secs)
     .fetch_all(pool)
     .await?;
     
@@ -137,7 +234,7 @@ pub async fn find_similar_merchants(
 
 // Result types - using f64 instead of Decimal
 
-#[derive(sqlx::FromRow, Debug)]
+#[derive(sqlx::FromRow, Debug, serde::Serialize)]
 pub struct SimilarTransaction {
     pub transaction_id: String,
     pub merchant: String,
@@ -152,9 +249,12 @@ pub struct HybridSearchResult {
     pub merchant: String,
     pub amount: f64,
     pub fraud_label: Option<bool>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
     pub combined_score: f64,
     pub text_score: f64,
     pub vector_score: f64,
+    /// 1 for a match made just now, halving every half-life
+    pub recency_score: f64,
 }
 
 #[derive(sqlx::FromRow, Debug)]
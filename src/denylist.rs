@@ -0,0 +1,53 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    db::denylist::{self, DenylistEntry},
+    error::ApiError,
+};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DenylistRequest {
+    /// Why the merchant is blocked, shown in the decision's reasoning
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// GET /api/admin/merchant-denylist - every denylisted merchant
+pub async fn list_denylist(State(state): State<AppState>) -> Result<Json<Vec<DenylistEntry>>, ApiError> {
+    Ok(Json(denylist::list_entries(&state.pool).await?))
+}
+
+/// PUT /api/admin/merchant-denylist/{merchant} - block a merchant outright
+pub async fn add_to_denylist(
+    State(state): State<AppState>,
+    Path(merchant): Path<String>,
+    Json(request): Json<DenylistRequest>,
+) -> Result<Json<DenylistEntry>, ApiError> {
+    if merchant.trim().is_empty() {
+        return Err(ApiError::BadRequest("merchant must not be empty".to_string()));
+    }
+
+    let entry = denylist::add_entry(&state.pool, &merchant, request.reason.as_deref()).await?;
+    tracing::warn!("🚫 Merchant denylisted: {} ({:?})", entry.merchant_name, entry.reason);
+
+    Ok(Json(entry))
+}
+
+/// DELETE /api/admin/merchant-denylist/{merchant} - take a merchant off the list
+pub async fn remove_from_denylist(
+    State(state): State<AppState>,
+    Path(merchant): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !denylist::remove_entry(&state.pool, &merchant).await? {
+        return Err(ApiError::NotFound(format!("Merchant '{}' is not denylisted", merchant)));
+    }
+    tracing::warn!("Merchant removed from denylist: {}", merchant);
+
+    Ok(StatusCode::NO_CONTENT)
+}
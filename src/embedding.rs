@@ -1,4 +1,4 @@
-use std::{collections::HashMap};
+use std::{collections::HashMap, future::Future, path::PathBuf, pin::Pin, sync::Arc};
 
 use axum::{Json, extract::State, response::IntoResponse};
 use candle_core::{Device, Tensor, safetensors};
@@ -7,7 +7,12 @@ use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use tokenizers::Tokenizer;
 
-use crate::AppState;
+use crate::{
+    AppState,
+    config::{self, Config, ConfigSource},
+    db::vector_search::{SimilarTransaction, find_similar_transactions_global},
+    error::ApiError,
+};
 
 #[derive(Deserialize)]
 pub struct EmbeddingRequest {
@@ -25,44 +30,244 @@ struct ErrorResponse {
     error: String,
 }
 
+#[derive(Deserialize)]
+pub struct EmbeddingDebugRequest {
+    text: String,
+    /// Number of nearest neighbors to return
+    k: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct EmbeddingDebugResponse {
+    embedding: Vec<f32>,
+    dimension: usize,
+    neighbors: Vec<SimilarTransaction>,
+    /// Share of the returned neighbors labeled as fraud
+    fraud_share: f64,
+}
+
+
+/// Where and how to load the embedding model
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingModelConfig {
+    /// Directory holding the tokenizer and weights
+    pub model_path: PathBuf,
+    /// Model name, for logs
+    pub model_name: String,
+    /// Tokenizer file inside `model_path`, `tokenizer.json` or `tokenizer.model`
+    pub tokenizer_file: String,
+    /// Safetensors weights file inside `model_path`
+    pub weights_file: String,
+}
+
+impl Default for EmbeddingModelConfig {
+    fn default() -> Self {
+        Self {
+            // note: embeddgemma with double 'd'
+            model_path: PathBuf::from("src/embeddgemma"),
+            model_name: "embeddinggemma".to_string(),
+            tokenizer_file: "tokenizer.json".to_string(),
+            weights_file: "model.safetensors".to_string(),
+        }
+    }
+}
+
+impl EmbeddingModelConfig {
+    /// Read `EMBEDDING_MODEL_PATH`, `EMBEDDING_MODEL_NAME`,
+    /// `EMBEDDING_TOKENIZER_FILE` and `EMBEDDING_WEIGHTS_FILE`
+    pub fn from_source(source: &dyn ConfigSource) -> Self {
+        let defaults = Self::default();
+        Self {
+            model_path: source
+                .get("EMBEDDING_MODEL_PATH")
+                .map(PathBuf::from)
+                .unwrap_or(defaults.model_path),
+            model_name: config::string(source, "EMBEDDING_MODEL_NAME", defaults.model_name),
+            tokenizer_file: config::string(source, "EMBEDDING_TOKENIZER_FILE", defaults.tokenizer_file),
+            weights_file: config::string(source, "EMBEDDING_WEIGHTS_FILE", defaults.weights_file),
+        }
+    }
+}
+
+/// Task prefixes prepended before embedding, for models trained for
+/// asymmetric retrieval (e.g. `search_query: ` / `search_document: `).
+///
+/// Empty by default. Stored embeddings are computed with the document
+/// prefix, so changing either prefix requires re-embedding stored
+/// transactions or lookups will compare vectors from different spaces.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmbeddingPrefixes {
+    /// Prepended to live lookups (e.g. the pattern agent's similarity search)
+    pub query: String,
+    /// Prepended to text whose embedding is stored
+    pub document: String,
+}
+
+impl EmbeddingPrefixes {
+    /// Read `EMBEDDING_QUERY_PREFIX` and `EMBEDDING_DOCUMENT_PREFIX`
+    pub fn from_source(source: &dyn ConfigSource) -> Self {
+        Self {
+            query: config::string(source, "EMBEDDING_QUERY_PREFIX", String::new()),
+            document: config::string(source, "EMBEDDING_DOCUMENT_PREFIX", String::new()),
+        }
+    }
+
+    /// Query and document embeddings of the same text are identical
+    pub fn is_symmetric(&self) -> bool {
+        self.query == self.document
+    }
+}
+
+/// Storage type of `transactions.transaction_embedding`.
+///
+/// `F16` stores pgvector `halfvec`, half the size of `vector` with a small
+/// loss of recall (roughly three significant digits per component, which
+/// rarely changes nearest-neighbour order for unit vectors). Switching
+/// requires migrating the column and its index, see `sql/halfvec.sql`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingPrecision {
+    #[default]
+    F32,
+    F16,
+}
+
+impl EmbeddingPrecision {
+    /// pgvector type to cast query parameters to, must match the column
+    pub fn sql_type(self) -> &'static str {
+        match self {
+            EmbeddingPrecision::F32 => "vector",
+            EmbeddingPrecision::F16 => "halfvec",
+        }
+    }
+}
 
-//load gemma model
-pub async fn load_model() -> anyhow::Result<(HashMap<String, Tensor>, Tokenizer, Device)> {
+impl std::str::FromStr for EmbeddingPrecision {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "f32" | "vector" => Ok(EmbeddingPrecision::F32),
+            "f16" | "halfvec" => Ok(EmbeddingPrecision::F16),
+            other => Err(format!("Unknown embedding precision '{}', expected f32 or f16", other)),
+        }
+    }
+}
+
+/// How transaction embeddings are compared, in SQL and by the vector index.
+///
+/// `InnerProduct` lets magnitude count towards similarity, paired with
+/// `EMBEDDING_NORMALIZE=false`. For unit vectors it ranks like `Cosine`.
+/// Merchant embeddings are always compared by cosine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    #[default]
+    Cosine,
+    InnerProduct,
+}
+
+impl DistanceMetric {
+    /// pgvector distance operator, smaller is closer
+    pub fn operator(self) -> &'static str {
+        match self {
+            DistanceMetric::Cosine => "<=>",
+            DistanceMetric::InnerProduct => "<#>",
+        }
+    }
+
+    /// SQL similarity between `column` and the bound `param`, larger is
+    /// closer: cosine similarity, or the raw inner product (`<#>` returns
+    /// its negation)
+    pub fn similarity_sql(self, column: &str, param: &str, precision: EmbeddingPrecision) -> String {
+        match self {
+            DistanceMetric::Cosine => format!("(1 - ({} <=> {}::{}))", column, param, precision.sql_type()),
+            DistanceMetric::InnerProduct => format!("(-({} <#> {}::{}))", column, param, precision.sql_type()),
+        }
+    }
+
+    /// Index operator class for a column of `column_type` (vector or halfvec)
+    pub fn opclass(self, column_type: &str) -> String {
+        let ops = match self {
+            DistanceMetric::Cosine => "cosine_ops",
+            DistanceMetric::InnerProduct => "ip_ops",
+        };
+        format!("{}_{}", column_type, ops)
+    }
+}
+
+impl std::str::FromStr for DistanceMetric {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "cosine" => Ok(DistanceMetric::Cosine),
+            "inner_product" | "ip" => Ok(DistanceMetric::InnerProduct),
+            other => Err(format!("Unknown distance metric '{}', expected cosine or inner_product", other)),
+        }
+    }
+}
+
+//load gemma model, blocking: it reads the weights from disk
+pub fn load_model(
+    config: &EmbeddingModelConfig,
+) -> anyhow::Result<(HashMap<String, Tensor>, Tokenizer, Device)> {
     //declare device to use cpu
     let device = Device::Cpu;
 
-    // Load model and tokenizers from local directory (note: embeddgemma with double 'd')
-    let model_path = std::path::Path::new("src/embeddgemma");
-    let tokenizer_file = model_path.join("tokenizer.json");
+    let model_path = config.model_path.as_path();
+    tracing::info!(
+        "Loading embedding model '{}' from {}",
+        config.model_name,
+        model_path.display()
+    );
+
+    if !model_path.is_dir() {
+        anyhow::bail!(
+            "Embedding model directory not found: {} (set EMBEDDING_MODEL_PATH)",
+            model_path.display()
+        );
+    }
+
+    let tokenizer_file = model_path.join(&config.tokenizer_file);
 
     // Check if tokenizer exists
     if !tokenizer_file.exists() {
-        anyhow::bail!("Tokenizer file not found: {:?}", tokenizer_file);
+        anyhow::bail!("Tokenizer file not found: {}", tokenizer_file.display());
     }
 
     //load tokenizers
     let tokenizer = match Tokenizer::from_file(&tokenizer_file) {
         std::result::Result::Ok(tok) => tok,
         Err(e) => {
-            eprintln!("Failed to load tokenizer.json: {}", e);
-            eprintln!("Trying tokenizer.model instead...");
-
-            // Try the .model file as fallback
-            let model_tokenizer_file = model_path.join("tokenizer.model");
-            if model_tokenizer_file.exists() {
-                // For SentencePiece tokenizers
-                Tokenizer::from_file(&model_tokenizer_file)
-                    .map_err(|e| anyhow::anyhow!("Failed to load tokenizer.model: {}", e))?
+            eprintln!("Failed to load {}: {}", tokenizer_file.display(), e);
+
+            // Try the other tokenizer format as fallback
+            let fallback_name = if config.tokenizer_file == "tokenizer.model" {
+                "tokenizer.json"
             } else {
-                anyhow::bail!("Could not load any tokenizer file: {}", e);
+                "tokenizer.model"
+            };
+            let fallback_file = model_path.join(fallback_name);
+            eprintln!("Trying {} instead...", fallback_file.display());
+            if fallback_file.exists() {
+                Tokenizer::from_file(&fallback_file).map_err(|e| {
+                    anyhow::anyhow!("Failed to load {}: {}", fallback_file.display(), e)
+                })?
+            } else {
+                anyhow::bail!(
+                    "Could not load any tokenizer file in {}: {}",
+                    model_path.display(),
+                    e
+                );
             }
         }
     };
     //load safetensors
     //load a file
-    let model_file = model_path.join("model.safetensors");
+    let model_file = model_path.join(&config.weights_file);
     if !model_file.exists() {
-        anyhow::bail!("Model file not found: {:?}", model_file);
+        anyhow::bail!("Model file not found: {}", model_file.display());
     }
 
     //load safe tensors
@@ -101,30 +306,329 @@ pub async fn generate_embedding(
     }
 }
 
-//common function to generate embedding using gemma model
+//embed a text and show where it lands among all stored transactions
+pub async fn debug_embedding(
+    State(state): State<AppState>,
+    Json(request): Json<EmbeddingDebugRequest>,
+) -> impl IntoResponse {
+    let embedding = match embed_query(&state, &request.text).await {
+        Ok(embedding) => embedding,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Embedding generation failed: {}", e),
+                }),
+            )
+                .into_response();
+        }
+    };
+
+    let k = request.k.unwrap_or(10).clamp(1, 100);
+    match find_similar_transactions_global(
+        &state.read_pool,
+        &embedding,
+        k,
+        state.config.embedding_precision,
+        state.config.embedding_distance_metric,
+    )
+    .await
+    {
+        Ok(neighbors) => {
+            let fraud_share = if neighbors.is_empty() {
+                0.0
+            } else {
+                neighbors.iter().filter(|n| n.fraud_label == Some(true)).count() as f64
+                    / neighbors.len() as f64
+            };
+            let dimension = embedding.len();
+            (
+                StatusCode::OK,
+                Json(EmbeddingDebugResponse {
+                    embedding,
+                    dimension,
+                    neighbors,
+                    fraud_share,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(ErrorResponse {
+                error: format!("Similarity search failed: {}", e),
+            }),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Serialize)]
+pub struct ModelReloadResponse {
+    pub previous_model: String,
+    pub model: String,
+    pub dimension: usize,
+}
+
+/// Dimension of the stored transaction embeddings, `None` when the column
+/// doesn't declare one
+async fn embedding_column_dimension(pool: &sqlx::PgPool) -> anyhow::Result<Option<usize>> {
+    // pgvector keeps the declared dimension in the type modifier
+    let typmod = sqlx::query_scalar::<_, i32>(
+        r#"
+        SELECT a.atttypmod
+        FROM pg_attribute a
+        WHERE a.attrelid = to_regclass('transactions')
+        AND a.attname = 'transaction_embedding'
+        AND NOT a.attisdropped
+        "#,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(typmod.filter(|dim| *dim > 0).map(|dim| dim as usize))
+}
+
+/// Held for the whole of a model reload
+static RELOAD_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// POST /api/admin/reload-model - load the model from the current
+/// configuration (`CONFIG_FILE` included) and swap it in without a restart.
+/// The old model keeps serving until the new one has loaded and produced
+/// embeddings of the stored dimension, and stays if it doesn't
+pub async fn reload_model(State(state): State<AppState>) -> Result<Json<ModelReloadResponse>, ApiError> {
+    // Two reloads at once would both load a model and race to swap it in
+    let _reloading = RELOAD_LOCK.lock().await;
+    let config = Config::load().map_err(|e| ApiError::BadRequest(format!("Invalid configuration: {}", e)))?;
+    // Stored embeddings were written in the running mode, a reload can't
+    // change it without re-embedding them
+    if config.embedding_normalize != state.config.embedding_normalize {
+        return Err(ApiError::BadRequest(
+            "EMBEDDING_NORMALIZE changed: re-embed stored transactions with --reembed-all and restart instead"
+                .to_string(),
+        ));
+    }
+    let model_config = config.embedding_model;
+    tracing::info!("🔄 Loading embedding model {} from {:?}", model_config.model_name, model_config.model_path);
+
+    let loading = model_config.clone();
+    let (tensors, tokenizer, device) = tokio::task::spawn_blocking(move || load_model(&loading))
+        .await
+        .map_err(anyhow::Error::from)?
+        .map_err(|e| ApiError::BadRequest(format!("Can't load model: {}", e)))?;
+    let embedder = CandleEmbedder::new(model_config.model_name.clone(), tensors, tokenizer, device)
+        .with_normalize(config.embedding_normalize);
+
+    let probe = embedder
+        .embed("model reload check")
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("New model failed to embed: {}", e)))?;
+    if probe.iter().any(|value| !value.is_finite()) {
+        return Err(ApiError::BadRequest("New model produced non-finite embeddings".to_string()));
+    }
+    if let Some(dimension) = embedding_column_dimension(&state.pool).await?
+        && probe.len() != dimension
+    {
+        return Err(ApiError::BadRequest(format!(
+            "New model produces {}-dimensional embeddings, the database stores {}",
+            probe.len(),
+            dimension
+        )));
+    }
+
+    let previous = state.embedder.swap(Arc::new(Box::new(embedder) as Box<dyn EmbeddingProvider>));
+    tracing::warn!("🔄 Embedding model swapped: {} -> {}", previous.name(), model_config.model_name);
+
+    Ok(Json(ModelReloadResponse {
+        previous_model: previous.name().to_string(),
+        model: model_config.model_name,
+        dimension: probe.len(),
+    }))
+}
+
+/// Embed text used to search stored embeddings
+pub async fn embed_query(state: &AppState, text: &str) -> Result<Vec<f32>, String> {
+    let text = format!("{}{}", state.config.embedding_prefixes.query, text);
+    embed_limited(state, &text).await
+}
+
+/// Embed text whose embedding will be stored and searched against
+pub async fn embed_document(state: &AppState, text: &str) -> Result<Vec<f32>, String> {
+    let text = format!("{}{}", state.config.embedding_prefixes.document, text);
+    embed_limited(state, &text).await
+}
+
+//common function to generate embedding with the configured provider
 pub async fn generate_embedding_internal(
     state: &AppState,
     text: String,
+) -> Result<Vec<f32>, String> {
+    embed_limited(state, &text).await
+}
+
+/// Embed once one of the `EMBEDDING_MAX_CONCURRENCY` permits is free, so a
+/// burst of analyses queues instead of oversubscribing the cores. The
+/// embedding runs in its own task that keeps the permit until it's done,
+/// also when the caller gives up on it at a deadline
+async fn embed_limited(state: &AppState, text: &str) -> Result<Vec<f32>, String> {
+    let permit = state
+        .embedding_permits
+        .clone()
+        .acquire_owned()
+        .await
+        .map_err(|e| format!("Embedding limiter closed: {}", e))?;
+    let embedder = state.embedder.load_full();
+    let text = text.to_string();
+    tokio::spawn(async move {
+        let _permit = permit;
+        embedder.embed(&text).await
+    })
+    .await
+    .map_err(|e| format!("Embedding task failed: {}", e))?
+}
+
+/// Boxed so providers can live behind `Arc<dyn EmbeddingProvider>`
+pub type EmbeddingFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<f32>, String>> + Send + 'a>>;
+
+/// Anything that turns text into an embedding, unit-length unless
+/// `EMBEDDING_NORMALIZE=false`: the local candle model by default, or e.g. a
+/// remote embedding API. Prefixes are applied by
+/// the caller, providers embed the text as given.
+pub trait EmbeddingProvider: Send + Sync {
+    fn embed<'a>(&'a self, text: &'a str) -> EmbeddingFuture<'a>;
+
+    /// Shown in logs
+    fn name(&self) -> &str;
+}
+
+/// The local gemma model loaded by `load_model`, run on candle
+pub struct CandleEmbedder {
+    name: String,
+    model: Arc<CandleModel>,
+    normalize: bool,
+}
+
+/// Shared with the blocking tasks that run the forward pass
+struct CandleModel {
+    tensors: HashMap<String, Tensor>,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl CandleEmbedder {
+    pub fn new(name: String, tensors: HashMap<String, Tensor>, tokenizer: Tokenizer, device: Device) -> Self {
+        Self {
+            name,
+            model: Arc::new(CandleModel {
+                tensors,
+                tokenizer,
+                device,
+            }),
+            normalize: true,
+        }
+    }
+
+    /// `false` returns the mean-pooled embeddings with their magnitude,
+    /// for inner-product search
+    pub fn with_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+}
+
+impl EmbeddingProvider for CandleEmbedder {
+    fn embed<'a>(&'a self, text: &'a str) -> EmbeddingFuture<'a> {
+        let model = self.model.clone();
+        let normalize = self.normalize;
+        let text = text.to_string();
+        Box::pin(async move {
+            // The forward pass is synchronous CPU work, on the runtime's
+            // threads it would stall every other request and couldn't be
+            // given up on at a deadline
+            tokio::task::spawn_blocking(move || {
+                if normalize {
+                    embed_with_model(&model.tensors, &model.tokenizer, &model.device, text)
+                } else {
+                    embed_with_model_raw(&model.tensors, &model.tokenizer, &model.device, text)
+                }
+            })
+            .await
+            .map_err(|e| format!("Embedding task failed: {}", e))?
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Embed `text` with an already loaded model, without the rest of `AppState`
+pub fn embed_with_model(
+    tensors: &HashMap<String, Tensor>,
+    tokenizer: &Tokenizer,
+    device: &Device,
+    text: String,
+) -> Result<Vec<f32>, String> {
+    let embedding_vec = embed_with_model_raw(tensors, tokenizer, device, text)?;
+
+    // Normalize to unit vector (important for cosine similarity!)
+    let length: f32 = embedding_vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length == 0.0 {
+        return Err(format!("Embedding has degenerate magnitude ({}), cannot normalize", length));
+    }
+    let normalized: Vec<f32> = embedding_vec.iter().map(|x| x / length).collect();
+
+    Ok(normalized)
+}
+
+/// A tokenizer from a different model than the weights produces ids past
+/// the end of the embedding matrix, which index_select reports opaquely
+fn check_token_ids(tokens: &[u32], vocab_size: usize) -> Result<(), String> {
+    match tokens.iter().find(|&&id| id as usize >= vocab_size) {
+        Some(&token_id) => Err(format!(
+            "Tokenizer/model vocab mismatch: token id {} is out of range for an embedding matrix of {} rows, \
+             check that the tokenizer and weights come from the same model",
+            token_id, vocab_size
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Mean-pooled embedding of `text` before normalization, its magnitude intact
+pub fn embed_with_model_raw(
+    tensors: &HashMap<String, Tensor>,
+    tokenizer: &Tokenizer,
+    device: &Device,
+    text: String,
 ) -> Result<Vec<f32>, String> {
     // Tokenize input text
-    let tokens = state
-        .tokenizer
+    let tokens = tokenizer
         .encode(text.clone(), true)
-        .map_err(|e| format!("Tokenization error: {}", e))?
+        .map_err(|e| format!("Tokenization error for {}: {}", describe_input(&text), e))?
         .get_ids()
         .to_vec();
 
+    // Nothing to pool, stacking an empty list would fail with a much less
+    // helpful message
+    if tokens.is_empty() {
+        return Err(format!("Tokenizer produced no tokens for {}", describe_input(&text)));
+    }
+
     // Get embedding weights
-    let embed_weights = state
-        .tensors
+    let embed_weights = tensors
         .get("embed_tokens.weight")
         .ok_or("embed_tokens.weight not found in model")?;
 
+    let vocab_size = embed_weights
+        .dim(0)
+        .map_err(|e| format!("embed_tokens.weight has no rows: {}", e))?;
+    check_token_ids(&tokens, vocab_size)?;
+
     // Create embeddings by indexing into embedding matrix
     let mut embeddings_vec = Vec::new();
 
     for &token_id in &tokens {
-        let token_tensor = candle_core::Tensor::new(&[token_id as u32], &state.device)
+        let token_tensor = candle_core::Tensor::new(&[token_id as u32], device)
             .map_err(|e| format!("Failed to create token tensor: {}", e))?;
 
         let token_embed = embed_weights
@@ -150,11 +654,28 @@ pub async fn generate_embedding_internal(
         .to_vec1::<f32>()
         .map_err(|e| format!("Tensor conversion error: {}", e))?;
 
-    // Normalize to unit vector (important for cosine similarity!)
     let length: f32 = embedding_vec.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let normalized: Vec<f32> = embedding_vec.iter().map(|x| x / length).collect();
+    if !length.is_finite() {
+        return Err(format!("Embedding has degenerate magnitude ({})", length));
+    }
 
-    Ok(normalized)
+    Ok(embedding_vec)
+}
+
+/// Length and first/last character of an input, enough to reproduce a
+/// failure without logging the (possibly personal) text itself
+fn describe_input(text: &str) -> String {
+    let chars = text.chars().count();
+    match (text.chars().next(), text.chars().last()) {
+        (Some(first), Some(last)) => format!(
+            "input of {} chars ({} bytes), starting {:?} ending {:?}",
+            chars,
+            text.len(),
+            first,
+            last
+        ),
+        _ => "empty input".to_string(),
+    }
 }
 
 pub fn embedding_to_pgvector(embedding: &[f32]) -> String {
@@ -165,4 +686,59 @@ pub fn embedding_to_pgvector(embedding: &[f32]) -> String {
             .collect::<Vec<_>>()
             .join(",")
     )
-}
\ No newline at end of file
+}
+
+/// Parse pgvector's text representation (`[0.1,0.2,...]`) back into floats
+pub fn pgvector_to_embedding(text: &str) -> Option<Vec<f32>> {
+    text.trim()
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().ok())
+        .collect()
+}
+
+/// Cosine similarity, 0.0 when either vector has no magnitude or lengths differ
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+/// Fold one more embedding into a running mean of `count` embeddings
+pub fn update_centroid(centroid: &[f32], count: i64, embedding: &[f32]) -> Vec<f32> {
+    if count <= 0 || centroid.len() != embedding.len() {
+        return embedding.to_vec();
+    }
+    let n = count as f32;
+    centroid
+        .iter()
+        .zip(embedding)
+        .map(|(c, e)| (c * n + e) / (n + 1.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_ids_within_the_vocab_pass() {
+        assert!(check_token_ids(&[0, 5, 9], 10).is_ok());
+    }
+
+    #[test]
+    fn token_id_past_the_vocab_is_a_mismatch() {
+        let err = check_token_ids(&[3, 10, 4], 10).unwrap_err();
+        assert!(err.contains("vocab mismatch"), "{}", err);
+        assert!(err.contains("token id 10"), "{}", err);
+        assert!(err.contains("10 rows"), "{}", err);
+    }
+}
@@ -0,0 +1,103 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::db::read_only::ReadOnlyError;
+
+/// Errors returned by the HTTP API.
+///
+/// Status-code contract:
+/// - `200` - the transaction was scored. The decision (APPROVE, CHALLENGE or
+///   BLOCK) is in the body; a BLOCK is a successful analysis, never an error.
+/// - `400` - the request is invalid and will never score, retrying won't help.
+/// - `401` - an admin endpoint was called without a valid admin token.
+/// - `403` - the operation writes and the service runs in read-only mode, or
+///   admin endpoints are disabled.
+/// - `404` - the requested resource (e.g. a user profile) does not exist.
+/// - `503` - a dependency (database, embedding model) is unavailable, the
+///   transaction could not be scored and the call is safe to retry.
+/// - `500` - an unexpected internal failure, the transaction was not scored.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+    kind: &'static str,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Unavailable(_) => "unavailable",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        if let Some(read_only) = err.downcast_ref::<ReadOnlyError>() {
+            return ApiError::Forbidden(read_only.to_string());
+        }
+
+        // Connection-level database failures are transient, anything else
+        // coming out of the agents is a bug or a schema problem.
+        match err.downcast_ref::<sqlx::Error>() {
+            Some(
+                sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+                | sqlx::Error::Io(_)
+                | sqlx::Error::Tls(_),
+            ) => ApiError::Unavailable(format!("Database unavailable: {}", err)),
+            // A write that got past the guard, refused by a read-only session
+            Some(sqlx::Error::Database(db_err)) if db_err.code().as_deref() == Some("25006") => {
+                ApiError::Forbidden(format!("Read-only mode: {}", db_err))
+            }
+            Some(_) => ApiError::Internal(format!("Database error: {}", err)),
+            None => ApiError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let body = ErrorBody {
+            error: self.to_string(),
+            kind: self.kind(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
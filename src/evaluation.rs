@@ -0,0 +1,540 @@
+use anyhow::Result;
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{collections::BTreeMap, time::Instant};
+
+use crate::{
+    AppState,
+    db::{fork::ForkManager, persistence, transactions},
+    error::ApiError,
+    models::transaction::{AgentWeights, Decision},
+};
+
+/// Confusion matrix and derived metrics at a single score cutoff
+#[derive(Debug, Serialize)]
+pub struct ThresholdPoint {
+    pub threshold: f64,
+    pub true_positives: u64,
+    pub false_positives: u64,
+    pub true_negatives: u64,
+    pub false_negatives: u64,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    pub false_positive_rate: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThresholdReport {
+    pub samples: usize,
+    pub fraud_samples: usize,
+    /// Area under the ROC curve traced by the requested thresholds
+    pub auc: f64,
+    pub points: Vec<ThresholdPoint>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ThresholdParams {
+    /// Comma-separated cutoffs, e.g. `0.3,0.4,0.5`
+    pub thresholds: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// Default cutoffs: 0.05 to 0.95 in steps of 0.05
+pub fn default_thresholds() -> Vec<f64> {
+    (1..20).map(|i| i as f64 * 0.05).collect()
+}
+
+/// Build the tradeoff from `(aggregate score, is_fraud)` samples.
+/// A sample is flagged at a threshold when its score is strictly above it,
+/// matching how the orchestrator turns scores into decisions.
+pub fn compute_tradeoff(samples: &[(f64, bool)], thresholds: &[f64]) -> ThresholdReport {
+    let mut points: Vec<ThresholdPoint> = thresholds
+        .iter()
+        .map(|&threshold| {
+            let (mut tp, mut fp, mut tn, mut fn_) = (0u64, 0u64, 0u64, 0u64);
+            for &(score, is_fraud) in samples {
+                match (score > threshold, is_fraud) {
+                    (true, true) => tp += 1,
+                    (true, false) => fp += 1,
+                    (false, false) => tn += 1,
+                    (false, true) => fn_ += 1,
+                }
+            }
+
+            let ratio = |num: u64, den: u64| if den > 0 { num as f64 / den as f64 } else { 0.0 };
+            let precision = ratio(tp, tp + fp);
+            let recall = ratio(tp, tp + fn_);
+            let f1 = if precision + recall > 0.0 {
+                2.0 * precision * recall / (precision + recall)
+            } else {
+                0.0
+            };
+
+            ThresholdPoint {
+                threshold,
+                true_positives: tp,
+                false_positives: fp,
+                true_negatives: tn,
+                false_negatives: fn_,
+                precision,
+                recall,
+                f1,
+                false_positive_rate: ratio(fp, fp + tn),
+            }
+        })
+        .collect();
+
+    points.sort_by(|a, b| a.threshold.total_cmp(&b.threshold));
+
+    // Trapezoidal area over (fpr, tpr), anchored at (1,1) and (0,0)
+    let mut roc: Vec<(f64, f64)> = vec![(1.0, 1.0)];
+    roc.extend(points.iter().map(|p| (p.false_positive_rate, p.recall)));
+    roc.push((0.0, 0.0));
+    let auc = roc
+        .windows(2)
+        .map(|w| (w[0].0 - w[1].0) * (w[0].1 + w[1].1) / 2.0)
+        .sum::<f64>();
+
+    ThresholdReport {
+        samples: samples.len(),
+        fraud_samples: samples.iter().filter(|(_, f)| *f).count(),
+        auc,
+        points,
+    }
+}
+
+/// Run labeled transactions through the ensemble and collect their aggregate scores
+pub async fn score_labeled_transactions(
+    pool: &PgPool,
+    state: &AppState,
+    limit: i64,
+) -> Result<Vec<(f64, bool)>> {
+    // One configuration for the whole run, even if it is reloaded meanwhile
+    let analyzer = state.analyzer.load_full();
+    let labeled = transactions::fetch_labeled_transactions(pool, limit).await?;
+
+    tracing::info!("📐 Scoring {} labeled transactions", labeled.len());
+
+    let mut samples = Vec::with_capacity(labeled.len());
+    for stored in &labeled {
+        let result = analyzer
+            .analyze(pool, state, &stored.to_transaction())
+            .await?;
+        samples.push((result.risk_score, stored.fraud_label.unwrap_or(false)));
+    }
+
+    Ok(samples)
+}
+
+/// GET /api/admin/roc - precision/recall tradeoff over labeled history
+pub async fn threshold_tradeoff(
+    State(state): State<AppState>,
+    Query(params): Query<ThresholdParams>,
+) -> Result<Json<ThresholdReport>, ApiError> {
+    let thresholds = match params.thresholds {
+        Some(raw) => raw
+            .split(',')
+            .map(|t| t.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ApiError::BadRequest(format!("Invalid thresholds: {}", e)))?,
+        None => default_thresholds(),
+    };
+
+    let samples =
+        score_labeled_transactions(&state.read_pool, &state, params.limit.unwrap_or(500)).await?;
+
+    Ok(Json(compute_tradeoff(&samples, &thresholds)))
+}
+
+/// Page size used when walking the decision history
+const REANALYSIS_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct ReanalysisRequest {
+    /// Maximum number of historical transactions to rescore
+    pub limit: Option<i64>,
+    /// Store the new results (appending to `analysis_results`) instead of a dry run
+    #[serde(default)]
+    pub persist: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecisionFlip {
+    pub transaction_id: String,
+    pub old_decision: Decision,
+    pub new_decision: Decision,
+    pub new_risk_score: f64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReanalysisReport {
+    pub processed: u64,
+    pub unchanged: u64,
+    pub flipped: u64,
+    /// Flip counts keyed by "OLD->NEW"
+    pub transitions: BTreeMap<String, u64>,
+    pub flips: Vec<DecisionFlip>,
+}
+
+/// Rescore previously decided transactions with the current ensemble and
+/// compare against the decision stored at the time. History is read from
+/// `pool`, results are written to the primary.
+pub async fn reanalyze_history(
+    pool: &PgPool,
+    state: &AppState,
+    limit: i64,
+    persist: bool,
+) -> Result<ReanalysisReport> {
+    let analyzer = state.analyzer.load_full();
+    let mut report = ReanalysisReport::default();
+    let mut cursor = None;
+
+    while (report.processed as i64) < limit {
+        let page_size = REANALYSIS_PAGE_SIZE.min(limit - report.processed as i64);
+        let page = transactions::fetch_decided_transactions(pool, cursor.clone(), page_size).await?;
+        if page.is_empty() {
+            break;
+        }
+
+        for stored in &page {
+            let transaction = stored.to_transaction();
+            let result = analyzer.analyze(pool, state, &transaction).await?;
+            report.processed += 1;
+
+            let old_decision = match stored.decision.as_deref().map(str::parse::<Decision>) {
+                Some(Ok(decision)) => decision,
+                _ => {
+                    tracing::warn!(
+                        "Skipping comparison for {}: unreadable stored decision {:?}",
+                        stored.transaction_id,
+                        stored.decision
+                    );
+                    continue;
+                }
+            };
+
+            if old_decision == result.decision {
+                report.unchanged += 1;
+            } else {
+                report.flipped += 1;
+                *report
+                    .transitions
+                    .entry(format!("{}->{}", old_decision, result.decision))
+                    .or_insert(0) += 1;
+                report.flips.push(DecisionFlip {
+                    transaction_id: stored.transaction_id.clone(),
+                    old_decision,
+                    new_decision: result.decision,
+                    new_risk_score: result.risk_score,
+                });
+            }
+
+            if persist {
+                persistence::save_analysis(
+                    &state.pool,
+                    &transaction,
+                    &result,
+                    None,
+                    state.config.embedding_precision,
+                )
+                .await?;
+            }
+        }
+
+        let last = &page[page.len() - 1];
+        cursor = Some((last.timestamp, last.transaction_id.clone()));
+    }
+
+    tracing::info!(
+        "🔁 Re-analysis done: {} processed, {} flipped",
+        report.processed,
+        report.flipped
+    );
+
+    Ok(report)
+}
+
+/// POST /api/admin/reanalyze - rescore history and report decision flips
+pub async fn reanalyze(
+    State(state): State<AppState>,
+    Json(request): Json<ReanalysisRequest>,
+) -> Result<Json<ReanalysisReport>, ApiError> {
+    let report = reanalyze_history(
+        &state.read_pool,
+        &state,
+        request.limit.unwrap_or(1000),
+        request.persist,
+    )
+    .await?;
+
+    Ok(Json(report))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BacktestRequest {
+    /// Maximum number of labeled transactions to replay
+    pub limit: Option<i64>,
+    /// Write each result into the fork as it's scored, so later replays see
+    /// earlier decisions (hysteresis, spending profiles). Never touches production
+    #[serde(default)]
+    pub persist: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BacktestReport {
+    /// Fork the replay ran against, already deleted
+    pub fork: String,
+    pub samples: usize,
+    /// Decision counts keyed by "DECISION/fraud" or "DECISION/legit"
+    pub decisions: BTreeMap<String, u64>,
+    pub tradeoff: ThresholdReport,
+    pub duration_ms: u64,
+}
+
+/// Replay labeled history through the ensemble against a throwaway fork.
+/// Any writes land in the fork, which is deleted afterwards even on error.
+pub async fn backtest_on_fork(state: &AppState, limit: i64, persist: bool) -> Result<BacktestReport> {
+    let start = Instant::now();
+    let manager = ForkManager::new(state.pool.clone(), state.config.database_url.clone());
+    let fork_name = format!("backtest_{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+    let fork = manager.open_fork(&fork_name).await?;
+    let analyzer = state.analyzer.load_full();
+
+    let replay = async {
+        let labeled = transactions::fetch_labeled_transactions(fork.pool(), limit).await?;
+        tracing::info!("🧪 Backtesting {} labeled transactions on fork {}", labeled.len(), fork.name());
+
+        let mut samples = Vec::with_capacity(labeled.len());
+        let mut decisions = BTreeMap::new();
+        for stored in &labeled {
+            let transaction = stored.to_transaction();
+            let result = analyzer.analyze(fork.pool(), state, &transaction).await?;
+            let is_fraud = stored.fraud_label.unwrap_or(false);
+
+            if persist {
+                persistence::save_analysis(
+                    fork.pool(),
+                    &transaction,
+                    &result,
+                    None,
+                    state.config.embedding_precision,
+                )
+                .await?;
+            }
+
+            *decisions
+                .entry(format!("{}/{}", result.decision, if is_fraud { "fraud" } else { "legit" }))
+                .or_insert(0) += 1;
+            samples.push((result.risk_score, is_fraud));
+        }
+
+        anyhow::Ok((samples, decisions))
+    };
+    // Early returns inside the replay drop the guard, which deletes the fork
+    let (samples, decisions) = replay.await?;
+    fork.cleanup().await?;
+
+    Ok(BacktestReport {
+        fork: fork_name,
+        samples: samples.len(),
+        decisions,
+        tradeoff: compute_tradeoff(&samples, &default_thresholds()),
+        duration_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+/// POST /api/admin/backtest - replay labeled history on a fork and report metrics
+pub async fn backtest(
+    State(state): State<AppState>,
+    Json(request): Json<BacktestRequest>,
+) -> Result<Json<BacktestReport>, ApiError> {
+    let report = backtest_on_fork(&state, request.limit.unwrap_or(500), request.persist).await?;
+
+    Ok(Json(report))
+}
+
+/// Agents in the order of `AgentWeights::as_array`
+const AGENTS: [&str; 5] = ["pattern", "anomaly", "geographic", "merchant", "network"];
+
+/// Decision sources of results decided without the agents' scores
+const UNSCORED_SOURCES: [&str; 3] = ["below_analysis_threshold", "merchant_denylist", "user_override"];
+
+/// How often one agent drove decisions and how its scores are spread
+#[derive(Debug, Serialize)]
+pub struct AgentContribution {
+    pub agent: String,
+    /// Decisions whose aggregate included the agent
+    pub decisions: u64,
+    /// Decisions where its weighted score was the largest
+    pub top_contributor: u64,
+    /// `top_contributor` over all decisions in the report
+    pub top_contributor_share: f64,
+    /// Mean of weight × score over the decisions it counted in
+    pub mean_contribution: f64,
+    pub mean_score: f64,
+    pub median_score: f64,
+    pub p90_score: f64,
+    /// Scores counted in ten 0.1-wide buckets, the last one including 1.0
+    pub score_histogram: [u64; 10],
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContributionReport {
+    pub since: DateTime<Utc>,
+    /// Stored results the agents' scores counted in
+    pub decisions: usize,
+    /// Stored results decided without the agents (gates, user overrides)
+    pub skipped: usize,
+    pub agents: Vec<AgentContribution>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContributionParams {
+    /// How many days back to look, default 7
+    pub days: Option<i64>,
+    /// Most recent results considered, default 10000
+    pub limit: Option<i64>,
+}
+
+/// Indexes into `AGENTS` of the agents whose scores counted in a stored
+/// result. `None` when the agents didn't decide it
+fn counted_agents(result: &serde_json::Value) -> Option<Vec<usize>> {
+    let mut counted = Vec::new();
+    match result["agent_status"].as_object().filter(|statuses| !statuses.is_empty()) {
+        Some(statuses) => {
+            for (i, agent) in AGENTS.iter().enumerate() {
+                if statuses.get(*agent).and_then(|status| status["status"].as_str()) == Some("ran") {
+                    counted.push(i);
+                }
+            }
+        }
+        // Results stored before `agent_status` only tell through the
+        // agents' reasons and the excluded list
+        None => {
+            let source = result["decision_source"].as_str().unwrap_or_default();
+            if UNSCORED_SOURCES.contains(&source) {
+                return None;
+            }
+            let listed = |key: &str, agent: &str| {
+                result[key]
+                    .as_array()
+                    .is_some_and(|entries| entries.iter().any(|entry| entry.as_str() == Some(agent)))
+            };
+            let reasons = result["agent_reasons"].as_array().map(Vec::as_slice).unwrap_or_default();
+            for (i, agent) in AGENTS.iter().enumerate() {
+                let reported = reasons.iter().any(|reason| reason["agent"].as_str() == Some(*agent));
+                if reported && !listed("excluded_agents", agent) {
+                    counted.push(i);
+                }
+            }
+        }
+    }
+    (!counted.is_empty()).then_some(counted)
+}
+
+/// Value at quantile `q` of ascending `sorted`, nearest rank
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    sorted[((sorted.len() - 1) as f64 * q).round() as usize]
+}
+
+/// Aggregate stored analysis results into per-agent contribution stats.
+/// An agent's contribution to a decision is its score times the weight it
+/// was aggregated with: the stored weights, or the defaults for results
+/// stored without them. Confidence scaling isn't stored and is left out
+pub fn compute_contributions(results: &[serde_json::Value]) -> (usize, Vec<AgentContribution>) {
+    let mut scores: [Vec<f64>; 5] = Default::default();
+    let mut contributions = [0.0; 5];
+    let mut top = [0u64; 5];
+    let mut decisions = 0;
+
+    for result in results {
+        let Some(counted) = counted_agents(result) else {
+            continue;
+        };
+        decisions += 1;
+
+        let weights = serde_json::from_value::<AgentWeights>(result["agent_weights"]["weights"].clone())
+            .unwrap_or_default()
+            .as_array();
+        let mut leader: Option<(usize, f64)> = None;
+        for i in counted {
+            let score = result["agent_scores"][AGENTS[i]].as_f64().unwrap_or(0.0);
+            let contribution = score * weights[i];
+            scores[i].push(score);
+            contributions[i] += contribution;
+            if contribution > leader.map_or(0.0, |(_, best)| best) {
+                leader = Some((i, contribution));
+            }
+        }
+        // No top contributor when every counted agent scored 0
+        if let Some((i, _)) = leader {
+            top[i] += 1;
+        }
+    }
+
+    let agents = AGENTS
+        .iter()
+        .enumerate()
+        .map(|(i, agent)| {
+            let agent_scores = &mut scores[i];
+            agent_scores.sort_by(f64::total_cmp);
+            let count = agent_scores.len();
+            let mean = |total: f64| if count > 0 { total / count as f64 } else { 0.0 };
+            let mut score_histogram = [0u64; 10];
+            for score in agent_scores.iter() {
+                score_histogram[((score.clamp(0.0, 1.0) * 10.0) as usize).min(9)] += 1;
+            }
+
+            AgentContribution {
+                agent: agent.to_string(),
+                decisions: count as u64,
+                top_contributor: top[i],
+                top_contributor_share: if decisions > 0 { top[i] as f64 / decisions as f64 } else { 0.0 },
+                mean_contribution: mean(contributions[i]),
+                mean_score: mean(agent_scores.iter().sum()),
+                median_score: quantile(agent_scores, 0.5),
+                p90_score: quantile(agent_scores, 0.9),
+                score_histogram,
+            }
+        })
+        .collect();
+
+    (decisions, agents)
+}
+
+/// GET /api/admin/agent-contributions - which agents drive recent decisions
+pub async fn agent_contributions(
+    State(state): State<AppState>,
+    Query(params): Query<ContributionParams>,
+) -> Result<Json<ContributionReport>, ApiError> {
+    let days = params.days.unwrap_or(7);
+    if days <= 0 {
+        return Err(ApiError::BadRequest(format!("days must be positive, got {}", days)));
+    }
+    let since = Duration::try_days(days)
+        .and_then(|window| Utc::now().checked_sub_signed(window))
+        .ok_or_else(|| ApiError::BadRequest(format!("days is too large, got {}", days)))?;
+    let limit = params.limit.unwrap_or(10_000);
+    if limit <= 0 {
+        return Err(ApiError::BadRequest(format!("limit must be positive, got {}", limit)));
+    }
+
+    let results = transactions::fetch_recent_results(&state.read_pool, since, limit).await?;
+    let (decisions, agents) = compute_contributions(&results);
+
+    Ok(Json(ContributionReport {
+        since,
+        decisions,
+        skipped: results.len() - decisions,
+        agents,
+    }))
+}
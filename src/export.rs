@@ -0,0 +1,217 @@
+use std::{fs::File, sync::Arc};
+
+use anyhow::{Context, Result};
+use arrow::{
+    array::{ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray},
+    datatypes::{DataType, Field, Schema, TimeUnit},
+    record_batch::RecordBatch,
+};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use sqlx::PgPool;
+
+use crate::config::{self, Config, ConfigSource};
+
+/// Rows per record batch, and so the most held in memory at once
+const BATCH_SIZE: usize = 8192;
+
+/// One stored analysis joined with its transaction
+#[derive(sqlx::FromRow, Debug)]
+struct DecisionRow {
+    analysis_id: i64,
+    transaction_id: String,
+    user_id: Option<String>,
+    decision: String,
+    risk_score: Option<f64>,
+    confidence: Option<f64>,
+    model_version: Option<String>,
+    analyzed_at: Option<DateTime<Utc>>,
+    amount: Option<f64>,
+    merchant: Option<String>,
+    merchant_category: Option<String>,
+    payment_method: Option<String>,
+    transaction_timestamp: Option<DateTime<Utc>>,
+    fraud_label: Option<bool>,
+}
+
+/// Exportable columns, in file order when all are selected
+const COLUMNS: &[&str] = &[
+    "analysis_id",
+    "transaction_id",
+    "user_id",
+    "decision",
+    "risk_score",
+    "confidence",
+    "model_version",
+    "analyzed_at",
+    "amount",
+    "merchant",
+    "merchant_category",
+    "payment_method",
+    "transaction_timestamp",
+    "fraud_label",
+];
+
+fn field(column: &str) -> Field {
+    let data_type = match column {
+        "analysis_id" => DataType::Int64,
+        "risk_score" | "confidence" | "amount" => DataType::Float64,
+        "analyzed_at" | "transaction_timestamp" => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        "fraud_label" => DataType::Boolean,
+        _ => DataType::Utf8,
+    };
+    Field::new(column, data_type, column != "analysis_id" && column != "transaction_id" && column != "decision")
+}
+
+fn column_array(column: &str, rows: &[DecisionRow]) -> ArrayRef {
+    let strings = |value: fn(&DecisionRow) -> Option<&str>| -> ArrayRef {
+        Arc::new(rows.iter().map(value).collect::<StringArray>())
+    };
+    let floats = |value: fn(&DecisionRow) -> Option<f64>| -> ArrayRef {
+        Arc::new(rows.iter().map(value).collect::<Float64Array>())
+    };
+    let timestamps = |value: fn(&DecisionRow) -> Option<DateTime<Utc>>| -> ArrayRef {
+        Arc::new(
+            rows.iter()
+                .map(|row| value(row).map(|t| t.timestamp_micros()))
+                .collect::<TimestampMicrosecondArray>()
+                .with_timezone("UTC"),
+        )
+    };
+
+    match column {
+        "analysis_id" => Arc::new(rows.iter().map(|row| Some(row.analysis_id)).collect::<Int64Array>()),
+        "transaction_id" => strings(|row| Some(row.transaction_id.as_str())),
+        "user_id" => strings(|row| row.user_id.as_deref()),
+        "decision" => strings(|row| Some(row.decision.as_str())),
+        "risk_score" => floats(|row| row.risk_score),
+        "confidence" => floats(|row| row.confidence),
+        "model_version" => strings(|row| row.model_version.as_deref()),
+        "analyzed_at" => timestamps(|row| row.analyzed_at),
+        "amount" => floats(|row| row.amount),
+        "merchant" => strings(|row| row.merchant.as_deref()),
+        "merchant_category" => strings(|row| row.merchant_category.as_deref()),
+        "payment_method" => strings(|row| row.payment_method.as_deref()),
+        "transaction_timestamp" => timestamps(|row| row.transaction_timestamp),
+        "fraud_label" => Arc::new(rows.iter().map(|row| row.fraud_label).collect::<BooleanArray>()),
+        other => unreachable!("column '{}' was validated", other),
+    }
+}
+
+/// What to export
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub path: String,
+    /// Analyses at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Analyses before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Subset of the exportable columns, all when empty
+    pub columns: Vec<String>,
+}
+
+/// Stream every stored analysis in the time range, joined with its
+/// transaction, to a Parquet file. Rows are written in batches, the history
+/// is never held in memory as a whole. Returns the number of rows written
+pub async fn export_decisions(pool: &PgPool, options: &ExportOptions) -> Result<u64> {
+    let columns: Vec<&str> = if options.columns.is_empty() {
+        COLUMNS.to_vec()
+    } else {
+        options
+            .columns
+            .iter()
+            .map(|column| {
+                COLUMNS
+                    .iter()
+                    .copied()
+                    .find(|known| known.eq_ignore_ascii_case(column.trim()))
+                    .with_context(|| format!("Unknown export column '{}', expected one of {}", column, COLUMNS.join(", ")))
+            })
+            .collect::<Result<_>>()?
+    };
+    let schema = Arc::new(Schema::new(columns.iter().map(|column| field(column)).collect::<Vec<_>>()));
+
+    let file = File::create(&options.path).with_context(|| format!("Can't create {}", options.path))?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(properties))?;
+
+    let mut rows = sqlx::query_as::<_, DecisionRow>(
+        r#"
+        SELECT
+            a.id as analysis_id,
+            a.transaction_id,
+            a.user_id,
+            a.decision,
+            a.risk_score,
+            a.confidence,
+            a.model_version,
+            a.created_at as analyzed_at,
+            t.amount::float8 as amount,
+            t.merchant,
+            t.merchant_category,
+            t.payment_method,
+            t.timestamp as transaction_timestamp,
+            t.fraud_label
+        FROM analysis_results a
+        LEFT JOIN transactions t USING (transaction_id)
+        WHERE ($1::timestamptz IS NULL OR a.created_at >= $1)
+        AND ($2::timestamptz IS NULL OR a.created_at < $2)
+        ORDER BY a.id
+        "#,
+    )
+    .bind(options.from)
+    .bind(options.to)
+    .fetch(pool);
+
+    let mut written = 0u64;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    loop {
+        let row = rows.try_next().await?;
+        let done = row.is_none();
+        batch.extend(row);
+
+        if batch.len() == BATCH_SIZE || (done && !batch.is_empty()) {
+            let arrays = columns.iter().map(|column| column_array(column, &batch)).collect();
+            writer.write(&RecordBatch::try_new(schema.clone(), arrays)?)?;
+            written += batch.len() as u64;
+            batch.clear();
+        }
+        if done {
+            break;
+        }
+    }
+
+    writer.close()?;
+    Ok(written)
+}
+
+/// Export decisions to Parquet and exit. Needs only the database.
+///
+/// Run with `cargo run --release -- --export-decisions`, configured by
+/// `EXPORT_PATH` (default `decisions.parquet`), `EXPORT_FROM` / `EXPORT_TO`
+/// (RFC 3339) and `EXPORT_COLUMNS` (comma-separated).
+pub async fn run_export() -> Result<()> {
+    let source = config::layered_source()?;
+    let config = Config::from_source(&source)?;
+    let timestamp = |key: &str| -> Result<Option<DateTime<Utc>>> {
+        source
+            .get(key)
+            .map(|value| value.trim().parse::<DateTime<Utc>>())
+            .transpose()
+            .with_context(|| format!("Invalid {}, expected an RFC 3339 timestamp", key))
+    };
+    let options = ExportOptions {
+        path: config::string(&source, "EXPORT_PATH", "decisions.parquet".to_string()),
+        from: timestamp("EXPORT_FROM")?,
+        to: timestamp("EXPORT_TO")?,
+        columns: config::parsed_list(&source, "EXPORT_COLUMNS", Vec::new())?,
+    };
+
+    let pool = crate::db::pool::create_pool(&config.database_url, 2).await?;
+    let written = export_decisions(&pool, &options).await?;
+    println!("Exported {} decisions to {}", written, options.path);
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use axum::{Json, extract::State};
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    db::challenges::{self, ChallengeOutcome, ChallengeRecord, Recorded},
+    error::ApiError,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ChallengeFeedbackRequest {
+    pub transaction_id: String,
+    pub outcome: ChallengeOutcome,
+}
+
+/// POST /api/feedback/challenge - how a challenged user's step-up
+/// authentication ended. Passes build the user's trust at the merchant in
+/// the pattern agent, they don't label the transaction
+pub async fn record_challenge_outcome(
+    State(state): State<AppState>,
+    Json(request): Json<ChallengeFeedbackRequest>,
+) -> Result<Json<ChallengeRecord>, ApiError> {
+    if request.transaction_id.trim().is_empty() {
+        return Err(ApiError::BadRequest("transaction_id must not be empty".to_string()));
+    }
+
+    let record = match challenges::record_outcome(&state.pool, &request.transaction_id, request.outcome).await? {
+        Recorded::Stored(record) => record,
+        Recorded::UnknownTransaction => {
+            return Err(ApiError::NotFound(format!("Transaction '{}' not found", request.transaction_id)));
+        }
+        Recorded::NotChallenged => {
+            return Err(ApiError::BadRequest(format!(
+                "Transaction '{}' was not challenged",
+                request.transaction_id
+            )));
+        }
+        Recorded::AlreadyRecorded => {
+            return Err(ApiError::BadRequest(format!(
+                "Transaction '{}' already has a challenge outcome",
+                request.transaction_id
+            )));
+        }
+    };
+    tracing::info!(
+        "Challenge {} for {} ({} at {})",
+        record.outcome,
+        record.transaction_id,
+        record.user_id,
+        record.merchant
+    );
+
+    Ok(Json(record))
+}
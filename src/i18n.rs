@@ -0,0 +1,248 @@
+//! Reason strings per locale.
+//!
+//! Agents emit [`Reason`]s (a code plus display-ready parameters) and the
+//! strings are rendered here from a translation table. Adding a locale means
+//! adding a `Locale` variant and a table function: every table is an
+//! exhaustive `match`, so a new `ReasonCode` can't ship untranslated.
+
+use crate::models::transaction::{AnalysisResult, Reason, ReasonCode};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Locale {
+    /// Match a BCP 47 tag ("es", "es-MX") on its primary language
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::English),
+            "es" => Some(Locale::Spanish),
+            _ => None,
+        }
+    }
+
+    /// Best supported locale for an `Accept-Language` header, by q-value.
+    /// English when nothing listed is supported
+    pub fn negotiate(accept_language: &str) -> Self {
+        let mut best: Option<(f64, Locale)> = None;
+        for entry in accept_language.split(',') {
+            let mut parts = entry.split(';');
+            let Some(locale) = parts.next().and_then(Locale::from_tag) else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f64>().ok())
+                .unwrap_or(1.0);
+            if quality > 0.0 && best.is_none_or(|(q, _)| quality > q) {
+                best = Some((quality, locale));
+            }
+        }
+        best.map(|(_, locale)| locale).unwrap_or_default()
+    }
+
+    /// Request field first, then the header, then English
+    pub fn resolve(requested: Option<&str>, accept_language: Option<&str>) -> Self {
+        requested
+            .and_then(Locale::from_tag)
+            .or_else(|| accept_language.map(Locale::negotiate))
+            .unwrap_or_default()
+    }
+}
+
+/// Render one reason, substituting its `{name}` placeholders
+pub fn render(locale: Locale, reason: &Reason) -> String {
+    let mut text = template(locale, reason.code).to_string();
+    for (name, value) in &reason.params {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+/// Render an agent's reasons as one string
+pub fn render_all(locale: Locale, reasons: &[Reason]) -> String {
+    reasons
+        .iter()
+        .map(|reason| render(locale, reason))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Network agent's reason when it detected a ring
+pub fn fraud_ring(locale: Locale, reason: &str) -> String {
+    let prefix = match locale {
+        Locale::English => "⚠️ FRAUD RING DETECTED",
+        Locale::Spanish => "⚠️ RED DE FRAUDE DETECTADA",
+    };
+    format!("{}: {}", prefix, reason)
+}
+
+/// Re-render the reasons of an English result in `locale`. The agents'
+/// English strings carry details (city, merchant name) that the generic
+/// "nothing found" messages of other locales leave out
+pub fn localize(result: &mut AnalysisResult, locale: Locale) {
+    if locale == Locale::English || result.agent_reasons.is_empty() {
+        return;
+    }
+
+    for agent_reason in &mut result.agent_reasons {
+        let reason = if agent_reason.reasons.is_empty() {
+            nothing_found(locale, &agent_reason.agent).to_string()
+        } else {
+            render_all(locale, &agent_reason.reasons)
+        };
+        agent_reason.reason = if agent_reason.agent == "network" && result.fraud_ring_detected {
+            fraud_ring(locale, &reason)
+        } else {
+            reason
+        };
+    }
+
+    result.reasoning = result
+        .agent_reasons
+        .iter()
+        .map(|agent_reason| format!("{}: {}", agent_label(locale, &agent_reason.agent), agent_reason.reason))
+        .collect::<Vec<_>>()
+        .join(" | ");
+}
+
+fn template(locale: Locale, code: ReasonCode) -> &'static str {
+    match locale {
+        Locale::English => english(code),
+        Locale::Spanish => spanish(code),
+    }
+}
+
+fn agent_label(locale: Locale, agent: &str) -> &'static str {
+    match (locale, agent) {
+        (Locale::English, "pattern") => "Pattern",
+        (Locale::English, "anomaly") => "Anomaly",
+        (Locale::English, "geographic") => "Geographic",
+        (Locale::English, "merchant") => "Merchant",
+        (Locale::English, "network") => "Network",
+        (Locale::Spanish, "pattern") => "Patrón",
+        (Locale::Spanish, "anomaly") => "Anomalía",
+        (Locale::Spanish, "geographic") => "Geografía",
+        (Locale::Spanish, "merchant") => "Comercio",
+        (Locale::Spanish, "network") => "Red",
+        _ => "?",
+    }
+}
+
+fn nothing_found(locale: Locale, agent: &str) -> &'static str {
+    match (locale, agent) {
+        (Locale::English, "pattern") => "Normal spending pattern",
+        (Locale::English, "anomaly") => "Normal transaction timing and frequency",
+        (Locale::English, "geographic") => "Normal location",
+        (Locale::English, "merchant") => "Trusted merchant",
+        (Locale::English, "network") => "No fraud ring indicators",
+        (Locale::Spanish, "pattern") => "Patrón de gasto normal",
+        (Locale::Spanish, "anomaly") => "Horario y frecuencia de transacciones normales",
+        (Locale::Spanish, "geographic") => "Ubicación normal",
+        (Locale::Spanish, "merchant") => "Comercio de confianza",
+        (Locale::Spanish, "network") => "Sin indicios de red de fraude",
+        _ => "",
+    }
+}
+
+fn english(code: ReasonCode) -> &'static str {
+    match code {
+        ReasonCode::AmountDeviation => "Amount {amount} is {ratio}x user's {scope}average {average}",
+        ReasonCode::PopulationAmountOutlier => "Amount {amount} is {z_score} std devs above typical {category} spending",
+        ReasonCode::AmountPercentileOutlier => "Amount {amount} is above the user's {percentile}th percentile ({threshold})",
+        ReasonCode::NewCategory => "New category '{category}'",
+        ReasonCode::HighRiskCategory => "High-risk category '{category}'",
+        ReasonCode::SimilarToFraud => "{percent}% of similar transactions were fraud",
+        ReasonCode::GlobalFraudMatch => "Closely matches confirmed fraud from another account (similarity {similarity})",
+        ReasonCode::ProfileNovelty => "Unlike user's usual spending (profile distance {distance})",
+        ReasonCode::HighVelocity => "{count} transactions in last hour (high velocity)",
+        ReasonCode::UnusualHour => "Transaction at unusual hour: {hour}:00",
+        ReasonCode::LargeAmountAtUnusualHour => "Large amount ({percentile}th percentile for user) at {hour}:00",
+        ReasonCode::RapidSuccession => "Transaction only {minutes} minutes after previous",
+        ReasonCode::AmountSpike => "Amount ${amount} is 3x recent average ${average}",
+        ReasonCode::NewDevice => "First transaction from this device",
+        ReasonCode::RoundAmount => "Round amount ${amount}",
+        ReasonCode::MissingLocation => "No location data",
+        ReasonCode::UnknownLocation => "Unknown or suspicious location",
+        ReasonCode::ImpossibleTravel => "Impossible travel: {distance_km}km in {hours} hours",
+        ReasonCode::UnlikelyTravel => "Unlikely travel pattern: {distance_km}km",
+        ReasonCode::NewCountry => "First transaction in {country}",
+        ReasonCode::HighRiskMerchant => "High-risk merchant: {fraud_rate}% fraud rate",
+        ReasonCode::ElevatedRiskMerchant => "Elevated risk merchant: {fraud_rate}% fraud rate",
+        ReasonCode::ElevatedRiskForCategory => {
+            "Elevated risk merchant: {fraud_rate}% fraud rate vs {category_rate}% for {category}"
+        }
+        ReasonCode::NewMerchant => "New/unknown merchant",
+        ReasonCode::UnrecognizedMerchant => "Unrecognized merchant",
+        ReasonCode::MerchantFraudReports => "Found {count} similar fraud cases via pg_text search",
+        ReasonCode::SimilarRiskyMerchants => "{count} similar high-risk merchants found",
+        ReasonCode::MerchantDenylisted => "Merchant '{merchant}' is on the denylist: {reason}",
+        ReasonCode::CategoryMismatch => "Declared category '{declared}' doesn't match merchant's category '{expected}'",
+        ReasonCode::UnlikelyCategory => {
+            "Merchant '{merchant}' is unlikely for category '{declared}' (similarity {similarity})"
+        }
+        ReasonCode::SharedDevice => "Device used by {count} users",
+        ReasonCode::SharedDeviceRing => "Device shared by {count} users (fraud ring)",
+        ReasonCode::CoordinatedMerchantActivity => "{count} coordinated transactions at same merchant",
+        ReasonCode::DeviceVelocityRing => "{count} rapid transactions from this device",
+        ReasonCode::CardTesting => "Card testing pattern: {count} merchants in {minutes} minutes",
+    }
+}
+
+fn spanish(code: ReasonCode) -> &'static str {
+    match code {
+        ReasonCode::AmountDeviation => "El importe {amount} es {ratio} veces la media {scope}del usuario ({average})",
+        ReasonCode::PopulationAmountOutlier => {
+            "El importe {amount} está {z_score} desviaciones estándar por encima del gasto típico en {category}"
+        }
+        ReasonCode::AmountPercentileOutlier => {
+            "El importe {amount} supera el percentil {percentile} del usuario ({threshold})"
+        }
+        ReasonCode::NewCategory => "Categoría nueva '{category}'",
+        ReasonCode::HighRiskCategory => "Categoría de alto riesgo '{category}'",
+        ReasonCode::SimilarToFraud => "El {percent}% de las transacciones similares fueron fraude",
+        ReasonCode::GlobalFraudMatch => {
+            "Muy parecida a un fraude confirmado de otra cuenta (similitud {similarity})"
+        }
+        ReasonCode::ProfileNovelty => "Distinta del gasto habitual del usuario (distancia al perfil {distance})",
+        ReasonCode::HighVelocity => "{count} transacciones en la última hora (velocidad alta)",
+        ReasonCode::UnusualHour => "Transacción a una hora inusual: {hour}:00",
+        ReasonCode::LargeAmountAtUnusualHour => {
+            "Importe elevado (percentil {percentile} del usuario) a las {hour}:00"
+        }
+        ReasonCode::RapidSuccession => "Transacción solo {minutes} minutos después de la anterior",
+        ReasonCode::AmountSpike => "El importe ${amount} triplica la media reciente ${average}",
+        ReasonCode::NewDevice => "Primera transacción desde este dispositivo",
+        ReasonCode::RoundAmount => "Importe redondo de ${amount}",
+        ReasonCode::MissingLocation => "Sin datos de ubicación",
+        ReasonCode::UnknownLocation => "Ubicación desconocida o sospechosa",
+        ReasonCode::ImpossibleTravel => "Viaje imposible: {distance_km}km en {hours} horas",
+        ReasonCode::UnlikelyTravel => "Patrón de viaje improbable: {distance_km}km",
+        ReasonCode::NewCountry => "Primera transacción en {country}",
+        ReasonCode::HighRiskMerchant => "Comercio de alto riesgo: {fraud_rate}% de fraude",
+        ReasonCode::ElevatedRiskMerchant => "Comercio de riesgo elevado: {fraud_rate}% de fraude",
+        ReasonCode::ElevatedRiskForCategory => {
+            "Comercio de riesgo elevado: {fraud_rate}% de fraude frente a {category_rate}% en {category}"
+        }
+        ReasonCode::NewMerchant => "Comercio nuevo o desconocido",
+        ReasonCode::UnrecognizedMerchant => "Comercio no reconocido",
+        ReasonCode::MerchantFraudReports => "{count} casos de fraude similares encontrados por búsqueda de texto",
+        ReasonCode::SimilarRiskyMerchants => "{count} comercios similares de alto riesgo",
+        ReasonCode::MerchantDenylisted => "El comercio '{merchant}' está en la lista de bloqueo: {reason}",
+        ReasonCode::CategoryMismatch => {
+            "La categoría declarada '{declared}' no coincide con la del comercio '{expected}'"
+        }
+        ReasonCode::UnlikelyCategory => {
+            "El comercio '{merchant}' es improbable para la categoría '{declared}' (similitud {similarity})"
+        }
+        ReasonCode::SharedDevice => "Dispositivo usado por {count} usuarios",
+        ReasonCode::SharedDeviceRing => "Dispositivo compartido por {count} usuarios (red de fraude)",
+        ReasonCode::CoordinatedMerchantActivity => "{count} transacciones coordinadas en el mismo comercio",
+        ReasonCode::DeviceVelocityRing => "{count} transacciones rápidas desde este dispositivo",
+        ReasonCode::CardTesting => "Patrón de prueba de tarjetas: {count} comercios en {minutes} minutos",
+    }
+}
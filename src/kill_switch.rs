@@ -0,0 +1,142 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use axum::{Json, extract::State};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState,
+    models::transaction::{AnalysisResult, Decision, DecisionSource},
+};
+
+/// Global override of the final decision, for incidents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OverrideMode {
+    /// Decisions come from scoring
+    #[default]
+    Normal,
+    /// Approve everything, e.g. when a bad deploy is blocking legitimate traffic
+    ForceApprove,
+    /// Challenge everything
+    ForceChallenge,
+}
+
+impl OverrideMode {
+    fn forced_decision(self) -> Option<Decision> {
+        match self {
+            OverrideMode::Normal => None,
+            OverrideMode::ForceApprove => Some(Decision::Approve),
+            OverrideMode::ForceChallenge => Some(Decision::Challenge),
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            OverrideMode::Normal => 0,
+            OverrideMode::ForceApprove => 1,
+            OverrideMode::ForceChallenge => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => OverrideMode::ForceApprove,
+            2 => OverrideMode::ForceChallenge,
+            _ => OverrideMode::Normal,
+        }
+    }
+}
+
+impl std::str::FromStr for OverrideMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_uppercase().as_str() {
+            "NORMAL" => Ok(OverrideMode::Normal),
+            "FORCE_APPROVE" => Ok(OverrideMode::ForceApprove),
+            "FORCE_CHALLENGE" => Ok(OverrideMode::ForceChallenge),
+            other => Err(format!(
+                "Unknown override mode '{}', expected NORMAL, FORCE_APPROVE or FORCE_CHALLENGE",
+                other
+            )),
+        }
+    }
+}
+
+/// Current override mode, switchable at runtime without a redeploy
+pub struct KillSwitch {
+    mode: AtomicU8,
+}
+
+impl KillSwitch {
+    pub fn new(mode: OverrideMode) -> Self {
+        Self {
+            mode: AtomicU8::new(mode.to_u8()),
+        }
+    }
+
+    pub fn mode(&self) -> OverrideMode {
+        OverrideMode::from_u8(self.mode.load(Ordering::Relaxed))
+    }
+
+    /// Switch modes, returning the previous one
+    pub fn set(&self, mode: OverrideMode) -> OverrideMode {
+        OverrideMode::from_u8(self.mode.swap(mode.to_u8(), Ordering::Relaxed))
+    }
+
+    /// Replace the scored decision when an override is active. The scores and
+    /// reasons are kept, and the decision scoring made is kept in
+    /// `overridden_decision`.
+    pub fn apply(&self, result: &mut AnalysisResult) {
+        let mode = self.mode();
+        let Some(forced) = mode.forced_decision() else {
+            return;
+        };
+
+        tracing::warn!(
+            "🚨 Override {:?}: {} for {} (scored {})",
+            mode,
+            forced,
+            result.transaction_id,
+            result.decision
+        );
+        result.overridden_decision = Some(result.decision);
+        result.decision = forced;
+        result.decision_source = DecisionSource::Override;
+    }
+}
+
+#[derive(Serialize)]
+pub struct OverrideStatus {
+    pub mode: OverrideMode,
+    /// Mode before this change, only set when switching
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<OverrideMode>,
+}
+
+#[derive(Deserialize)]
+pub struct OverrideRequest {
+    pub mode: OverrideMode,
+}
+
+/// GET /api/admin/override - current override mode
+pub async fn get_override(State(state): State<AppState>) -> Json<OverrideStatus> {
+    Json(OverrideStatus {
+        mode: state.kill_switch.mode(),
+        previous: None,
+    })
+}
+
+/// PUT /api/admin/override - switch the override mode
+pub async fn set_override(
+    State(state): State<AppState>,
+    Json(request): Json<OverrideRequest>,
+) -> Json<OverrideStatus> {
+    let previous = state.kill_switch.set(request.mode);
+    tracing::warn!("🚨 Decision override changed: {:?} -> {:?}", previous, request.mode);
+
+    Json(OverrideStatus {
+        mode: request.mode,
+        previous: Some(previous),
+    })
+}
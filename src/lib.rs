@@ -1,9 +1,31 @@
+pub mod admin_auth;
 pub mod agents;
 pub mod analysis;
+pub mod bench;
+pub mod category;
+pub mod circuit_breaker;
+pub mod config;
 pub mod db;
+pub mod denylist;
 pub mod embedding;
+pub mod error;
+pub mod evaluation;
+pub mod export;
+pub mod feedback;
+pub mod i18n;
+pub mod kill_switch;
 pub mod models;
+pub mod post_processing;
+pub mod profile;
+pub mod reembed;
+pub mod reload;
 pub mod seed_data;
+pub mod signing;
+pub mod state;
+pub mod transactions;
+pub mod user_overrides;
+#[cfg(feature = "test-utils")]
+pub mod testing;
 
 pub use agents::*;
 pub use analysis::FraudAnalyzer;
@@ -11,15 +33,4 @@ pub use db::pool::create_pool;
 pub use models::*;
 
 // Re-export AppState
-use candle_core::{Device, Tensor};
-use sqlx::PgPool;
-use std::{collections::HashMap, sync::Arc};
-use tokenizers::Tokenizer;
-
-#[derive(Clone)]
-pub struct AppState {
-    pub pool: PgPool,
-    pub tensors: Arc<HashMap<String, Tensor>>,
-    pub tokenizer: Arc<Tokenizer>,
-    pub device: Device,
-}
+pub use state::AppState;
@@ -1,86 +1,164 @@
+mod admin_auth;
 mod agents;
 mod analysis;
+mod bench;
+mod category;
+mod circuit_breaker;
+mod config;
 mod db;
+mod denylist;
 mod embedding;
+mod error;
+mod evaluation;
+mod export;
+mod feedback;
+mod i18n;
+mod kill_switch;
 mod models;
+mod post_processing;
+mod profile;
+mod reembed;
+mod reload;
 mod seed_data;
-use axum::response::Html;
+mod signing;
+mod state;
+mod transactions;
+mod user_overrides;
 use axum::{Router, serve};
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Json, Query, State},
+    middleware,
+    http::{HeaderMap, header},
+    response::{Html, IntoResponse, Response},
+    routing::{get, patch, post, put},
 };
-use candle_core::{Device, Tensor};
+use arc_swap::ArcSwap;
 use std::fs;
-use std::{collections::HashMap, env, sync::Arc};
+use std::time::{Duration, Instant};
+use std::sync::Arc;
 use tower_http::compression::CompressionLayer;
 use tower_http::cors::{Any, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::timeout::{RequestBodyTimeoutLayer, TimeoutLayer};
 
-use sqlx::PgPool;
-use tokenizers::Tokenizer;
 use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
 
 use tracing_subscriber::prelude::*;
 
 use crate::analysis::FraudAnalyzer;
-use crate::models::transaction::AnalysisResult;
+use crate::circuit_breaker::CircuitBreakers;
+use crate::config::Config;
+use crate::db::{merchant_similarity::SimilarRiskyMerchants, persistence, population::PopulationStats, user_overrides::UserOverrideCache};
+use crate::error::ApiError;
+use crate::i18n::Locale;
+use crate::kill_switch::KillSwitch;
+use crate::state::AppState;
+use crate::models::transaction::{FieldCase, ScoreScale, Verbosity};
 use crate::{
-    agents::pattern::PatternAgent, embedding::load_model, models::transaction::TransactionRequest,
+    agents::pattern::PatternAgent,
+    embedding::{CandleEmbedder, EmbeddingProvider, load_model},
+    models::transaction::TransactionRequest,
 };
 
-#[derive(Clone)]
-pub struct AppState {
-    pub pool: PgPool,
-    pub tensors: Arc<HashMap<String, Tensor>>,
-    pub tokenizer: Arc<Tokenizer>,
-    pub device: Device,
-}
-
 async fn test_pattern_agent(
     State(app_state): State<AppState>,
     Json(request): Json<TransactionRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
+    request.validate().map_err(ApiError::BadRequest)?;
     let transaction = request.to_transaction();
-    let agent = PatternAgent::new();
+    let agent = PatternAgent::with_config(app_state.config.pattern.clone());
 
-    match agent
-        .analyze(&app_state.pool, &app_state, &transaction)
-        .await
-    {
-        Ok(score) => Ok(Json(serde_json::json!({
-            "agent": "Pattern",
-            "risk_score": score.risk_score,
-            "reason": score.reason,
-            "details": score.details
-        }))),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
-    }
+    let score = agent
+        .analyze(&app_state.read_pool, &app_state, &transaction)
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "agent": "Pattern",
+        "risk_score": score.risk_score,
+        "confidence": score.confidence,
+        "reason": score.reason,
+        "details": score.details
+    })))
 }
 
 //main function to call orchestrator
+//
+// A scored transaction is always a 200, whatever the decision: BLOCK is an
+// answer, not a failure. Only invalid input (400) and infrastructure failures
+// (5xx) come back as errors, see `ApiError` for the full contract.
+#[derive(Debug, serde::Deserialize)]
+struct AnalyzeParams {
+    /// `percent` reports scores as 0-100 integers
+    #[serde(default)]
+    score_scale: ScoreScale,
+    /// `terse` drops the per-agent reasons, `verbose` adds each agent's details
+    #[serde(default)]
+    verbosity: Verbosity,
+    /// `camel` renames fields for JS/TS clients, defaults to `RESPONSE_FIELD_CASE`
+    field_case: Option<FieldCase>,
+}
+
 async fn analyze_transaction(
     State(app_state): State<AppState>,
+    Query(params): Query<AnalyzeParams>,
+    headers: HeaderMap,
     Json(request): Json<TransactionRequest>,
-) -> Result<Json<AnalysisResult>, (StatusCode, String)> {
+) -> Result<Response, ApiError> {
     tracing::info!("📥 Received transaction for user: {}", request.user_id);
 
-    let analyzer = FraudAnalyzer::new(app_state.pool.clone());
+    request.validate().map_err(ApiError::BadRequest)?;
 
-    match analyzer
-        .analyze_transaction(&app_state.pool, &app_state, request)
+    let transaction = request.to_transaction();
+
+    match app_state
+        .analyzer
+        .load_full()
+        .analyze(&app_state.read_pool, &app_state, &transaction)
         .await
     {
-        Ok(result) => {
+        Ok(mut result) => {
             tracing::info!("✅ Analysis complete: {}", result.decision);
-            Ok(Json(result))
+
+            // Stored as scored, an operator override only changes what is
+            // returned and is recorded next to it
+            let scored = result.clone();
+            app_state.finalize_decision(&mut result);
+            let override_decision = result.overridden_decision.map(|_| result.decision);
+
+            // The decision stands even if it couldn't be stored, `persisted`
+            // tells durability-sensitive callers which case they're in
+            result.persisted = persistence::persist_analysis(
+                &app_state.pool,
+                &transaction,
+                &scored,
+                override_decision,
+                app_state.config.embedding_precision,
+                app_state.config.persistence_mode,
+            )
+            .await;
+
+            // Stored in English, only the response is localized
+            let locale = Locale::resolve(
+                request.locale.as_deref(),
+                headers.get(header::ACCEPT_LANGUAGE).and_then(|value| value.to_str().ok()),
+            );
+            i18n::localize(&mut result, locale);
+
+            let mut body = match params.score_scale {
+                ScoreScale::Unit => serde_json::to_value(&result).map_err(anyhow::Error::from)?,
+                ScoreScale::Percent => result.to_percent_json(),
+            };
+            result.apply_verbosity(&mut body, params.verbosity);
+            params
+                .field_case
+                .unwrap_or(app_state.config.response_field_case)
+                .apply(&mut body);
+            Ok(Json(body).into_response())
         }
         Err(e) => {
             tracing::error!("❌ Analysis failed: {}", e);
-            Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Analysis failed: {}", e),
-            ))
+            Err(ApiError::from(e))
         }
     }
 }
@@ -97,25 +175,108 @@ async fn main() -> anyhow::Result<()> {
     // Load .env file
     let _ = dotenvy::dotenv();
 
+    // Capacity planning mode: measure embedding throughput and exit
+    if std::env::args().any(|arg| arg == "--bench-embedding") {
+        return bench::run_embedding_benchmark().await;
+    }
+
+    // Maintenance: recompute stored embeddings with the current model and exit
+    if std::env::args().any(|arg| arg == "--reembed-all") {
+        return reembed::run_reembed().await;
+    }
+
+    // Analytics export: decision history to Parquet and exit
+    if std::env::args().any(|arg| arg == "--export-decisions") {
+        return export::run_export().await;
+    }
+
+    // Load and validate all configuration up front, a bad value should stop
+    // startup rather than surface in a handler
+    let config = Config::load()?;
+    tracing::info!("Scoring model version {}", config.model_version());
+    if config.override_mode != kill_switch::OverrideMode::Normal {
+        tracing::warn!("🚨 Starting with decision override {:?}", config.override_mode);
+    }
+
+    // Before any pool exists, read-only pools refuse writes themselves
+    db::read_only::set_read_only(config.read_only);
+    if config.read_only {
+        tracing::warn!("🔒 Read-only mode: nothing will be written to the database");
+    }
+
     // Load database pool
-    let database_url = std::env::var("DATABASE_URL")?;
-    let pool = crate::db::pool::create_pool(&database_url).await?;
+    let pool = crate::db::pool::create_pool(&config.database_url, config.pool_max_connections).await?;
+    let read_pool = match &config.database_read_url {
+        Some(read_url) => {
+            tracing::info!("Routing agent queries to the read replica");
+            crate::db::pool::create_pool(read_url, config.pool_max_connections).await?
+        }
+        None => pool.clone(),
+    };
 
-    //call function to load gemma model
-    let (tensors, tokenizers, device) = load_model().await?;
+    // Make sure similarity queries don't fall back to sequential scans.
+    // `--ensure-vector-indexes` does only this and exits
+    let ensure_only = std::env::args().any(|arg| arg == "--ensure-vector-indexes");
+    if ensure_only || config.vector_index.ensure_at_startup {
+        let created = crate::db::schema::ensure_vector_indexes(
+            &pool,
+            config.vector_index.index_type,
+            &config.vector_index.params,
+            config.embedding_distance_metric,
+        )
+        .await?;
+        tracing::info!("Vector indexes in place, created {:?}", created);
+        if ensure_only {
+            return Ok(());
+        }
+    }
 
-    //declare the listener
-    let port = env::var("PORT");
-    let address = format!("0.0.0.0:{}", port.unwrap_or("2008".to_string()));
-    let listener = TcpListener::bind(address.clone()).await.unwrap();
+    crate::db::schema::check_embedding_normalization(&read_pool, config.embedding_normalize).await?;
+
+    //call function to load gemma model
+    let (tensors, tokenizers, device) = load_model(&config.embedding_model)?;
+    let embedder = CandleEmbedder::new(config.embedding_model.model_name.clone(), tensors, tokenizers, device)
+        .with_normalize(config.embedding_normalize);
 
     //declare appstate
     let app_state = AppState {
         pool: pool.clone(),
-        tensors: Arc::new(tensors),
-        tokenizer: Arc::new(tokenizers),
-        device,
+        read_pool,
+        embedder: Arc::new(ArcSwap::from_pointee(Box::new(embedder) as Box<dyn EmbeddingProvider>)),
+        embedding_permits: Arc::new(Semaphore::new(config.embedding_max_concurrency)),
+        population: Arc::new(PopulationStats::new(
+            Duration::from_secs(config.population_stats_ttl_secs),
+            config.category_taxonomy.clone(),
+        )),
+        merchant_similarity: Arc::new(SimilarRiskyMerchants::new(Duration::from_secs(
+            config.merchant_similarity_ttl_secs,
+        ))),
+        user_overrides: Arc::new(UserOverrideCache::new(Duration::from_secs(
+            config.user_overrides_ttl_secs,
+        ))),
+        analyzer: Arc::new(ArcSwap::from_pointee(FraudAnalyzer::new(&config)?)),
+        kill_switch: Arc::new(KillSwitch::new(config.override_mode)),
+        breakers: Arc::new(CircuitBreakers::new(config.circuit_breaker.clone())),
+        config: Arc::new(config.clone()),
     };
+
+    // Scoring settings in CONFIG_FILE apply live, without a restart
+    if let Some(path) = config::config_file_path() {
+        reload::spawn_config_watcher(app_state.clone(), path)?;
+    }
+
+    // Run one throwaway embedding so the first real request doesn't pay
+    // for candle's lazy initialization
+    let warmup_start = Instant::now();
+    match embedding::generate_embedding_internal(&app_state, "warmup".to_string()).await {
+        Ok(_) => tracing::info!(
+            "🔥 Embedding warmup ({}) took {:?}",
+            app_state.embedder.load().name(),
+            warmup_start.elapsed()
+        ),
+        Err(e) => tracing::warn!("⚠️ Embedding warmup failed: {}", e),
+    }
+
     //cors
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -126,15 +287,68 @@ async fn main() -> anyhow::Result<()> {
     // seed_data::seed_database(&app_state).await?;
     // println!("-->Database seeding completed!");
 
+    // Request hardening: oversized bodies get 413, slow bodies and slow
+    // requests get 408
+    let max_body_bytes = config.max_request_body_bytes;
+    let request_timeout = Duration::from_secs(config.request_timeout_secs);
+    let admin_request_timeout = Duration::from_secs(config.admin_request_timeout_secs);
+
     //app router and handlers
-    let app = Router::new()
+    let api = Router::new()
         .route("/", get(serve_ui))
+        .route("/health", get(circuit_breaker::health))
         .route("/api/pattern", post(test_pattern_agent))
         .route("/api/analyze", post(analyze_transaction))
+        .route("/api/embedding/debug", post(embedding::debug_embedding))
+        .route("/api/feedback/challenge", post(feedback::record_challenge_outcome))
+        .route("/api/users/{user_id}/profile", get(profile::user_profile))
+        .route("/api/transactions/search", post(transactions::search_transactions))
+        .route("/api/transactions/{transaction_id}", patch(transactions::patch_transaction))
+        .route(
+            "/api/transactions/{transaction_id}/history",
+            get(transactions::transaction_history),
+        )
+        .layer(TimeoutLayer::new(request_timeout));
+
+    // Admin jobs scan stored history and get their own, longer limit
+    let admin = Router::new()
+        .route("/api/admin/roc", get(evaluation::threshold_tradeoff))
+        .route("/api/admin/reanalyze", post(evaluation::reanalyze))
+        .route("/api/admin/backtest", post(evaluation::backtest))
+        .route("/api/admin/agent-contributions", get(evaluation::agent_contributions))
+        .route("/api/admin/merchant-denylist", get(denylist::list_denylist))
+        .route(
+            "/api/admin/merchant-denylist/{merchant}",
+            put(denylist::add_to_denylist).delete(denylist::remove_from_denylist),
+        )
+        .route("/api/admin/user-overrides", get(user_overrides::list_user_overrides))
+        .route(
+            "/api/admin/user-overrides/{user_id}",
+            put(user_overrides::set_user_override).delete(user_overrides::remove_user_override),
+        )
+        .route(
+            "/api/admin/override",
+            get(kill_switch::get_override).put(kill_switch::set_override),
+        )
+        .layer(TimeoutLayer::new(admin_request_timeout))
+        // Loading a model can take longer than any request, reloads are
+        // serialized instead
+        .route("/api/admin/reload-model", post(embedding::reload_model))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), admin_auth::require_admin));
+
+    let app = api
+        .merge(admin)
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(max_body_bytes))
+        .layer(RequestBodyTimeoutLayer::new(request_timeout))
         .layer(CompressionLayer::new())
         .layer(cors)
         .with_state(app_state);
 
+    //declare the listener only once the model is warm
+    let address = format!("0.0.0.0:{}", config.port);
+    let listener = TcpListener::bind(address.clone()).await.unwrap();
+
     //server the api
     tracing::info!("Server listening on {}", address);
 
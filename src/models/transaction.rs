@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, HashMap};
+
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
@@ -7,6 +9,21 @@ pub struct Location {
     pub country: String,
     pub lat: f64,
     pub lon: f64,
+    /// Radius of the geolocation's uncertainty (GPS fix, IP lookup), if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuracy_km: Option<f64>,
+}
+
+impl Location {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.lat) || !(-180.0..=180.0).contains(&self.lon) {
+            return Err("location lat/lon out of range".to_string());
+        }
+        if matches!(self.accuracy_km, Some(accuracy) if !accuracy.is_finite() || accuracy < 0.0) {
+            return Err("location accuracy_km must be a non-negative number".to_string());
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,61 +32,694 @@ pub struct Transaction {
     pub user_id: String,
     pub amount: f64,
     pub merchant: String,
+    /// Acquirer's stable id for the merchant. Keys the merchant lookup when
+    /// present, so reputation survives renames of `merchant`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merchant_id: Option<String>,
     pub merchant_category: String,
-    pub location: Location,
+    /// Absent for card-not-present flows without geolocation
+    pub location: Option<Location>,
     pub timestamp: DateTime<Utc>,
     pub payment_method: String,
     pub device_fingerprint: String,
+    /// Free-form attributes (channel, session_id, card BIN, acquirer, ...)
+    /// kept for investigation. Agents don't read it
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionRequest {
+    /// Client-side id, lets a resubmission be recognized; generated when absent
+    #[serde(default)]
+    pub transaction_id: Option<String>,
     pub user_id: String,
     pub amount: f64,
     pub merchant: String,
+    /// Acquirer's stable merchant id, preferred over the name for the merchant lookup
+    #[serde(default)]
+    pub merchant_id: Option<String>,
     pub merchant_category: String,
-    pub location: Location,
+    #[serde(default)]
+    pub location: Option<Location>,
     pub payment_method: String,
     pub device_fingerprint: String,
+    /// Free-form attributes stored with the transaction, must be a JSON object
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    /// Locale for the reason strings, e.g. "es". Takes precedence over `Accept-Language`
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// When the transaction happened, for queued or replayed transactions.
+    /// Defaults to the time it is received
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
 }
 
+/// How far ahead of the server clock a request timestamp may be, for clock skew
+const MAX_FUTURE_TIMESTAMP_SECS: i64 = 300;
+
 impl TransactionRequest {
+    /// Reject requests that can never be scored meaningfully
+    pub fn validate(&self) -> Result<(), String> {
+        if matches!(&self.transaction_id, Some(id) if id.trim().is_empty()) {
+            return Err("transaction_id must not be empty when given".to_string());
+        }
+        if self.user_id.trim().is_empty() {
+            return Err("user_id must not be empty".to_string());
+        }
+        if self.merchant.trim().is_empty() {
+            return Err("merchant must not be empty".to_string());
+        }
+        if matches!(&self.merchant_id, Some(id) if id.trim().is_empty()) {
+            return Err("merchant_id must not be empty when given".to_string());
+        }
+        if !self.amount.is_finite() || self.amount < 0.0 {
+            return Err(format!("amount must be a non-negative number, got {}", self.amount));
+        }
+        if matches!(&self.metadata, Some(metadata) if !metadata.is_object()) {
+            return Err("metadata must be a JSON object".to_string());
+        }
+        if let Some(timestamp) = self.timestamp
+            && timestamp > Utc::now() + chrono::Duration::seconds(MAX_FUTURE_TIMESTAMP_SECS)
+        {
+            return Err(format!("timestamp {} is in the future", timestamp.to_rfc3339()));
+        }
+        if let Some(location) = &self.location {
+            location.validate()?;
+        }
+        Ok(())
+    }
+
     pub fn to_transaction(&self) -> Transaction {
         Transaction {
-            transaction_id: uuid::Uuid::new_v4().to_string(),
+            transaction_id: self
+                .transaction_id
+                .clone()
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
             user_id: self.user_id.clone(),
             amount: self.amount,
             merchant: self.merchant.clone(),
+            merchant_id: self.merchant_id.clone(),
             merchant_category: self.merchant_category.clone(),
             location: self.location.clone(),
-            timestamp: Utc::now(),
+            timestamp: self.timestamp.unwrap_or_else(Utc::now),
             payment_method: self.payment_method.clone(),
             device_fingerprint: self.device_fingerprint.clone(),
+            metadata: self.metadata.clone(),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentScores {
     pub pattern: f64,
     pub anomaly: f64,
     pub geographic: f64,
     pub merchant: f64,
+    pub network: f64,
+}
+
+/// Weight of each agent in the aggregate score, before any confidence
+/// scaling, renormalized over the agents that count. The global weights
+/// must sum to 1, category overrides needn't
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentWeights {
+    pub pattern: f64,
+    pub anomaly: f64,
+    pub geographic: f64,
+    pub merchant: f64,
+    pub network: f64,
+}
+
+impl Default for AgentWeights {
+    fn default() -> Self {
+        Self {
+            pattern: 0.25,
+            anomaly: 0.20,
+            geographic: 0.15,
+            merchant: 0.25,
+            network: 0.15,
+        }
+    }
+}
+
+/// Slack on the sum of the global weights, for decimal rounding
+const WEIGHT_SUM_TOLERANCE: f64 = 1e-6;
+
+impl AgentWeights {
+    pub fn as_array(&self) -> [f64; 5] {
+        [self.pattern, self.anomaly, self.geographic, self.merchant, self.network]
+    }
+
+    /// Every weight non-negative and the five summing to 1
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.as_array().iter().all(|w| w.is_finite() && *w >= 0.0) {
+            return Err(format!("weights must be non-negative numbers, got {:?}", self));
+        }
+        let total: f64 = self.as_array().iter().sum();
+        if (total - 1.0).abs() > WEIGHT_SUM_TOLERANCE {
+            return Err(format!("weights must sum to 1.0, got {}", total));
+        }
+        Ok(())
+    }
+}
+
+/// The weights an analysis was aggregated with
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedWeights {
+    /// Category whose override was used, `None` for the global weights
+    pub category: Option<String>,
+    pub weights: AgentWeights,
 }
 
-#[derive(Debug, Serialize)]
+/// Final verdict for a transaction, serialized as "APPROVE" / "CHALLENGE" / "BLOCK".
+/// Ordered by severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Decision {
+    Approve,
+    Challenge,
+    Block,
+}
+
+impl Decision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Decision::Approve => "APPROVE",
+            Decision::Challenge => "CHALLENGE",
+            Decision::Block => "BLOCK",
+        }
+    }
+}
+
+impl std::str::FromStr for Decision {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "APPROVE" => Ok(Decision::Approve),
+            "CHALLENGE" => Ok(Decision::Challenge),
+            "BLOCK" => Ok(Decision::Block),
+            other => Err(format!("Unknown decision '{}'", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Decision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Which rule produced the final decision, serialized in snake_case
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionSource {
+    /// Network agent detected a fraud ring and forced a BLOCK
+    FraudRing,
+    /// Aggregate score against the challenge/block thresholds
+    Threshold,
+    /// Previous decision kept by hysteresis
+    Hysteresis,
+    /// APPROVE downgraded to CHALLENGE for lack of data
+    DataSufficiency,
+    /// Operator override (kill switch) replaced the scored decision
+    Override,
+    /// Amount below the analysis threshold, approved without running the agents
+    BelowAnalysisThreshold,
+    /// No agent finished within the analysis deadline, challenged by default
+    Deadline,
+    /// Merchant is on the denylist, blocked without running the agents
+    MerchantDenylist,
+    /// Operator-set action for this user, decided without running the agents
+    UserOverride,
+    /// A registered `DecisionPostProcessor` changed the decision
+    PostProcessor,
+}
+
+/// Coarse bucket of the aggregated risk score, serialized as "LOW" / "MEDIUM" / "HIGH"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RiskBand {
+    Low,
+    Medium,
+    High,
+}
+
+/// How strong the evidence for a fraud ring is, serialized as
+/// "SUSPECTED" / "LIKELY" / "CONFIRMED". Ordered weakest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RingSeverity {
+    /// One ring signal, just past its threshold
+    Suspected,
+    /// A ring signal well past its threshold
+    Likely,
+    /// Several ring signals at once, or one far past its threshold
+    Confirmed,
+}
+
+impl std::str::FromStr for RingSeverity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_uppercase().as_str() {
+            "SUSPECTED" => Ok(RingSeverity::Suspected),
+            "LIKELY" => Ok(RingSeverity::Likely),
+            "CONFIRMED" => Ok(RingSeverity::Confirmed),
+            other => Err(format!(
+                "Unknown ring severity '{}', expected SUSPECTED, LIKELY or CONFIRMED",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct AnalysisResult {
-    pub decision: String,
+    pub transaction_id: String,
+    pub decision: Decision,
+    pub decision_source: DecisionSource,
+    /// What scoring decided, when an operator override replaced it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overridden_decision: Option<Decision>,
+    pub risk_score: f64,
+    /// Weighted average of the agent scores alone, before the payment method
+    /// prior, the cool-down and any rule that overrides the threshold
+    /// decision. 0 when the agents didn't run
+    pub raw_aggregate_score: f64,
+    pub risk_band: RiskBand,
     pub confidence: f64,
+    /// 0-1, how much evidence (history, embeddings) the decision rests on
+    pub data_sufficiency: f64,
+    /// Risk added to the aggregate for the payment method
+    pub payment_method_risk: f64,
+    /// Version of the scoring logic and configuration that produced this result
+    pub model_version: String,
     pub latency_ms: u64,
     pub agent_scores: AgentScores,
     pub fraud_ring_detected: bool,
+    /// Strength of the ring evidence, when a ring was detected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fraud_ring_severity: Option<RingSeverity>,
+    /// The previous decision for this transaction was kept because the
+    /// score didn't move far enough past the threshold
+    pub hysteresis_applied: bool,
+    /// The score was dampened because a recent challenge for this user and
+    /// merchant was cleared as legitimate
+    pub cooldown_applied: bool,
+    /// Operator-set multiplier applied to this user's score
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_risk_multiplier: Option<f64>,
     pub reasoning: String,
+    /// Per-agent reasons with machine-readable codes, `reasoning` flattened
+    pub agent_reasons: Vec<AgentReason>,
+    /// Some agents didn't finish within the analysis deadline, or failed,
+    /// and the decision was made without them. Their `agent_scores` read 0
+    pub partial: bool,
+    /// Agents cut off by the deadline
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub timed_out_agents: Vec<String>,
+    /// Agents left out of the aggregate for lack of data
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub excluded_agents: Vec<String>,
+    /// How each agent's run ended, keyed by agent. Explains an aggregate
+    /// taken over fewer than all five agents. Empty when the agents didn't run
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub agent_status: HashMap<String, AgentStatus>,
+    /// The transaction and result are committed to the database. False when
+    /// the write failed, or is still queued with asynchronous persistence
+    pub persisted: bool,
+    /// HMAC over transaction_id, decision and time, when a signing secret is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<crate::signing::DecisionSignature>,
+    /// Embedding of the transaction computed by the pattern agent, kept for persistence
+    #[serde(skip)]
+    pub embedding: Option<Vec<f32>>,
+    /// Agent weights the aggregate used, reported in verbose responses.
+    /// `None` when the agents didn't run
+    #[serde(skip)]
+    pub applied_weights: Option<AppliedWeights>,
+}
+
+/// Scale the scores of an analysis response are reported on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreScale {
+    /// 0.0-1.0 floats
+    #[default]
+    Unit,
+    /// 0-100 integers, for risk engines that expect them
+    Percent,
+}
+
+/// How much of the analysis an analyze response carries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verbosity {
+    /// Decision and scores only, no per-agent reasons
+    Terse,
+    #[default]
+    Normal,
+    /// Adds each agent's `details` to its entry in `agent_reasons`, and the
+    /// agent weights the aggregate used
+    Verbose,
+}
+
+/// Casing of the field names in an analysis response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldCase {
+    /// `risk_score`, `agent_scores`
+    #[default]
+    Snake,
+    /// `riskScore`, `agentScores`, for JS/TS clients
+    Camel,
+}
+
+impl std::str::FromStr for FieldCase {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "snake" | "snake_case" => Ok(FieldCase::Snake),
+            "camel" | "camelcase" => Ok(FieldCase::Camel),
+            other => Err(format!("Unknown field case '{}', expected snake or camel", other)),
+        }
+    }
+}
+
+/// Fields whose value is a map keyed by data (agent names, detail and
+/// placeholder names), not by struct fields. The field itself is renamed,
+/// the keys inside are left as they are
+const DATA_KEYED_FIELDS: &[&str] = &["details", "agent_status", "params", "metadata"];
+
+impl FieldCase {
+    /// Rename the struct field names in `value`, nested objects and arrays
+    /// included, leaving the keys of `DATA_KEYED_FIELDS` alone
+    pub fn apply(self, value: &mut serde_json::Value) {
+        if self == FieldCase::Snake {
+            return;
+        }
+        match value {
+            serde_json::Value::Object(object) => {
+                let renamed = std::mem::take(object)
+                    .into_iter()
+                    .map(|(key, mut nested)| {
+                        if !DATA_KEYED_FIELDS.contains(&key.as_str()) {
+                            self.apply(&mut nested);
+                        }
+                        (to_camel_case(&key), nested)
+                    })
+                    .collect();
+                *object = renamed;
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|item| self.apply(item)),
+            _ => {}
+        }
+    }
+}
+
+fn to_camel_case(key: &str) -> String {
+    let mut camel = String::with_capacity(key.len());
+    let mut upper_next = false;
+    for c in key.chars() {
+        if c == '_' {
+            // Leading underscores have nothing to join
+            upper_next = !camel.is_empty();
+        } else if upper_next {
+            camel.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
+
+impl AnalysisResult {
+    /// Reason code of the highest-scoring agent that gave one
+    pub fn top_reason_code(&self) -> Option<ReasonCode> {
+        self.agent_reasons
+            .iter()
+            .filter(|reason| !reason.reason_codes.is_empty())
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+            .map(|reason| reason.reason_codes[0])
+    }
+
+    /// One `key=value` line for high-volume logs, e.g.
+    /// `txn=t-1 decision=BLOCK score=0.82 top_reason=IMPOSSIBLE_TRAVEL latency_ms=41 model=...`
+    pub fn log_summary(&self) -> String {
+        let top_reason = match self.top_reason_code() {
+            Some(code) => serde_json::to_value(code)
+                .ok()
+                .and_then(|value| value.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            None => "none".to_string(),
+        };
+        format!(
+            "txn={} decision={} score={:.2} top_reason={} latency_ms={} model={}",
+            self.transaction_id, self.decision, self.risk_score, top_reason, self.latency_ms, self.model_version
+        )
+    }
+
+    /// Trim or extend a serialized result (from `serde_json::to_value` or
+    /// `to_percent_json`) to the requested verbosity
+    pub fn apply_verbosity(&self, value: &mut serde_json::Value, verbosity: Verbosity) {
+        match verbosity {
+            Verbosity::Terse => {
+                if let Some(object) = value.as_object_mut() {
+                    object.remove("agent_reasons");
+                    object.remove("agent_status");
+                }
+            }
+            Verbosity::Normal => {}
+            Verbosity::Verbose => {
+                if let Some(agent_reasons) = value["agent_reasons"].as_array_mut() {
+                    for (entry, agent_reason) in agent_reasons.iter_mut().zip(&self.agent_reasons) {
+                        entry["details"] = agent_reason.details.clone();
+                    }
+                }
+                if let Some(applied) = &self.applied_weights {
+                    value["agent_weights"] = serde_json::to_value(applied).unwrap_or_default();
+                }
+            }
+        }
+    }
+
+    /// The response with the aggregate, payment method and agent scores as
+    /// 0-100 integers. Confidence and data sufficiency keep their 0-1 scale
+    pub fn to_percent_json(&self) -> serde_json::Value {
+        let percent = |score: f64| serde_json::Value::from((score.clamp(0.0, 1.0) * 100.0).round() as i64);
+
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        value["risk_score"] = percent(self.risk_score);
+        value["raw_aggregate_score"] = percent(self.raw_aggregate_score);
+        value["payment_method_risk"] = percent(self.payment_method_risk);
+        value["agent_scores"] = serde_json::json!({
+            "pattern": percent(self.agent_scores.pattern),
+            "anomaly": percent(self.agent_scores.anomaly),
+            "geographic": percent(self.agent_scores.geographic),
+            "merchant": percent(self.agent_scores.merchant),
+            "network": percent(self.agent_scores.network),
+        });
+        if let Some(agent_reasons) = value["agent_reasons"].as_array_mut() {
+            for (entry, agent_reason) in agent_reasons.iter_mut().zip(&self.agent_reasons) {
+                entry["score"] = percent(agent_reason.score);
+            }
+        }
+        value
+    }
 }
 
 #[derive(Debug, Serialize, Clone)]
 pub struct AgentScore {
     pub risk_score: f64,
     pub reason: String,
+    /// Each signal that fired with the values its message needs, in the same
+    /// order as `reason`
+    pub reasons: Vec<Reason>,
+    /// 0-1, how much the agent trusts its own score given the data it had.
+    /// 1.0 unless the agent was working from thin data
+    pub confidence: f64,
+    /// The agent had the data its checks need (history, locations, a known
+    /// merchant). When false, a low score means "couldn't tell", not "safe"
+    pub data_available: bool,
+    /// What was missing, when data wasn't available
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_note: Option<String>,
+    pub details: serde_json::Value,
+    /// Transaction embedding, for agents that compute one
+    #[serde(skip)]
+    pub embedding: Option<Vec<f32>>,
+}
+
+impl AgentScore {
+    /// The agent couldn't judge and added no risk. An agent that scores the
+    /// missing data itself (an unknown merchant, a missing location) still
+    /// counts, its risk isn't a default
+    pub fn abstains_for_lack_of_data(&self) -> bool {
+        !self.data_available && self.risk_score <= 0.0
+    }
+}
+
+/// Stable identifier for a risk signal, serialized in SCREAMING_SNAKE_CASE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReasonCode {
+    // Pattern
+    AmountDeviation,
+    PopulationAmountOutlier,
+    AmountPercentileOutlier,
+    NewCategory,
+    HighRiskCategory,
+    SimilarToFraud,
+    GlobalFraudMatch,
+    ProfileNovelty,
+    // Anomaly
+    HighVelocity,
+    UnusualHour,
+    LargeAmountAtUnusualHour,
+    RapidSuccession,
+    AmountSpike,
+    NewDevice,
+    RoundAmount,
+    // Geographic
+    MissingLocation,
+    UnknownLocation,
+    ImpossibleTravel,
+    UnlikelyTravel,
+    NewCountry,
+    // Merchant
+    HighRiskMerchant,
+    ElevatedRiskMerchant,
+    /// Elevated against the merchant's category baseline
+    ElevatedRiskForCategory,
+    NewMerchant,
+    UnrecognizedMerchant,
+    MerchantFraudReports,
+    SimilarRiskyMerchants,
+    MerchantDenylisted,
+    /// Declared category differs from the merchant's known category
+    CategoryMismatch,
+    /// Unknown merchant whose name doesn't fit the declared category
+    UnlikelyCategory,
+    // Network
+    SharedDevice,
+    SharedDeviceRing,
+    CoordinatedMerchantActivity,
+    DeviceVelocityRing,
+    /// One device hitting many merchants in a burst
+    CardTesting,
+}
+
+/// A signal that fired, with the values its message is rendered from.
+/// `reason` strings are rendered from these by `i18n`, in English unless the
+/// caller asked for another locale
+#[derive(Debug, Clone, Serialize)]
+pub struct Reason {
+    pub code: ReasonCode,
+    pub params: BTreeMap<&'static str, String>,
+}
+
+impl Reason {
+    pub fn new(code: ReasonCode) -> Self {
+        Self {
+            code,
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Add a value for the `{name}` placeholder, already formatted for display
+    pub fn with(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.params.insert(name, value.to_string());
+        self
+    }
+}
+
+/// How an agent's run ended, serialized as `{"status": "abstained", "detail": "..."}`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", content = "detail", rename_all = "snake_case")]
+pub enum AgentStatus {
+    /// Finished, its score counts in the aggregate
+    Ran,
+    /// Finished without data and was left out of the aggregate, with its data note
+    Abstained(String),
+    /// Cut off by the analysis deadline
+    TimedOut,
+    /// Failed, the decision was made without it
+    Errored(String),
+}
+
+impl AgentStatus {
+    /// Short form for the reasoning string and logs
+    pub fn label(&self) -> &'static str {
+        match self {
+            AgentStatus::Ran => "ran",
+            AgentStatus::Abstained(_) => "abstained",
+            AgentStatus::TimedOut => "timed out",
+            AgentStatus::Errored(_) => "failed",
+        }
+    }
+}
+
+/// One agent's contribution to the decision, for clients that need more than the flat string
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentReason {
+    pub agent: String,
+    pub score: f64,
+    pub reason: String,
+    pub reason_codes: Vec<ReasonCode>,
+    pub data_available: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_note: Option<String>,
+    /// What `reason` was rendered from, kept to re-render it in another locale
+    #[serde(skip)]
+    pub reasons: Vec<Reason>,
+    /// The agent's full breakdown, only returned to verbose requests
+    #[serde(skip)]
     pub details: serde_json::Value,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camel_case_joins_underscored_words() {
+        assert_eq!(to_camel_case("risk_score"), "riskScore");
+        assert_eq!(to_camel_case("agent_reasons"), "agentReasons");
+        assert_eq!(to_camel_case("_private"), "private");
+        assert_eq!(to_camel_case("partial"), "partial");
+    }
+
+    #[test]
+    fn camel_case_renames_struct_fields_only() {
+        let mut body = serde_json::json!({
+            "risk_score": 0.4,
+            "agent_status": {"pattern": "ran", "geographic_fallback": "ran"},
+            "agent_reasons": [{
+                "reason_codes": ["SHARED_DEVICE"],
+                "details": {"similar_risky_matches": [{"merchant_name": "m"}]},
+                "reasons": [{"code": "SHARED_DEVICE", "params": {"user_count": "3"}}],
+            }],
+        });
+        FieldCase::Camel.apply(&mut body);
+
+        assert_eq!(body["riskScore"], 0.4);
+        assert_eq!(body["agentStatus"]["geographic_fallback"], "ran");
+        let reason = &body["agentReasons"][0];
+        assert_eq!(reason["reasonCodes"][0], "SHARED_DEVICE");
+        assert_eq!(reason["details"]["similar_risky_matches"][0]["merchant_name"], "m");
+        assert_eq!(reason["reasons"][0]["params"]["user_count"], "3");
+    }
+
+    #[test]
+    fn snake_case_leaves_the_body_alone() {
+        let mut body = serde_json::json!({"risk_score": 0.4});
+        FieldCase::Snake.apply(&mut body);
+        assert_eq!(body, serde_json::json!({"risk_score": 0.4}));
+    }
+}
@@ -0,0 +1,59 @@
+//! Extension point for deployment-specific rules that don't belong in the
+//! agents, e.g. "block anything over $5,000 from accounts younger than a
+//! week". Implement [`DecisionPostProcessor`] and register it on the
+//! analyzer with [`FraudAnalyzer::with_post_processors`]; processors run in
+//! registration order after every analysis, gated and overridden ones
+//! included, and survive configuration reloads.
+//!
+//! [`FraudAnalyzer::with_post_processors`]: crate::analysis::FraudAnalyzer::with_post_processors
+
+use std::sync::Arc;
+
+use crate::{
+    AppState,
+    models::transaction::{AnalysisResult, DecisionSource, Transaction},
+};
+
+/// What a post-processor can look at besides the result
+pub struct AnalysisContext<'a> {
+    pub transaction: &'a Transaction,
+    /// Configuration, pools and caches, for rules that need them
+    pub state: &'a AppState,
+}
+
+/// A custom rule applied to the orchestrator's result
+pub trait DecisionPostProcessor: Send + Sync {
+    /// Return the result, adjusted or as is. A processor that changes the
+    /// decision without setting `decision_source` gets
+    /// `DecisionSource::PostProcessor`
+    fn adjust(&self, ctx: &AnalysisContext<'_>, result: AnalysisResult) -> AnalysisResult;
+
+    /// Shown in logs
+    fn name(&self) -> &str;
+}
+
+/// Run `result` through each processor in order
+pub fn apply_chain(
+    processors: &[Arc<dyn DecisionPostProcessor>],
+    ctx: &AnalysisContext<'_>,
+    mut result: AnalysisResult,
+) -> AnalysisResult {
+    for processor in processors {
+        let (decision, source) = (result.decision, result.decision_source);
+        result = processor.adjust(ctx, result);
+
+        if result.decision != decision {
+            if result.decision_source == source {
+                result.decision_source = DecisionSource::PostProcessor;
+            }
+            tracing::info!(
+                "🧩 Post-processor {} changed {} from {} to {}",
+                processor.name(),
+                ctx.transaction.transaction_id,
+                decision,
+                result.decision
+            );
+        }
+    }
+    result
+}
@@ -0,0 +1,57 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::Serialize;
+
+use crate::{
+    AppState,
+    db::profile::{
+        self, CategorySpending, DeviceUsage, RecentDecision, RecentLocation, TransactionCounts,
+        UserRecord,
+    },
+    error::ApiError,
+};
+
+/// Locations and decisions included in a profile export
+const PROFILE_RECENT_LIMIT: i64 = 20;
+
+/// Everything the agents know about a user, in one place
+#[derive(Debug, Serialize)]
+pub struct UserProfile {
+    pub user: UserRecord,
+    pub category_baseline: Vec<CategorySpending>,
+    pub recent_locations: Vec<RecentLocation>,
+    pub devices: Vec<DeviceUsage>,
+    pub transactions: TransactionCounts,
+    pub recent_decisions: Vec<RecentDecision>,
+}
+
+/// GET /api/users/{user_id}/profile - full fraud profile for access requests and debugging
+pub async fn user_profile(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Result<Json<UserProfile>, ApiError> {
+    let pool = &state.read_pool;
+
+    let user = profile::fetch_user(pool, &user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Unknown user '{}'", user_id)))?;
+
+    let (category_baseline, recent_locations, devices, transactions, recent_decisions) = tokio::try_join!(
+        profile::fetch_category_spending(pool, &user_id),
+        profile::fetch_recent_locations(pool, &user_id, PROFILE_RECENT_LIMIT),
+        profile::fetch_devices(pool, &user_id),
+        profile::fetch_transaction_counts(pool, &user_id),
+        profile::fetch_recent_decisions(pool, &user_id, PROFILE_RECENT_LIMIT),
+    )?;
+
+    Ok(Json(UserProfile {
+        user,
+        category_baseline,
+        recent_locations,
+        devices,
+        transactions,
+        recent_decisions,
+    }))
+}
@@ -0,0 +1,223 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use anyhow::{Context, Result, anyhow};
+use futures::{StreamExt, TryStreamExt};
+use sqlx::PgPool;
+
+use crate::{
+    agents::pattern::describe,
+    config::{self, Config},
+    db::{read_only, transactions::StoredTransaction},
+    embedding::{
+        CandleEmbedder, EmbeddingPrecision, EmbeddingPrefixes, EmbeddingProvider,
+        embedding_to_pgvector, load_model,
+    },
+};
+
+/// Limits and checkpointing for a re-embed run
+#[derive(Debug, Clone)]
+pub struct ReembedOptions {
+    /// Transactions fetched, embedded and committed together
+    pub batch_size: i64,
+    /// Embeddings computed at once
+    pub concurrency: usize,
+    /// Holds the last committed transaction_id, a rerun resumes after it
+    pub checkpoint: PathBuf,
+    pub prefixes: EmbeddingPrefixes,
+    pub precision: EmbeddingPrecision,
+}
+
+/// Progress after each committed batch
+#[derive(Debug, Clone, Copy)]
+pub struct ReembedProgress {
+    pub processed: u64,
+    pub total: u64,
+    pub elapsed_secs: f64,
+}
+
+impl ReembedProgress {
+    /// Seconds left at the rate so far, `None` before there is a rate
+    pub fn eta_secs(&self) -> Option<f64> {
+        (self.processed > 0).then(|| {
+            let rate = self.processed as f64 / self.elapsed_secs.max(f64::EPSILON);
+            self.total.saturating_sub(self.processed) as f64 / rate
+        })
+    }
+}
+
+/// Recompute every stored transaction embedding with the current model and
+/// prefixes, `options.batch_size` rows per commit and at most
+/// `options.concurrency` embeddings in flight. Walks transactions in
+/// `transaction_id` order and checkpoints after every batch, so a crashed
+/// run picks up where it stopped. Spending centroids are rebuilt from the
+/// new embeddings at the end. Returns the number of transactions re-embedded
+pub async fn reembed_all(
+    pool: &PgPool,
+    embedder: Arc<dyn EmbeddingProvider>,
+    options: &ReembedOptions,
+    mut on_progress: impl FnMut(ReembedProgress),
+) -> Result<u64> {
+    read_only::ensure_writable("re-embedding transactions")?;
+
+    let mut cursor = read_checkpoint(&options.checkpoint)?;
+    if let Some(last) = &cursor {
+        tracing::info!("Resuming re-embed after transaction {}", last);
+    }
+
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM transactions WHERE ($1::text IS NULL OR transaction_id > $1)",
+    )
+    .bind(&cursor)
+    .fetch_one(pool)
+    .await? as u64;
+
+    let start = Instant::now();
+    let mut processed = 0u64;
+    loop {
+        let page = sqlx::query_as::<_, StoredTransaction>(&format!(
+            r#"
+            SELECT {}
+            FROM transactions
+            WHERE ($1::text IS NULL OR transaction_id > $1)
+            ORDER BY transaction_id
+            LIMIT $2
+            "#,
+            crate::db::transactions::STORED_TRANSACTION_COLUMNS
+        ))
+        .bind(&cursor)
+        .bind(options.batch_size)
+        .fetch_all(pool)
+        .await?;
+        let Some(last) = page.last().map(|row| row.transaction_id.clone()) else {
+            break;
+        };
+
+        // The provider runs the CPU-bound embeddings on blocking threads,
+        // buffered to bound how many at once
+        let embeddings: Vec<Vec<f32>> = futures::stream::iter(&page)
+            .map(|row| {
+                let embedder = embedder.clone();
+                let text = format!("{}{}", options.prefixes.document, describe(&row.to_transaction()));
+                async move {
+                    embedder
+                        .embed(&text)
+                        .await
+                        .map_err(|e| anyhow!("Embedding failed: {}", e))
+                }
+            })
+            .buffered(options.concurrency.max(1))
+            .try_collect()
+            .await?;
+
+        let mut tx = pool.begin().await?;
+        for (row, embedding) in page.iter().zip(&embeddings) {
+            sqlx::query(&format!(
+                "UPDATE transactions SET transaction_embedding = $2::{} WHERE transaction_id = $1",
+                options.precision.sql_type()
+            ))
+            .bind(&row.transaction_id)
+            .bind(embedding_to_pgvector(embedding))
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        write_checkpoint(&options.checkpoint, &last)?;
+        processed += page.len() as u64;
+        cursor = Some(last);
+        on_progress(ReembedProgress {
+            processed,
+            total,
+            elapsed_secs: start.elapsed().as_secs_f64(),
+        });
+    }
+
+    // The running centroids were built from the old embeddings
+    sqlx::query(
+        r#"
+        UPDATE users u
+        SET spending_centroid = c.centroid::vector,
+            centroid_count = c.count
+        FROM (
+            SELECT user_id, AVG(transaction_embedding::vector) as centroid, COUNT(*)::int as count
+            FROM transactions
+            WHERE transaction_embedding IS NOT NULL
+            GROUP BY user_id
+        ) c
+        WHERE u.user_id = c.user_id
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Done, the next run starts over
+    if options.checkpoint.exists() {
+        std::fs::remove_file(&options.checkpoint)?;
+    }
+    Ok(processed)
+}
+
+fn read_checkpoint(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text.trim().to_string()).filter(|id| !id.is_empty())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("Can't read checkpoint {}", path.display())),
+    }
+}
+
+fn write_checkpoint(path: &Path, transaction_id: &str) -> Result<()> {
+    // Written aside and renamed, a crash mid-write can't leave it truncated
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, transaction_id)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// Re-embed every stored transaction and exit, e.g. after changing the
+/// embedding model or prefixes.
+///
+/// Run with `cargo run --release -- --reembed-all`, tuned by
+/// `REEMBED_BATCH_SIZE` (default 100), `REEMBED_CONCURRENCY` (default 4) and
+/// `REEMBED_CHECKPOINT_FILE` (default `reembed.checkpoint`).
+pub async fn run_reembed() -> Result<()> {
+    let source = config::layered_source()?;
+    let config = Config::from_source(&source)?;
+    let options = ReembedOptions {
+        batch_size: config::parsed(&source, "REEMBED_BATCH_SIZE", 100i64)?.max(1),
+        concurrency: config::parsed(&source, "REEMBED_CONCURRENCY", 4usize)?.max(1),
+        checkpoint: PathBuf::from(config::string(
+            &source,
+            "REEMBED_CHECKPOINT_FILE",
+            "reembed.checkpoint".to_string(),
+        )),
+        prefixes: config.embedding_prefixes.clone(),
+        precision: config.embedding_precision,
+    };
+    read_only::set_read_only(config.read_only);
+
+    let model_config = &config.embedding_model;
+    let (tensors, tokenizer, device) = load_model(model_config)?;
+    let embedder = Arc::new(
+        CandleEmbedder::new(model_config.model_name.clone(), tensors, tokenizer, device)
+            .with_normalize(config.embedding_normalize),
+    );
+    let pool = crate::db::pool::create_pool(&config.database_url, 4).await?;
+
+    let reembedded = reembed_all(&pool, embedder, &options, |progress| {
+        tracing::info!(
+            "🔁 Re-embedded {}/{} transactions, ETA {}",
+            progress.processed,
+            progress.total,
+            progress
+                .eta_secs()
+                .map_or("unknown".to_string(), |secs| format!("{:.0}s", secs))
+        );
+    })
+    .await?;
+    println!("Re-embedded {} transactions", reembedded);
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! Live reload of the scoring configuration.
+//!
+//! When `CONFIG_FILE` is set, a background task watches it and rebuilds the
+//! [`FraudAnalyzer`] (agent weights, thresholds, windows, every agent
+//! tunable) on each change. The new analyzer is swapped in atomically, so a
+//! request in flight finishes on the configuration it started with. A file
+//! that fails to parse or validate is rejected and the previous analyzer
+//! stays in place, as is one that changes settings read only at startup
+//! (database, storage, embedding prefixes, category aliases, ...).
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::{
+    AppState,
+    analysis::FraudAnalyzer,
+    config::{Config, ConfigError},
+};
+
+/// Editors write a file in several steps, wait for them to settle
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watch `path` and reload the analyzer whenever it changes
+pub fn spawn_config_watcher(state: AppState, path: PathBuf) -> anyhow::Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let file_name = path.file_name().map(|name| name.to_os_string());
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let touches_file = event
+                .paths
+                .iter()
+                .any(|changed| changed.file_name().map(|name| name.to_os_string()) == file_name);
+            if touches_file && !event.kind.is_access() {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+
+    // The directory rather than the file: editors and config-map updates
+    // replace the file, which would end a watch on the file itself
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+    tracing::info!("👀 Watching {} for configuration changes", path.display());
+
+    tokio::spawn(async move {
+        // Dropping the watcher stops it, it lives as long as the task
+        let _watcher = watcher;
+        while rx.recv().await.is_some() {
+            tokio::time::sleep(DEBOUNCE).await;
+            while rx.try_recv().is_ok() {}
+            reload(&state);
+        }
+    });
+
+    Ok(())
+}
+
+/// Rebuild the analyzer from the current configuration, keeping the old one
+/// when the new configuration is invalid or changes settings that only a
+/// restart applies
+pub fn reload(state: &AppState) {
+    let loaded = Config::load().and_then(|config| {
+        let changed = config.restart_only_changes(&state.config);
+        if !changed.is_empty() {
+            return Err(ConfigError::Validation(format!(
+                "{} only take effect on a restart",
+                changed.join(", ")
+            )));
+        }
+        FraudAnalyzer::new(&config).map(|analyzer| (config, analyzer))
+    });
+    match loaded {
+        Ok((config, analyzer)) => {
+            // Registered in code, not configuration: carried over as they are
+            let analyzer = analyzer.with_post_processors(state.analyzer.load().post_processors().to_vec());
+            let previous = state.analyzer.swap(Arc::new(analyzer));
+            tracing::info!(
+                "🔄 Reloaded scoring configuration: model version {} -> {}",
+                previous.model_version(),
+                config.model_version()
+            );
+        }
+        Err(e) => {
+            tracing::error!("❌ Rejected configuration reload, keeping the previous configuration: {}", e);
+        }
+    }
+}
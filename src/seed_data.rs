@@ -1,43 +1,175 @@
-use anyhow::Result;
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
 use chrono::{Utc, Duration};
-use crate::AppState;
+use serde::Deserialize;
+use crate::{AppState, db::read_only};
+
+/// The scenarios seeded when `SEED_SCENARIO_FILE` isn't set
+const DEFAULT_SCENARIOS: &str = include_str!("../data/seed_scenarios.json");
 
+/// Users, merchants and historical transactions to seed, loaded from JSON
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedScenarios {
+    pub users: Vec<SeedUser>,
+    #[serde(default)]
+    pub merchants: Vec<SeedMerchant>,
+    #[serde(default)]
+    pub transactions: Vec<SeedTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedUser {
+    pub user_id: String,
+    pub email: String,
+    pub average_transaction_amount: f64,
+    #[serde(default)]
+    pub common_categories: Vec<String>,
+    /// What the user stands for, printed after seeding
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedMerchant {
+    pub name: String,
+    /// Acquirer's merchant id
+    #[serde(default)]
+    pub merchant_id: Option<String>,
+    pub category: String,
+    pub fraud_rate: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedTransaction {
+    pub user_id: String,
+    pub merchant: String,
+    pub amount: f64,
+    pub category: String,
+    pub fraud: bool,
+    pub days_ago: i64,
+}
+
+impl SeedScenarios {
+    /// Parse and validate scenarios, `origin` names them in errors
+    pub fn parse(json: &str, origin: &str) -> Result<Self> {
+        let scenarios: Self = serde_json::from_str(json)
+            .with_context(|| format!("Invalid seed scenarios in {}", origin))?;
+        scenarios
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Invalid seed scenarios in {}: {}", origin, e))?;
+        Ok(scenarios)
+    }
+
+    /// The file at `path`, or the bundled scenarios
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        match path {
+            Some(path) => {
+                let json = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read seed scenario file {}", path))?;
+                Self::parse(&json, path)
+            }
+            None => Self::parse(DEFAULT_SCENARIOS, "the bundled scenarios"),
+        }
+    }
+
+    /// Reject scenarios the database would refuse halfway through seeding
+    pub fn validate(&self) -> Result<(), String> {
+        let mut user_ids = HashSet::new();
+        for (i, user) in self.users.iter().enumerate() {
+            if user.user_id.trim().is_empty() {
+                return Err(format!("users[{}]: user_id must not be empty", i));
+            }
+            if !user_ids.insert(user.user_id.as_str()) {
+                return Err(format!("users[{}]: duplicate user_id '{}'", i, user.user_id));
+            }
+            if !user.average_transaction_amount.is_finite() || user.average_transaction_amount < 0.0 {
+                return Err(format!(
+                    "users[{}]: average_transaction_amount must be a non-negative number",
+                    i
+                ));
+            }
+        }
+
+        let mut merchant_names = HashSet::new();
+        let mut merchant_ids = HashSet::new();
+        for (i, merchant) in self.merchants.iter().enumerate() {
+            if merchant.name.trim().is_empty() {
+                return Err(format!("merchants[{}]: name must not be empty", i));
+            }
+            if !merchant_names.insert(merchant.name.as_str()) {
+                return Err(format!("merchants[{}]: duplicate name '{}'", i, merchant.name));
+            }
+            if let Some(id) = &merchant.merchant_id {
+                if id.trim().is_empty() {
+                    return Err(format!("merchants[{}]: merchant_id must not be empty when given", i));
+                }
+                if !merchant_ids.insert(id.as_str()) {
+                    return Err(format!("merchants[{}]: duplicate merchant_id '{}'", i, id));
+                }
+            }
+            if !(0.0..=1.0).contains(&merchant.fraud_rate) {
+                return Err(format!("merchants[{}]: fraud_rate must be within 0..=1", i));
+            }
+        }
+
+        for (i, transaction) in self.transactions.iter().enumerate() {
+            if !user_ids.contains(transaction.user_id.as_str()) {
+                return Err(format!(
+                    "transactions[{}]: user_id '{}' is not one of the seeded users",
+                    i, transaction.user_id
+                ));
+            }
+            if transaction.merchant.trim().is_empty() {
+                return Err(format!("transactions[{}]: merchant must not be empty", i));
+            }
+            if !transaction.amount.is_finite() || transaction.amount < 0.0 {
+                return Err(format!("transactions[{}]: amount must be a non-negative number", i));
+            }
+            if transaction.days_ago < 0 {
+                return Err(format!("transactions[{}]: days_ago must not be negative", i));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Seed the scenarios from `SEED_SCENARIO_FILE`, or the bundled ones
 pub async fn seed_database(app_state: &AppState) -> Result<()> {
+    read_only::ensure_writable("seeding")?;
+    let scenarios = SeedScenarios::load(app_state.config.seed_scenario_file.as_deref())?;
     println!("🌱 Seeding FraudSwarm database...\n");
     
     println!("1️⃣ Creating test users...");
-    seed_users(app_state).await?;
-    println!("   -->Created 5 test users\n");
+    seed_users(app_state, &scenarios.users).await?;
+    println!("   -->Created {} test users\n", scenarios.users.len());
     
     println!("2️⃣ Creating merchants...");
-    seed_merchants(app_state).await?;
-    println!("   -->Created 10 merchants\n");
+    seed_merchants(app_state, &scenarios.merchants).await?;
+    println!("   -->Created {} merchants\n", scenarios.merchants.len());
     
     println!("3️⃣ Creating sample transactions...");
-    seed_transactions(app_state).await?;
-    println!("   -->Created 30 sample transactions\n");
+    seed_transactions(app_state, &scenarios.transactions).await?;
+    println!("   -->Created {} sample transactions\n", scenarios.transactions.len());
     
     println!("🎉 Database seeded successfully!");
     println!("\nSample users created:");
-    println!("  - user_normal_123 (normal spending)");
-    println!("  - user_frequent_456 (frequent buyer)");
-    println!("  - user_fraud_789 (fraudulent activity)");
-    println!("  - user_traveler_321 (business traveler)");
-    println!("  - user_business_654 (high-value transactions)");
+    for user in &scenarios.users {
+        match &user.description {
+            Some(description) => println!("  - {} ({})", user.user_id, description),
+            None => println!("  - {}", user.user_id),
+        }
+    }
     
     Ok(())
 }
 
-async fn seed_users(app_state: &AppState) -> Result<()> {
-    let users = vec![
-        ("user_normal_123", "normal@example.com", 150.0, vec!["groceries", "gas"]),
-        ("user_frequent_456", "frequent@example.com", 500.0, vec!["electronics", "clothing"]),
-        ("user_fraud_789", "fraud@example.com", 200.0, vec!["electronics"]),
-        ("user_traveler_321", "traveler@example.com", 300.0, vec!["hotels", "restaurants"]),
-        ("user_business_654", "business@example.com", 800.0, vec!["software", "office"]),
-    ];
-    
-    for (user_id, email, avg_amount, categories) in users {
+async fn seed_users(app_state: &AppState, users: &[SeedUser]) -> Result<()> {
+    for user in users {
         sqlx::query(
             r#"
             INSERT INTO users (user_id, email, average_transaction_amount, common_categories)
@@ -47,10 +179,10 @@ async fn seed_users(app_state: &AppState) -> Result<()> {
                 common_categories = EXCLUDED.common_categories
             "#
         )
-        .bind(user_id)
-        .bind(email)
-        .bind(avg_amount)
-        .bind(categories)
+        .bind(&user.user_id)
+        .bind(&user.email)
+        .bind(user.average_transaction_amount)
+        .bind(&user.common_categories)
         .execute(&app_state.pool)
         .await?;
     }
@@ -58,24 +190,11 @@ async fn seed_users(app_state: &AppState) -> Result<()> {
     Ok(())
 }
 
-async fn seed_merchants(app_state: &AppState) -> Result<()> {
-    let merchants = vec![
-        ("BestBuy Electronics", "electronics", 0.05),
-        ("Amazon Online", "general", 0.02),
-        ("Shell Gas Station", "gas", 0.01),
-        ("Walmart Superstore", "groceries", 0.03),
-        ("ScamElectronics Inc", "electronics", 0.45), // High fraud rate!
-        ("Apple Store", "electronics", 0.01),
-        ("Starbucks Coffee", "food", 0.02),
-        ("Hilton Hotel", "hotels", 0.03),
-        ("SuspiciousShop", "general", 0.38), // High fraud rate!
-        ("Target Store", "retail", 0.02),
-    ];
-    
-    for (name, category, fraud_rate) in merchants {
-        let embedding = crate::embedding::generate_embedding_internal(
+async fn seed_merchants(app_state: &AppState, merchants: &[SeedMerchant]) -> Result<()> {
+    for merchant in merchants {
+        let embedding = crate::embedding::embed_document(
             app_state,
-            format!("Merchant: {} Category: {}", name, category)
+            &format!("Merchant: {} Category: {}", merchant.name, merchant.category)
         ).await
         .map_err(|e| anyhow::anyhow!("Embedding generation failed: {}", e))?;
         
@@ -83,91 +202,69 @@ async fn seed_merchants(app_state: &AppState) -> Result<()> {
         
         sqlx::query(
             r#"
-            INSERT INTO merchants (merchant_name, category, fraud_rate, merchant_embedding)
-            VALUES ($1, $2, $3, $4::vector)
+            INSERT INTO merchants (merchant_name, acquirer_merchant_id, category, fraud_rate, merchant_embedding)
+            VALUES ($1, $2, $3, $4, $5::vector)
             ON CONFLICT (merchant_name) DO UPDATE
-            SET fraud_rate = EXCLUDED.fraud_rate,
+            SET acquirer_merchant_id = EXCLUDED.acquirer_merchant_id,
+                fraud_rate = EXCLUDED.fraud_rate,
                 merchant_embedding = EXCLUDED.merchant_embedding,
                 last_updated = NOW()
             "#
         )
-        .bind(name)
-        .bind(category)
-        .bind(fraud_rate)
+        .bind(&merchant.name)
+        .bind(&merchant.merchant_id)
+        .bind(&merchant.category)
+        .bind(merchant.fraud_rate)
         .bind(embedding_str)
         .execute(&app_state.pool)
         .await?;
     }
+
+    // Embeddings and fraud rates changed
+    app_state.merchant_similarity.invalidate().await;
     
     Ok(())
 }
 
-async fn seed_transactions(app_state: &AppState) -> Result<()> {
-    let scenarios = vec![
-        // Normal user transactions
-        ("user_normal_123", "Walmart Superstore", 85.50, "groceries", false, 5),
-        ("user_normal_123", "Shell Gas Station", 45.00, "gas", false, 10),
-        ("user_normal_123", "Starbucks Coffee", 12.50, "food", false, 15),
-        ("user_normal_123", "Target Store", 65.00, "retail", false, 20),
-        
-        // Frequent buyer - normal high spending
-        ("user_frequent_456", "Amazon Online", 250.00, "general", false, 2),
-        ("user_frequent_456", "BestBuy Electronics", 899.99, "electronics", false, 7),
-        ("user_frequent_456", "Apple Store", 1299.00, "electronics", false, 12),
-        ("user_frequent_456", "Target Store", 450.00, "retail", false, 18),
-        
-        // Fraud cases - high risk
-        ("user_fraud_789", "ScamElectronics Inc", 2500.00, "electronics", true, 1),
-        ("user_fraud_789", "SuspiciousShop", 1800.00, "general", true, 3),
-        ("user_fraud_789", "ScamElectronics Inc", 3200.00, "electronics", true, 8),
-        
-        // Business traveler - legitimate high transactions
-        ("user_traveler_321", "Hilton Hotel", 450.00, "hotels", false, 4),
-        ("user_traveler_321", "Starbucks Coffee", 15.00, "food", false, 6),
-        ("user_traveler_321", "Shell Gas Station", 60.00, "gas", false, 9),
-        ("user_traveler_321", "Hilton Hotel", 520.00, "hotels", false, 14),
-        
-        // Business user - high value legitimate
-        ("user_business_654", "Apple Store", 2500.00, "electronics", false, 11),
-        ("user_business_654", "Amazon Online", 800.00, "general", false, 16),
-        ("user_business_654", "BestBuy Electronics", 1200.00, "electronics", false, 19),
-        
-        // Mixed scenarios for testing
-        ("user_normal_123", "Apple Store", 2000.00, "electronics", false, 25), // Unusual high amount but legit
-        ("user_frequent_456", "SuspiciousShop", 1500.00, "general", true, 22), // Caught fraud
-        ("user_traveler_321", "ScamElectronics Inc", 2800.00, "electronics", true, 27), // Travel + fraud
-    ];
-    
-    for (user_id, merchant, amount, category, is_fraud, days_ago) in scenarios {
+async fn seed_transactions(app_state: &AppState, transactions: &[SeedTransaction]) -> Result<()> {
+    for transaction in transactions {
         let txn_id = uuid::Uuid::new_v4().to_string();
-        let timestamp = Utc::now() - Duration::days(days_ago);
+        let timestamp = Utc::now() - Duration::days(transaction.days_ago);
         
-        let description = format!("{} spending ${} at {} in {}", user_id, amount, merchant, category);
-        let embedding = crate::embedding::generate_embedding_internal(app_state, description).await
+        let description = format!(
+            "{} spending ${} at {} in {}",
+            transaction.user_id, transaction.amount, transaction.merchant, transaction.category
+        );
+        let embedding = crate::embedding::embed_document(app_state, &description).await
             .map_err(|e| anyhow::anyhow!("Embedding generation failed: {}", e))?;
         let embedding_str = crate::embedding::embedding_to_pgvector(&embedding);
         
         // Random device fingerprint
         let device_fp = format!("fp_{}", &txn_id[..8]);
         
-        sqlx::query(
+        sqlx::query(&format!(
             r#"
             INSERT INTO transactions (
                 transaction_id, user_id, merchant, amount,
                 merchant_category, timestamp, fraud_label,
-                transaction_embedding, payment_method, device_fingerprint
+                transaction_embedding, payment_method, device_fingerprint,
+                merchant_id
+            )
+            VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8::{vector}, 'credit_card', $9,
+                (SELECT acquirer_merchant_id FROM merchants WHERE merchant_name = $3)
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8::vector, 'credit_card', $9)
             ON CONFLICT (transaction_id) DO NOTHING
-            "#
-        )
+            "#,
+            vector = app_state.config.embedding_precision.sql_type()
+        ))
         .bind(&txn_id)
-        .bind(user_id)
-        .bind(merchant)
-        .bind(amount)
-        .bind(category)
+        .bind(&transaction.user_id)
+        .bind(&transaction.merchant)
+        .bind(transaction.amount)
+        .bind(&transaction.category)
         .bind(timestamp)
-        .bind(is_fraud)
+        .bind(transaction.fraud)
         .bind(embedding_str)
         .bind(device_fp)
         .execute(&app_state.pool)
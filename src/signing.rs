@@ -0,0 +1,86 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::models::transaction::Decision;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Only algorithm currently issued
+pub const ALGORITHM: &str = "HMAC-SHA256";
+
+/// Proof that a decision came from this service unaltered.
+///
+/// The signed message is `transaction_id.DECISION.issued_at`, so a
+/// downstream service holding the shared secret can recompute it from the
+/// response fields with [`verify_decision`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionSignature {
+    pub algorithm: String,
+    /// Unix seconds at signing
+    pub issued_at: i64,
+    /// Lowercase hex MAC
+    pub value: String,
+}
+
+fn message(transaction_id: &str, decision: Decision, issued_at: i64) -> String {
+    format!("{}.{}.{}", transaction_id, decision, issued_at)
+}
+
+fn mac(secret: &[u8]) -> HmacSha256 {
+    // HMAC takes keys of any length
+    HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length")
+}
+
+/// Sign a decision with the shared secret
+pub fn sign_decision(
+    secret: &[u8],
+    transaction_id: &str,
+    decision: Decision,
+    issued_at: i64,
+) -> DecisionSignature {
+    let mut mac = mac(secret);
+    mac.update(message(transaction_id, decision, issued_at).as_bytes());
+    let value = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    DecisionSignature {
+        algorithm: ALGORITHM.to_string(),
+        issued_at,
+        value,
+    }
+}
+
+/// Check a signature received alongside a decision, in constant time.
+/// Freshness (how old `issued_at` may be) is left to the caller.
+pub fn verify_decision(
+    secret: &[u8],
+    transaction_id: &str,
+    decision: Decision,
+    signature: &DecisionSignature,
+) -> bool {
+    if signature.algorithm != ALGORITHM {
+        return false;
+    }
+    let Some(expected) = decode_hex(&signature.value) else {
+        return false;
+    };
+
+    let mut mac = mac(secret);
+    mac.update(message(transaction_id, decision, signature.issued_at).as_bytes());
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.len() % 2 == 1 {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,66 @@
+use arc_swap::ArcSwap;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+use crate::{
+    analysis::FraudAnalyzer,
+    circuit_breaker::CircuitBreakers,
+    config::Config,
+    db::{merchant_similarity::SimilarRiskyMerchants, population::PopulationStats, user_overrides::UserOverrideCache},
+    embedding::EmbeddingProvider,
+    kill_switch::KillSwitch,
+    models::transaction::AnalysisResult,
+    signing,
+};
+
+/// Shared state handed to every handler and agent
+#[derive(Clone)]
+pub struct AppState {
+    /// Primary, for writes
+    pub pool: PgPool,
+    /// Read replica for agent and reporting queries, the primary when no
+    /// replica is configured
+    pub read_pool: PgPool,
+    /// Embedding backend used by the agents, the seeder and the debug
+    /// endpoints, swapped for a new model by `/api/admin/reload-model`
+    pub embedder: Arc<ArcSwap<Box<dyn EmbeddingProvider>>>,
+    /// Bounds concurrent embedding computations, separately from the
+    /// database pools
+    pub embedding_permits: Arc<Semaphore>,
+    /// Cached per-category amount distribution across all users
+    pub population: Arc<PopulationStats>,
+    /// Cached per-merchant matches of similar high-fraud merchants
+    pub merchant_similarity: Arc<SimilarRiskyMerchants>,
+    /// Cached per-user overrides, reloaded when edited
+    pub user_overrides: Arc<UserOverrideCache>,
+    /// Configuration loaded at startup
+    pub config: Arc<Config>,
+    /// Orchestrator built from `config`, swapped for a new one when
+    /// `CONFIG_FILE` changes
+    pub analyzer: Arc<ArcSwap<FraudAnalyzer>>,
+    /// Operator override of final decisions, switchable at runtime
+    pub kill_switch: Arc<KillSwitch>,
+    /// Per-query-type breakers around the agents' database queries
+    pub breakers: Arc<CircuitBreakers>,
+}
+
+impl AppState {
+    /// Apply the operator override, then sign what will be returned. Every
+    /// path that hands a fresh decision to a caller goes through this
+    pub fn finalize_decision(&self, result: &mut AnalysisResult) {
+        // Agents still ran and their scores are reported, only the
+        // decision is replaced
+        self.kill_switch.apply(result);
+
+        // Signed after any override, the signature covers what is returned
+        if let Some(secret) = &self.config.decision_signing_secret {
+            result.signature = Some(signing::sign_decision(
+                secret.as_bytes(),
+                &result.transaction_id,
+                result.decision,
+                chrono::Utc::now().timestamp(),
+            ));
+        }
+    }
+}
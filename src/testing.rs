@@ -0,0 +1,415 @@
+//! Reproducible transactions and database fixtures for exercising the agents.
+//!
+//! Only compiled with the `test-utils` feature. Everything here is
+//! deterministic: ids are derived from the names passed in and timestamps
+//! are fixed offsets from the time the fixture is inserted, so a fixture
+//! always produces the same history relative to "now".
+//!
+//! Tests that touch the database connect to `TEST_DATABASE_URL`, which must
+//! already have `sql/schema.sql` applied. `DATABASE_URL` is deliberately not
+//! used: the fixtures insert and delete rows.
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Semaphore;
+
+use crate::{
+    AppState,
+    analysis::FraudAnalyzer,
+    circuit_breaker::CircuitBreakers,
+    config::{Config, ConfigSource},
+    db::{merchant_similarity::SimilarRiskyMerchants, population::PopulationStats, user_overrides::UserOverrideCache},
+    embedding::{EmbeddingFuture, EmbeddingProvider},
+    kill_switch::KillSwitch,
+    models::transaction::{Location, Transaction},
+};
+
+/// Builds a `Transaction` with sensible defaults, override only what a test cares about
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    transaction: Transaction,
+}
+
+impl TransactionBuilder {
+    pub fn new(transaction_id: &str, user_id: &str) -> Self {
+        Self {
+            transaction: Transaction {
+                transaction_id: transaction_id.to_string(),
+                user_id: user_id.to_string(),
+                amount: 50.0,
+                merchant: "Corner Grocery".to_string(),
+                merchant_id: None,
+                merchant_category: "groceries".to_string(),
+                location: Some(new_york()),
+                timestamp: Utc::now(),
+                payment_method: "credit_card".to_string(),
+                device_fingerprint: format!("device_{}", user_id),
+                metadata: None,
+            },
+        }
+    }
+
+    pub fn amount(mut self, amount: f64) -> Self {
+        self.transaction.amount = amount;
+        self
+    }
+
+    pub fn merchant(mut self, merchant: &str, category: &str) -> Self {
+        self.transaction.merchant = merchant.to_string();
+        self.transaction.merchant_category = category.to_string();
+        self
+    }
+
+    pub fn merchant_id(mut self, merchant_id: &str) -> Self {
+        self.transaction.merchant_id = Some(merchant_id.to_string());
+        self
+    }
+
+    pub fn location(mut self, location: Option<Location>) -> Self {
+        self.transaction.location = location;
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.transaction.timestamp = timestamp;
+        self
+    }
+
+    /// Timestamp `ago` before now
+    pub fn ago(self, ago: Duration) -> Self {
+        self.timestamp(Utc::now() - ago)
+    }
+
+    pub fn payment_method(mut self, payment_method: &str) -> Self {
+        self.transaction.payment_method = payment_method.to_string();
+        self
+    }
+
+    pub fn device(mut self, device_fingerprint: &str) -> Self {
+        self.transaction.device_fingerprint = device_fingerprint.to_string();
+        self
+    }
+
+    pub fn metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.transaction.metadata = Some(metadata);
+        self
+    }
+
+    pub fn build(self) -> Transaction {
+        self.transaction
+    }
+}
+
+pub fn new_york() -> Location {
+    Location {
+        city: "New York".to_string(),
+        country: "US".to_string(),
+        lat: 40.7128,
+        lon: -74.0060,
+        accuracy_km: None,
+    }
+}
+
+pub fn lagos() -> Location {
+    Location {
+        city: "Lagos".to_string(),
+        country: "NG".to_string(),
+        lat: 6.5244,
+        lon: 3.3792,
+        accuracy_km: None,
+    }
+}
+
+/// Insert a user, leaving existing rows alone
+pub async fn insert_user(pool: &PgPool, user_id: &str, average_amount: f64, categories: &[&str]) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO users (user_id, average_transaction_amount, common_categories)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id) DO NOTHING
+        "#,
+    )
+    .bind(user_id)
+    .bind(average_amount)
+    .bind(categories.iter().map(|c| c.to_string()).collect::<Vec<_>>())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Insert a merchant with a known fraud rate and, optionally, the
+/// acquirer's id for it, replacing any existing one
+pub async fn insert_merchant(
+    pool: &PgPool,
+    merchant_name: &str,
+    acquirer_merchant_id: Option<&str>,
+    category: &str,
+    fraud_rate: f64,
+    total_transactions: i32,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO merchants (merchant_name, acquirer_merchant_id, category, fraud_rate, total_transactions)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (merchant_name) DO UPDATE
+        SET acquirer_merchant_id = EXCLUDED.acquirer_merchant_id,
+            category = EXCLUDED.category,
+            fraud_rate = EXCLUDED.fraud_rate,
+            total_transactions = EXCLUDED.total_transactions
+        "#,
+    )
+    .bind(merchant_name)
+    .bind(acquirer_merchant_id)
+    .bind(category)
+    .bind(fraud_rate)
+    .bind(total_transactions)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Insert a historical transaction (no embedding, no analysis result)
+pub async fn insert_transaction(pool: &PgPool, transaction: &Transaction, fraud_label: Option<bool>) -> Result<()> {
+    insert_user(pool, &transaction.user_id, 0.0, &[]).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO transactions (
+            transaction_id, user_id, amount, merchant, merchant_category,
+            location, timestamp, payment_method, device_fingerprint, fraud_label, metadata,
+            merchant_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        ON CONFLICT (transaction_id) DO NOTHING
+        "#,
+    )
+    .bind(&transaction.transaction_id)
+    .bind(&transaction.user_id)
+    .bind(transaction.amount)
+    .bind(&transaction.merchant)
+    .bind(&transaction.merchant_category)
+    .bind(transaction.location.as_ref().map(serde_json::to_value).transpose()?)
+    .bind(transaction.timestamp)
+    .bind(&transaction.payment_method)
+    .bind(&transaction.device_fingerprint)
+    .bind(fraud_label)
+    .bind(&transaction.metadata)
+    .bind(&transaction.merchant_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// A user with 20 legitimate ~$50 grocery purchases, one every other day
+/// from the same device in New York
+pub async fn seed_normal_user(pool: &PgPool, user_id: &str) -> Result<Vec<Transaction>> {
+    insert_user(pool, user_id, 50.0, &["groceries"]).await?;
+
+    let mut history = Vec::new();
+    for i in 0..20 {
+        let transaction = TransactionBuilder::new(&format!("{}_hist_{:02}", user_id, i), user_id)
+            .amount(45.0 + (i % 5) as f64 * 2.5)
+            .ago(Duration::days(2 * (i + 1)))
+            .build();
+        insert_transaction(pool, &transaction, Some(false)).await?;
+        history.push(transaction);
+    }
+
+    Ok(history)
+}
+
+/// A user whose recent history is confirmed fraud: large electronics
+/// purchases in quick succession
+pub async fn seed_fraud_user(pool: &PgPool, user_id: &str) -> Result<Vec<Transaction>> {
+    insert_user(pool, user_id, 50.0, &["groceries"]).await?;
+
+    let mut history = Vec::new();
+    for i in 0..5 {
+        let transaction = TransactionBuilder::new(&format!("{}_fraud_{:02}", user_id, i), user_id)
+            .amount(1500.0 + i as f64 * 100.0)
+            .merchant("QuickElectronics Online", "electronics")
+            .payment_method("online")
+            .ago(Duration::minutes(10 * (i + 1)))
+            .build();
+        insert_transaction(pool, &transaction, Some(true)).await?;
+        history.push(transaction);
+    }
+
+    Ok(history)
+}
+
+/// `users` distinct users transacting from one device within the last day,
+/// enough to trip the network agent's device-sharing ring check when above 3
+pub async fn seed_shared_device_ring(pool: &PgPool, device_fingerprint: &str, users: usize) -> Result<Vec<Transaction>> {
+    let mut ring = Vec::new();
+    for i in 0..users {
+        let user_id = format!("ring_{}_{:02}", device_fingerprint, i);
+        let transaction = TransactionBuilder::new(&format!("{}_txn", user_id), &user_id)
+            .amount(200.0)
+            .merchant("GiftCards4U", "gift_cards")
+            .device(device_fingerprint)
+            .location(Some(lagos()))
+            .ago(Duration::hours(i as i64 + 1))
+            .build();
+        insert_transaction(pool, &transaction, None).await?;
+        ring.push(transaction);
+    }
+
+    Ok(ring)
+}
+
+/// Remove merchants inserted with `insert_merchant`
+pub async fn delete_merchants(pool: &PgPool, merchant_names: &[&str]) -> Result<()> {
+    sqlx::query("DELETE FROM merchants WHERE merchant_name = ANY($1)")
+        .bind(merchant_names)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Remove everything the fixtures inserted for these users
+pub async fn cleanup_users(pool: &PgPool, user_ids: &[String]) -> Result<()> {
+    sqlx::query("DELETE FROM analysis_results WHERE user_id = ANY($1)")
+        .bind(user_ids)
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM transactions WHERE user_id = ANY($1)")
+        .bind(user_ids)
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM users WHERE user_id = ANY($1)")
+        .bind(user_ids)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Settings given inline, for building a `Config` without the environment
+pub struct MapSource(HashMap<String, String>);
+
+impl MapSource {
+    pub fn new(settings: &[(&str, &str)]) -> Self {
+        Self(
+            settings
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl ConfigSource for MapSource {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0.get(key).cloned()
+    }
+}
+
+/// Database the fixtures are written to: `TEST_DATABASE_URL`, never the
+/// service's `DATABASE_URL`
+pub fn test_database_url() -> Result<String> {
+    std::env::var("TEST_DATABASE_URL")
+        .context("TEST_DATABASE_URL must point at a database with sql/schema.sql applied")
+}
+
+pub async fn test_pool() -> Result<PgPool> {
+    let url = test_database_url()?;
+    Ok(PgPool::connect(&url).await?)
+}
+
+/// The default configuration with `settings` on top
+pub fn test_config(database_url: &str, settings: &[(&str, &str)]) -> Result<Config> {
+    let mut all = vec![("DATABASE_URL", database_url)];
+    all.extend_from_slice(settings);
+    Ok(Config::from_source(&MapSource::new(&all))?)
+}
+
+/// Deterministic stand-in for the embedding model: a unit vector derived
+/// from the text, so tests don't need the model files
+pub struct StubEmbedder;
+
+/// Dimension of the `vector` columns in the schema
+const STUB_DIMENSION: usize = 768;
+
+impl EmbeddingProvider for StubEmbedder {
+    fn embed<'a>(&'a self, text: &'a str) -> EmbeddingFuture<'a> {
+        Box::pin(async move {
+            let seed = text
+                .bytes()
+                .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+            let raw: Vec<f32> = (0..STUB_DIMENSION)
+                .map(|i| ((seed.rotate_left(i as u32 % 64) >> 40) as f32 / (1u64 << 24) as f32) - 0.5)
+                .collect();
+            let length = raw.iter().map(|x| x * x).sum::<f32>().sqrt().max(f32::EPSILON);
+            Ok::<_, String>(raw.iter().map(|x| x / length).collect())
+        })
+    }
+
+    fn name(&self) -> &str {
+        "stub"
+    }
+}
+
+/// State for calling the agents and handlers directly, wired like `main`
+/// but with [`StubEmbedder`] in place of the model
+pub fn test_state(pool: PgPool, config: Config) -> Result<AppState> {
+    Ok(AppState {
+        read_pool: pool.clone(),
+        pool,
+        embedder: Arc::new(ArcSwap::from_pointee(Box::new(StubEmbedder) as Box<dyn EmbeddingProvider>)),
+        embedding_permits: Arc::new(Semaphore::new(config.embedding_max_concurrency)),
+        population: Arc::new(PopulationStats::new(
+            std::time::Duration::from_secs(config.population_stats_ttl_secs),
+            config.category_taxonomy.clone(),
+        )),
+        merchant_similarity: Arc::new(SimilarRiskyMerchants::new(std::time::Duration::from_secs(
+            config.merchant_similarity_ttl_secs,
+        ))),
+        user_overrides: Arc::new(UserOverrideCache::new(std::time::Duration::from_secs(
+            config.user_overrides_ttl_secs,
+        ))),
+        analyzer: Arc::new(ArcSwap::from_pointee(FraudAnalyzer::new(&config)?)),
+        kill_switch: Arc::new(KillSwitch::new(config.override_mode)),
+        breakers: Arc::new(CircuitBreakers::new(config.circuit_breaker.clone())),
+        config: Arc::new(config),
+    })
+}
+
+/// Pool, configuration and state for a test against `TEST_DATABASE_URL`,
+/// owning the users the test inserts
+pub struct TestDatabase {
+    pub pool: PgPool,
+    pub config: Config,
+    pub state: AppState,
+    user_ids: Vec<String>,
+}
+
+impl TestDatabase {
+    /// Connect with `settings` on top of the defaults, clearing whatever an
+    /// earlier, interrupted run left for `user_ids`
+    pub async fn connect(user_ids: &[String], settings: &[(&str, &str)]) -> Result<Self> {
+        let pool = test_pool().await?;
+        let config = test_config(&test_database_url()?, settings)?;
+        let state = test_state(pool.clone(), config.clone())?;
+        cleanup_users(&pool, user_ids).await?;
+        Ok(Self {
+            pool,
+            config,
+            state,
+            user_ids: user_ids.to_vec(),
+        })
+    }
+
+    /// Remove the users' rows again. Call before asserting, so a failing
+    /// test doesn't leave them behind
+    pub async fn cleanup(&self) -> Result<()> {
+        cleanup_users(&self.pool, &self.user_ids).await
+    }
+}
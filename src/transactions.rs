@@ -0,0 +1,179 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState,
+    db::{
+        persistence,
+        transactions::{self, AnalysisRecord, StoredTransaction, TransactionPatch},
+    },
+    error::ApiError,
+    models::transaction::{AnalysisResult, Decision},
+};
+
+/// Fields a PATCH may set, everything else about a transaction is fixed
+const MUTABLE_FIELDS: &[&str] = &[
+    "fraud_label",
+    "location",
+    "merchant_category",
+    "payment_method",
+    "device_fingerprint",
+    "metadata",
+];
+/// What the transaction is, changing these would make it a different one
+const IMMUTABLE_FIELDS: &[&str] = &["transaction_id", "user_id", "amount", "merchant", "timestamp"];
+
+/// Results returned when the request doesn't set a limit
+const DEFAULT_SEARCH_LIMIT: i64 = 50;
+/// Upper bound on `limit`
+const MAX_SEARCH_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct TransactionSearch {
+    #[serde(default)]
+    pub user_id: Option<String>,
+    /// Matches transactions whose metadata contains these keys and values
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+/// POST /api/transactions/search - stored transactions by user and metadata, newest first
+pub async fn search_transactions(
+    State(state): State<AppState>,
+    Json(search): Json<TransactionSearch>,
+) -> Result<Json<Vec<StoredTransaction>>, ApiError> {
+    if matches!(&search.metadata, Some(metadata) if !metadata.is_object()) {
+        return Err(ApiError::BadRequest("metadata must be a JSON object".to_string()));
+    }
+    let limit = search.limit.unwrap_or(DEFAULT_SEARCH_LIMIT);
+    if !(1..=MAX_SEARCH_LIMIT).contains(&limit) {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_SEARCH_LIMIT
+        )));
+    }
+
+    let rows = transactions::search_transactions(
+        &state.read_pool,
+        search.user_id.as_deref(),
+        search.metadata.as_ref(),
+        limit,
+    )
+    .await?;
+
+    Ok(Json(rows))
+}
+
+/// GET /api/transactions/{transaction_id}/history - every scoring of a
+/// transaction in chronological order, with the model version behind each
+pub async fn transaction_history(
+    State(state): State<AppState>,
+    Path(transaction_id): Path<String>,
+) -> Result<Json<Vec<AnalysisRecord>>, ApiError> {
+    // From the primary, so an analysis just returned is already listed
+    let history = transactions::fetch_analysis_history(&state.pool, &transaction_id).await?;
+    if history.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "No analyses stored for transaction '{}'",
+            transaction_id
+        )));
+    }
+
+    Ok(Json(history))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PatchParams {
+    /// Re-run the analysis on the updated transaction and store the result
+    #[serde(default)]
+    pub rescore: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatchResponse {
+    pub transaction: StoredTransaction,
+    /// Decision stored before the update
+    pub previous_decision: Option<Decision>,
+    /// New analysis, when rescoring was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<AnalysisResult>,
+}
+
+/// PATCH /api/transactions/{transaction_id} - fill in data that arrived
+/// after the decision (fraud label, corrected location, ...), optionally
+/// rescoring with `?rescore=true`
+pub async fn patch_transaction(
+    State(state): State<AppState>,
+    Path(transaction_id): Path<String>,
+    Query(params): Query<PatchParams>,
+    Json(body): Json<serde_json::Map<String, serde_json::Value>>,
+) -> Result<Json<PatchResponse>, ApiError> {
+    for field in body.keys() {
+        if IMMUTABLE_FIELDS.contains(&field.as_str()) {
+            return Err(ApiError::BadRequest(format!("{} can't be changed", field)));
+        }
+        if !MUTABLE_FIELDS.contains(&field.as_str()) {
+            return Err(ApiError::BadRequest(format!(
+                "Unknown field '{}', expected one of {}",
+                field,
+                MUTABLE_FIELDS.join(", ")
+            )));
+        }
+    }
+    let patch: TransactionPatch = serde_json::from_value(serde_json::Value::Object(body))
+        .map_err(|e| ApiError::BadRequest(format!("Invalid patch: {}", e)))?;
+    if let Some(location) = &patch.location {
+        location.validate().map_err(ApiError::BadRequest)?;
+    }
+    if matches!(&patch.metadata, Some(metadata) if !metadata.is_object()) {
+        return Err(ApiError::BadRequest("metadata must be a JSON object".to_string()));
+    }
+
+    let stored = transactions::update_transaction(&state.pool, &transaction_id, &patch)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Transaction '{}' not found", transaction_id)))?;
+    let previous_decision = persistence::latest_decision(&state.pool, &transaction_id, &stored.user_id).await?;
+    tracing::info!("✏️ Updated transaction {}", transaction_id);
+
+    // Scored against the primary, the replica may not have the update yet
+    let analysis = if params.rescore {
+        let transaction = stored.to_transaction();
+        let mut result = state
+            .analyzer
+            .load_full()
+            .analyze(&state.pool, &state, &transaction)
+            .await?;
+        let scored = result.clone();
+        state.finalize_decision(&mut result);
+        let override_decision = result.overridden_decision.map(|_| result.decision);
+        persistence::save_analysis(
+            &state.pool,
+            &transaction,
+            &scored,
+            override_decision,
+            state.config.embedding_precision,
+        )
+        .await?;
+        result.persisted = true;
+        tracing::info!(
+            "🔁 Rescored {}: {:?} -> {}",
+            transaction_id,
+            previous_decision,
+            result.decision
+        );
+        Some(result)
+    } else {
+        None
+    };
+
+    Ok(Json(PatchResponse {
+        transaction: stored,
+        previous_decision,
+        analysis,
+    }))
+}
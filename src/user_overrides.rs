@@ -0,0 +1,86 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    db::user_overrides::{self, UserOverride},
+    error::ApiError,
+    models::transaction::Decision,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct UserOverrideRequest {
+    /// Decision returned for every transaction of the user, without scoring
+    #[serde(default)]
+    pub action: Option<Decision>,
+    /// Multiplier on the user's aggregate score, e.g. 0.5 or 2.0
+    #[serde(default)]
+    pub risk_multiplier: Option<f64>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// GET /api/admin/user-overrides - every user with special handling
+pub async fn list_user_overrides(State(state): State<AppState>) -> Result<Json<Vec<UserOverride>>, ApiError> {
+    Ok(Json(user_overrides::list_overrides(&state.pool).await?))
+}
+
+/// PUT /api/admin/user-overrides/{user_id} - force a decision or scale the
+/// score for one user
+pub async fn set_user_override(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Json(request): Json<UserOverrideRequest>,
+) -> Result<Json<UserOverride>, ApiError> {
+    if user_id.trim().is_empty() {
+        return Err(ApiError::BadRequest("user_id must not be empty".to_string()));
+    }
+    if request.action.is_none() && request.risk_multiplier.is_none() {
+        return Err(ApiError::BadRequest("action or risk_multiplier is required".to_string()));
+    }
+    if let Some(multiplier) = request.risk_multiplier
+        && (!multiplier.is_finite() || multiplier < 0.0)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "risk_multiplier must be a non-negative number, got {}",
+            multiplier
+        )));
+    }
+
+    let entry = user_overrides::set_override(
+        &state.pool,
+        &user_id,
+        request.action,
+        request.risk_multiplier,
+        request.reason.as_deref(),
+    )
+    .await?;
+    state.user_overrides.invalidate().await;
+    tracing::warn!(
+        "👤 User override for {}: action {:?}, multiplier {:?} ({:?})",
+        entry.user_id,
+        entry.action,
+        entry.risk_multiplier,
+        entry.reason
+    );
+
+    Ok(Json(entry))
+}
+
+/// DELETE /api/admin/user-overrides/{user_id} - back to uniform scoring
+pub async fn remove_user_override(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !user_overrides::remove_override(&state.pool, &user_id).await? {
+        return Err(ApiError::NotFound(format!("User '{}' has no override", user_id)));
+    }
+    state.user_overrides.invalidate().await;
+    tracing::warn!("User override removed: {}", user_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
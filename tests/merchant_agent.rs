@@ -0,0 +1,92 @@
+#![cfg(feature = "test-utils")]
+
+use chrono::Duration;
+use FraudsWarn::{
+    agents::merchant::MerchantAgent,
+    models::transaction::ReasonCode,
+    testing::{self, TestDatabase, TransactionBuilder},
+};
+
+#[tokio::test]
+async fn merchant_with_many_transactions_is_not_new() -> anyhow::Result<()> {
+    let user_id = "fixture_merchant_live".to_string();
+    let db = TestDatabase::connect(&[user_id.clone()], &[]).await?;
+    let agent = MerchantAgent::with_config(db.config.merchant.clone());
+
+    // Neither has total_transactions populated, as after seeding
+    let (busy, quiet) = ("Fixture Busy Deli", "Fixture Quiet Deli");
+    testing::insert_merchant(&db.pool, busy, None, "groceries", 0.01, 0).await?;
+    testing::insert_merchant(&db.pool, quiet, None, "groceries", 0.01, 0).await?;
+    for i in 0..25 {
+        let transaction = TransactionBuilder::new(&format!("{}_busy_{:02}", user_id, i), &user_id)
+            .merchant(busy, "groceries")
+            .ago(Duration::days(i + 1))
+            .build();
+        testing::insert_transaction(&db.pool, &transaction, Some(false)).await?;
+    }
+    for i in 0..2 {
+        let transaction = TransactionBuilder::new(&format!("{}_quiet_{:02}", user_id, i), &user_id)
+            .merchant(quiet, "groceries")
+            .ago(Duration::days(i + 1))
+            .build();
+        testing::insert_transaction(&db.pool, &transaction, Some(false)).await?;
+    }
+
+    let at_busy = TransactionBuilder::new("fixture_merchant_live_busy_txn", &user_id)
+        .merchant(busy, "groceries")
+        .build();
+    let at_quiet = TransactionBuilder::new("fixture_merchant_live_quiet_txn", &user_id)
+        .merchant(quiet, "groceries")
+        .build();
+    let busy_score = agent.analyze(&db.pool, &db.state, &at_busy).await;
+    let quiet_score = agent.analyze(&db.pool, &db.state, &at_quiet).await;
+    db.cleanup().await?;
+    testing::delete_merchants(&db.pool, &[busy, quiet]).await?;
+
+    let is_new = |reasons: &[FraudsWarn::models::transaction::Reason]| {
+        reasons.iter().any(|r| r.code == ReasonCode::NewMerchant)
+    };
+    let busy_score = busy_score?;
+    assert!(!is_new(&busy_score.reasons), "25 transactions flagged new: {:?}", busy_score.reasons);
+    let quiet_score = quiet_score?;
+    assert!(is_new(&quiet_score.reasons), "2 transactions not flagged new: {:?}", quiet_score.reasons);
+    Ok(())
+}
+
+#[tokio::test]
+async fn renamed_merchant_is_matched_by_acquirer_id() -> anyhow::Result<()> {
+    let user_id = "fixture_merchant_acquirer".to_string();
+    let db = TestDatabase::connect(&[user_id.clone()], &[]).await?;
+    let agent = MerchantAgent::with_config(db.config.merchant.clone());
+
+    let (on_record, peer) = ("Fixture Acquirer Old Name", "Fixture Acquirer Peer");
+    let category = "fixture_acquirer_category";
+    testing::insert_merchant(&db.pool, on_record, Some("fixture-acq-1"), category, 0.4, 50).await?;
+    testing::insert_merchant(&db.pool, peer, None, category, 0.02, 50).await?;
+
+    let renamed = TransactionBuilder::new("fixture_merchant_acquirer_renamed", &user_id)
+        .merchant("Fixture Acquirer New Name", category)
+        .merchant_id("fixture-acq-1")
+        .build();
+    // Same name as the merchant on record, but another acquirer's merchant
+    let namesake = TransactionBuilder::new("fixture_merchant_acquirer_namesake", &user_id)
+        .merchant(on_record, category)
+        .merchant_id("fixture-acq-2")
+        .build();
+    let renamed_score = agent.analyze(&db.pool, &db.state, &renamed).await;
+    let namesake_score = agent.analyze(&db.pool, &db.state, &namesake).await;
+    db.cleanup().await?;
+    testing::delete_merchants(&db.pool, &[on_record, peer]).await?;
+
+    let details = renamed_score?.details;
+    assert_eq!(details["matched_by"], "merchant_id", "{}", details);
+    assert_eq!(details["matched_merchant"], on_record, "{}", details);
+    assert_eq!(details["merchant_fraud_rate"], 0.4, "{}", details);
+    // The merchant itself stays out of its category baseline under its new name
+    assert_eq!(details["category_merchants"], 1, "{}", details);
+
+    let details = namesake_score?.details;
+    assert!(details["matched_merchant"].is_null(), "{}", details);
+    assert_eq!(details["category_merchants"], 2, "{}", details);
+    Ok(())
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "test-utils")]
+
+use chrono::Duration;
+use FraudsWarn::{
+    agents::network::NetworkAgent,
+    models::transaction::ReasonCode,
+    testing::{self, TestDatabase, TransactionBuilder},
+};
+
+#[tokio::test]
+async fn busy_merchant_with_many_legitimate_users_is_not_a_ring() -> anyhow::Result<()> {
+    // 30 regulars with a month of grocery history each, a handful of them
+    // shopping in the last hour like on any other day
+    let users: Vec<String> = (0..30).map(|i| format!("fixture_coord_{:02}", i)).collect();
+    let user_id = "fixture_coord_current".to_string();
+    let all_users: Vec<String> = users.iter().cloned().chain([user_id.clone()]).collect();
+    let db = TestDatabase::connect(&all_users, &[]).await?;
+    let agent = NetworkAgent::with_config(db.config.network.clone());
+    for (i, user) in users.iter().enumerate() {
+        testing::seed_normal_user(&db.pool, user).await?;
+        if i < 5 {
+            let recent = TransactionBuilder::new(&format!("{}_recent", user), user)
+                .ago(Duration::minutes(5 * (i as i64 + 1)))
+                .build();
+            testing::insert_transaction(&db.pool, &recent, Some(false)).await?;
+        }
+    }
+
+    // Already stored, as when a transaction is retried or re-scored
+    let transaction = TransactionBuilder::new("fixture_coord_current_txn", &user_id).build();
+    testing::insert_transaction(&db.pool, &transaction, None).await?;
+
+    let score = agent.analyze(&db.pool, &db.state, &transaction).await;
+    db.cleanup().await?;
+
+    let score = score?;
+    assert_eq!(score.details["fraud_ring_detected"], false, "{}", score.details);
+    assert!(
+        !score.reasons.iter().any(|r| r.code == ReasonCode::CoordinatedMerchantActivity),
+        "{:?}",
+        score.reasons
+    );
+    let coordinated = score.details["coordinated_transactions"].as_i64().unwrap();
+    let threshold = score.details["coordination_threshold"].as_i64().unwrap();
+    assert!(coordinated <= threshold, "{} distinct users against a threshold of {}", coordinated, threshold);
+    Ok(())
+}
+
+#[tokio::test]
+async fn coordination_counts_users_of_a_renamed_merchant_by_acquirer_id() -> anyhow::Result<()> {
+    // Other users shopped there minutes ago, under the merchant's old name
+    let users: Vec<String> = (0..3).map(|i| format!("fixture_coord_acq_{:02}", i)).collect();
+    let user_id = "fixture_coord_acq_current".to_string();
+    let all_users: Vec<String> = users.iter().cloned().chain([user_id.clone()]).collect();
+    let db = TestDatabase::connect(&all_users, &[]).await?;
+    let agent = NetworkAgent::with_config(db.config.network.clone());
+    for (i, user) in users.iter().enumerate() {
+        let earlier = TransactionBuilder::new(&format!("{}_earlier", user), user)
+            .merchant("Fixture Ring Old Name", "electronics")
+            .merchant_id("fixture-acq-ring")
+            .ago(Duration::minutes(i as i64 + 1))
+            .build();
+        testing::insert_transaction(&db.pool, &earlier, None).await?;
+    }
+
+    let transaction = TransactionBuilder::new("fixture_coord_acq_current_txn", &user_id)
+        .merchant("Fixture Ring New Name", "electronics")
+        .merchant_id("fixture-acq-ring")
+        .build();
+    let score = agent.analyze(&db.pool, &db.state, &transaction).await;
+    db.cleanup().await?;
+
+    let score = score?;
+    assert_eq!(score.details["coordinated_transactions"], 3, "{}", score.details);
+    Ok(())
+}
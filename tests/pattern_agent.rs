@@ -0,0 +1,30 @@
+#![cfg(feature = "test-utils")]
+
+use FraudsWarn::{
+    agents::pattern::{PatternAgent, PatternConfig},
+    testing::{self, TestDatabase, TransactionBuilder},
+};
+
+#[tokio::test]
+async fn five_times_the_average_amount_scores_at_least_0_3() -> anyhow::Result<()> {
+    let user_id = "fixture_pattern_5x";
+    let db = TestDatabase::connect(&[user_id.to_string()], &[]).await?;
+    // Amount and category only, the stub embedder says nothing about similarity
+    let agent = PatternAgent::with_config(PatternConfig {
+        lightweight: true,
+        ..db.config.pattern.clone()
+    });
+
+    testing::seed_normal_user(&db.pool, user_id).await?;
+
+    // The history averages $50 in groceries
+    let transaction = TransactionBuilder::new("fixture_pattern_5x_txn", user_id)
+        .amount(250.0)
+        .build();
+    let score = agent.analyze(&db.pool, &db.state, &transaction).await;
+    db.cleanup().await?;
+
+    let score = score?;
+    assert!(score.risk_score >= 0.3, "pattern score {} for a 5x amount", score.risk_score);
+    Ok(())
+}
@@ -0,0 +1,128 @@
+#![cfg(feature = "test-utils")]
+//! Every guarded write path refuses with a 403 in read-only mode.
+//!
+//! The pool points at a database that doesn't exist and is never connected
+//! to: a write that got past its guard would fail with a connection error
+//! instead of `Forbidden`. The mode is process-wide, so these live in their
+//! own test binary. The fraud-rate recomputation named in the original
+//! request has no write path in the tree and is not covered.
+
+use std::collections::HashMap;
+
+use axum::{Json, extract::State, http::StatusCode};
+use FraudsWarn::{
+    AppState,
+    db::{challenges::ChallengeOutcome, fork::ForkManager, persistence, read_only},
+    embedding::EmbeddingPrecision,
+    error::ApiError,
+    feedback::{self, ChallengeFeedbackRequest},
+    models::transaction::{AgentScores, AnalysisResult, Decision, DecisionSource, RiskBand, Transaction},
+    seed_data,
+    testing::{self, TransactionBuilder},
+};
+use sqlx::postgres::PgPoolOptions;
+
+const UNREACHABLE_URL: &str = "postgres://read-only-test@127.0.0.1:1/never_connected";
+
+fn read_only_state() -> AppState {
+    read_only::set_read_only(true);
+    let pool = PgPoolOptions::new().connect_lazy(UNREACHABLE_URL).unwrap();
+    let config = testing::test_config(UNREACHABLE_URL, &[("READ_ONLY", "true")]).unwrap();
+    testing::test_state(pool, config).unwrap()
+}
+
+fn assert_forbidden(err: ApiError) {
+    assert_eq!(err.status(), StatusCode::FORBIDDEN, "{}", err);
+    assert!(err.to_string().contains("Read-only mode"), "{}", err);
+}
+
+fn approved(transaction: &Transaction) -> AnalysisResult {
+    AnalysisResult {
+        transaction_id: transaction.transaction_id.clone(),
+        decision: Decision::Approve,
+        decision_source: DecisionSource::Threshold,
+        overridden_decision: None,
+        risk_score: 0.1,
+        raw_aggregate_score: 0.1,
+        risk_band: RiskBand::Low,
+        confidence: 0.9,
+        data_sufficiency: 1.0,
+        payment_method_risk: 0.0,
+        model_version: "test".to_string(),
+        latency_ms: 0,
+        agent_scores: AgentScores {
+            pattern: 0.1,
+            anomaly: 0.1,
+            geographic: 0.1,
+            merchant: 0.1,
+            network: 0.1,
+        },
+        fraud_ring_detected: false,
+        fraud_ring_severity: None,
+        hysteresis_applied: false,
+        cooldown_applied: false,
+        user_risk_multiplier: None,
+        reasoning: String::new(),
+        agent_reasons: Vec::new(),
+        partial: false,
+        timed_out_agents: Vec::new(),
+        excluded_agents: Vec::new(),
+        agent_status: HashMap::new(),
+        persisted: false,
+        signature: None,
+        embedding: None,
+        applied_weights: None,
+    }
+}
+
+#[tokio::test]
+async fn persistence_is_refused() {
+    let state = read_only_state();
+    let transaction = TransactionBuilder::new("read_only_txn", "read_only_user").build();
+
+    let err = persistence::save_analysis(
+        &state.pool,
+        &transaction,
+        &approved(&transaction),
+        None,
+        EmbeddingPrecision::default(),
+    )
+    .await
+    .unwrap_err();
+    assert_forbidden(err.into());
+}
+
+#[tokio::test]
+async fn feedback_is_refused() {
+    let state = read_only_state();
+
+    let err = feedback::record_challenge_outcome(
+        State(state),
+        Json(ChallengeFeedbackRequest {
+            transaction_id: "read_only_txn".to_string(),
+            outcome: ChallengeOutcome::Passed,
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert_forbidden(err);
+}
+
+#[tokio::test]
+async fn fork_creation_is_refused() {
+    let state = read_only_state();
+
+    let err = ForkManager::new(state.pool.clone(), UNREACHABLE_URL.to_string())
+        .create_fork("read_only_fork")
+        .await
+        .unwrap_err();
+    assert_forbidden(err.into());
+}
+
+#[tokio::test]
+async fn seeding_is_refused() {
+    let state = read_only_state();
+
+    let err = seed_data::seed_database(&state).await.unwrap_err();
+    assert_forbidden(err.into());
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "test-utils")]
+
+use FraudsWarn::{
+    analysis::FraudAnalyzer,
+    testing::{self, TestDatabase, TransactionBuilder},
+};
+
+#[tokio::test]
+async fn sequential_and_concurrent_runs_aggregate_the_same() -> anyhow::Result<()> {
+    let user_id = "fixture_sequential_agents".to_string();
+    let db = TestDatabase::connect(&[user_id.clone()], &[]).await?;
+    let sequential_config =
+        testing::test_config(&testing::test_database_url()?, &[("ANALYSIS_SEQUENTIAL_AGENTS", "true")])?;
+    testing::seed_normal_user(&db.pool, &user_id).await?;
+    let transaction = TransactionBuilder::new("fixture_sequential_agents_txn", &user_id)
+        .amount(480.0)
+        .location(Some(testing::lagos()))
+        .build();
+
+    let concurrent = FraudAnalyzer::new(&db.config)?.analyze(&db.pool, &db.state, &transaction).await;
+    let sequential = FraudAnalyzer::new(&sequential_config)?.analyze(&db.pool, &db.state, &transaction).await;
+    db.cleanup().await?;
+
+    let (concurrent, sequential) = (concurrent?, sequential?);
+    assert_eq!(
+        serde_json::to_value(&sequential.agent_scores)?,
+        serde_json::to_value(&concurrent.agent_scores)?
+    );
+    assert_eq!(sequential.risk_score, concurrent.risk_score);
+    assert_eq!(sequential.raw_aggregate_score, concurrent.raw_aggregate_score);
+    assert_eq!(sequential.decision, concurrent.decision);
+    assert_eq!(sequential.agent_status, concurrent.agent_status);
+    assert_eq!(sequential.excluded_agents, concurrent.excluded_agents);
+    Ok(())
+}